@@ -0,0 +1,173 @@
+//! Bloom filters of block identifiers, used to tell a serving node
+//! which blocks the requester already has.
+
+use chain_core::property::Serialize;
+
+use std::{cmp, f64::consts::LN_2};
+
+// The largest number of hash functions accepted in a filter received
+// from a peer, which bounds the cost of checking an identifier.
+const MAX_HASH_COUNT: u32 = 64;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A compact probabilistic set of serialized block identifiers.
+///
+/// A filter can tell for certain that an identifier has not been
+/// inserted, but it may report an identifier that has not been inserted
+/// as contained. The rate of such false positives depends on the number
+/// of bits, the number of hash functions, and the number of inserted
+/// identifiers.
+///
+/// The bit positions of an identifier are derived from the 64-bit
+/// FNV-1a hash of its serialized form, as specified for the
+/// `BloomFilter` message in node.proto, so that the filters built by
+/// a node are understood by the peers it sends them to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    hash_count: u32,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter with `bit_len` bits, rounded up to
+    /// whole bytes, and `hash_count` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit_len` or `hash_count` is 0, or if `hash_count`
+    /// exceeds 64.
+    pub fn new(bit_len: usize, hash_count: u32) -> Self {
+        assert!(bit_len > 0, "the bit length of a filter should not be 0");
+        assert!(
+            hash_count > 0 && hash_count <= MAX_HASH_COUNT,
+            "the number of hash functions should be between 1 and {}",
+            MAX_HASH_COUNT
+        );
+        BloomFilter {
+            bits: vec![0; (bit_len + 7) / 8],
+            hash_count,
+        }
+    }
+
+    /// Creates an empty filter sized to hold `capacity` identifiers
+    /// with a false positive rate of about `false_positive_rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_rate` is not between 0 and 1,
+    /// exclusive.
+    pub fn with_rate(capacity: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "the false positive rate should be between 0 and 1"
+        );
+        let n = cmp::max(capacity, 1) as f64;
+        let bit_len = (-n * false_positive_rate.ln() / (LN_2 * LN_2)).ceil();
+        let hash_count = (bit_len / n * LN_2).round() as u32;
+        BloomFilter::new(
+            cmp::max(bit_len as usize, 8),
+            cmp::min(cmp::max(hash_count, 1), MAX_HASH_COUNT),
+        )
+    }
+
+    /// Restores a filter from its bits and its number of hash functions,
+    /// as received from a peer.
+    ///
+    /// Returns `None` if there are no bits, or if the number of hash
+    /// functions is 0 or exceeds 64.
+    pub fn from_parts(bits: Vec<u8>, hash_count: u32) -> Option<Self> {
+        if bits.is_empty() || hash_count == 0 || hash_count > MAX_HASH_COUNT {
+            return None;
+        }
+        Some(BloomFilter { bits, hash_count })
+    }
+
+    /// Returns the bits of the filter, the bit at position `b` being
+    /// `1 << (b % 8)` in byte `b / 8`.
+    pub fn bits(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// Returns the number of hash functions of the filter.
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// Inserts the serialized identifier of a block.
+    pub fn insert_bytes(&mut self, id: &[u8]) {
+        for pos in self.positions(id) {
+            self.bits[pos / 8] |= 1 << (pos % 8);
+        }
+    }
+
+    /// Checks whether the serialized identifier of a block may have
+    /// been inserted.
+    pub fn contains_bytes(&self, id: &[u8]) -> bool {
+        self.positions(id)
+            .all(|pos| self.bits[pos / 8] & (1 << (pos % 8)) != 0)
+    }
+
+    /// Inserts the identifier of a block in its serialized form.
+    pub fn insert<I: Serialize>(&mut self, id: &I) -> Result<(), I::Error> {
+        let id = id.serialize_as_vec()?;
+        self.insert_bytes(&id);
+        Ok(())
+    }
+
+    /// Checks whether the identifier of a block may have been inserted.
+    pub fn contains<I: Serialize>(&self, id: &I) -> Result<bool, I::Error> {
+        let id = id.serialize_as_vec()?;
+        Ok(self.contains_bytes(&id))
+    }
+
+    // The bit positions of the identifier, by double hashing:
+    // (h1 + i * h2) mod m for each hash function i, with h1 and h2
+    // the lower and the upper halves of the FNV-1a hash.
+    fn positions(&self, id: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = fnv1a(id);
+        let h1 = hash & 0xffff_ffff;
+        let h2 = hash >> 32;
+        let bit_len = self.bits.len() as u64 * 8;
+        (0..u64::from(self.hash_count))
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bit_len) as usize)
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserted_ids_are_contained() {
+        let mut filter = BloomFilter::with_rate(100, 0.01);
+        for i in 0u32..100 {
+            filter.insert_bytes(&i.to_be_bytes());
+        }
+        for i in 0u32..100 {
+            assert!(filter.contains_bytes(&i.to_be_bytes()));
+        }
+        let false_positives = (100u32..1100)
+            .filter(|i| filter.contains_bytes(&i.to_be_bytes()))
+            .count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn filter_is_restored_from_its_parts() {
+        let mut filter = BloomFilter::new(64, 3);
+        filter.insert_bytes(b"block");
+        let restored = BloomFilter::from_parts(filter.bits().to_vec(), filter.hash_count());
+        assert_eq!(restored.as_ref(), Some(&filter));
+        assert!(restored.unwrap().contains_bytes(b"block"));
+        assert_eq!(BloomFilter::from_parts(Vec::new(), 3), None);
+        assert_eq!(BloomFilter::from_parts(vec![0; 8], 0), None);
+    }
+}
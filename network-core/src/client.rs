@@ -1,6 +1,9 @@
 //! Abstractions for the client-side network interface of a blockchain node.
 
 pub mod block;
+pub mod gossip;
+pub mod peer;
+pub mod transaction;
 
 mod error;
 
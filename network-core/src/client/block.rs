@@ -1,4 +1,5 @@
 use super::Error;
+use crate::bloom::BloomFilter;
 
 use chain_core::property::{Block, HasHeader};
 
@@ -13,8 +14,35 @@ pub trait BlockService<T: Block> {
     /// of the current chain tip as known by the serving node.
     type TipFuture: Future<Item = (T::Id, T::Date), Error = Error>;
 
+    /// Requests the identifier and the date of the current chain tip.
+    ///
+    /// When other fields of the tip header are needed, e.g. to make
+    /// a fork choice decision, use `HeaderService::tip_header` to
+    /// retrieve the full header in a single request.
+    ///
+    /// A freshly bootstrapped node, whose chain consists only of
+    /// the genesis block, reports the genesis block as its tip:
+    /// the future resolves to the identifier of the genesis block and
+    /// the date at epoch 0, slot 0. This is not an error; the chain
+    /// of a node is never empty.
     fn tip(&mut self) -> Self::TipFuture;
 
+    /// The type of asynchronous futures returned by method `chain_length`.
+    ///
+    /// The future resolves to the height of the current chain tip
+    /// as known by the serving node.
+    type ChainLengthFuture: Future<Item = u64, Error = Error>;
+
+    /// Requests the length of the chain of the serving node, as the
+    /// height of its tip; the genesis block is at height 0.
+    ///
+    /// This is cheaper than `tip` for comparing the chains of many
+    /// peers, as no block identifiers or dates are transferred.
+    /// The length is a snapshot taken when the node processes the
+    /// request: the chain may have grown, or switched to a fork,
+    /// by the time the response is acted upon.
+    fn chain_length(&mut self) -> Self::ChainLengthFuture;
+
     /// The type of an asynchronous stream that provides blocks in
     /// response to method `pull_blocks_to_tip`.
     type PullBlocksToTipStream: Stream<Item = T, Error = Error>;
@@ -25,8 +53,68 @@ pub trait BlockService<T: Block> {
     /// implementation to produce a server-streamed response.
     type PullBlocksToTipFuture: Future<Item = Self::PullBlocksToTipStream, Error = Error>;
 
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the serving node, up to the tip of its chain.
+    ///
+    /// The `from` list holds checkpoints known to the caller, e.g. the tips
+    /// of the branches it has synchronized, conventionally listed from
+    /// the most recent. The serving node starts streaming after the block
+    /// that is the latest in its chain among those it recognizes,
+    /// regardless of its position in the list, so the list may include
+    /// blocks the node does not know. An empty list requests the chain
+    /// from the genesis block. If the node recognizes none of the blocks,
+    /// the request fails with an error of kind `ErrorKind::NotFound`.
     fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture;
 
+    /// The type of an asynchronous stream that provides blocks in
+    /// response to method `pull_blocks`.
+    type PullBlocksStream: Stream<Item = T, Error = Error>;
+
+    /// The type of asynchronous futures returned by method `pull_blocks`.
+    ///
+    /// The future resolves to a stream that will be used by the protocol
+    /// implementation to produce a server-streamed response.
+    type PullBlocksFuture: Future<Item = Self::PullBlocksStream, Error = Error>;
+
+    /// Retrieves the blocks following the latest of the blocks identified
+    /// by `from` that is an ancestor of the block identified by `to`,
+    /// up to and including the latter.
+    ///
+    /// An empty `from` list requests the chain from the genesis block.
+    /// If `to` is not known to the serving node or does not descend from
+    /// any of the `from` blocks, the request fails with an error of kind
+    /// `ErrorKind::NotFound`.
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> Self::PullBlocksFuture;
+
+    /// Retrieves the blocks of the chain in descending order, starting
+    /// from the tip and going back until a block identified in `to`
+    /// is reached.
+    ///
+    /// The stream ends without an error before the first block whose
+    /// identifier is in `to`; that block is not included. If none of
+    /// the `to` blocks is in the chain of the serving node, the stream
+    /// ends with the genesis block.
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> Self::PullBlocksFuture;
+
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the serving node, up to the tip of its chain, letting
+    /// the node skip the blocks whose identifiers are in `known`.
+    ///
+    /// This is `pull_blocks_to_tip` with a hint that saves the transfer
+    /// of blocks the caller already has, e.g. when it synchronizes from
+    /// several peers with overlapping chains. The serving node may
+    /// ignore the filter and send all of the blocks. Because the filter
+    /// has false positives, the node may also skip blocks that the caller
+    /// does not have: the stream is not guaranteed to be a contiguous
+    /// chain, and the caller has to reconcile the received blocks with
+    /// the blocks it has, e.g. by retrieving the missing parents with
+    /// `get_blocks`, before it can rely on having the whole chain.
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Self::PullBlocksFuture;
+
     /// The type of an asynchronous stream that provides blocks in
     /// response to method `get_blocks`.
     type GetBlocksStream: Stream<Item = T, Error = Error>;
@@ -36,11 +124,126 @@ pub trait BlockService<T: Block> {
     /// The future resolves to a stream that will be used by the protocol
     /// implementation to produce a server-streamed response.
     type GetBlocksFuture: Future<Item = Self::GetBlocksStream, Error = Error>;
+
+    /// Retrieves the blocks identified by `ids`.
+    ///
+    /// The blocks are streamed in the order of their identifiers in the
+    /// request. If a block is not known to the serving node, the stream
+    /// yields the blocks preceding it in the request and then fails
+    /// with an error.
+    fn get_blocks(&mut self, ids: &[T::Id]) -> Self::GetBlocksFuture;
+
+    /// The type of asynchronous futures returned by method `get_block`.
+    type GetBlockFuture: Future<Item = T, Error = Error>;
+
+    /// Retrieves the block identified by `id`.
+    ///
+    /// This is a single request-response exchange, without the stream
+    /// that `get_blocks` would return for one identifier. If the block
+    /// is not known to the serving node, the future fails with an error
+    /// of kind `ErrorKind::NotFound`.
+    fn get_block(&mut self, id: T::Id) -> Self::GetBlockFuture;
+
+    /// The type of an asynchronous stream that provides blocks in
+    /// response to method `get_blocks_pipelined`.
+    type GetBlocksPipelinedStream: Stream<Item = T, Error = Error>;
+
+    /// The type of asynchronous futures returned by method
+    /// `get_blocks_pipelined`.
+    ///
+    /// The future resolves to a stream of blocks sent by the remote node.
+    type GetBlocksPipelinedFuture: Future<Item = Self::GetBlocksPipelinedStream, Error = Error>;
+
+    /// Retrieves the blocks identified by the items of the stream `ids`
+    /// in a single exchange, sending each identifier as soon as it is
+    /// taken from the stream.
+    ///
+    /// The response stream yields exactly one block for each identifier,
+    /// in the order of the identifiers, so the blocks can be matched with
+    /// the submitted identifiers by position. The serving node can send
+    /// a block while more identifiers are being submitted, which avoids
+    /// a round trip for each block as with repeated calls to `get_block`.
+    /// If a block is not known to the serving node, the stream yields
+    /// the blocks preceding it and then fails with an error of kind
+    /// `ErrorKind::NotFound`. An error from `ids` aborts the request.
+    fn get_blocks_pipelined<S>(&mut self, ids: S) -> Self::GetBlocksPipelinedFuture
+    where
+        S: Stream<Item = T::Id, Error = Error> + Send + 'static;
+
+    /// Retrieves `count` consecutive blocks of the chain, starting from
+    /// the block at height `from_height`; the genesis block is at height 0.
+    ///
+    /// The serving node caps the number of blocks it sends: requesting
+    /// more blocks than the cap results in a stream of up to the cap,
+    /// not an error. The gRPC server implementation in `network-grpc`
+    /// sends at most 500 blocks per request. The stream also ends early
+    /// when it reaches the tip of the chain; if `from_height` is beyond
+    /// the tip, the stream is empty.
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture;
+
+    /// The type of asynchronous futures returned by method
+    /// `block_id_at_height`.
+    type BlockIdAtHeightFuture: Future<Item = T::Id, Error = Error>;
+
+    /// Retrieves the identifier of the block at `height` in the chain
+    /// of the serving node; the genesis block is at height 0.
+    ///
+    /// The block itself is not transferred, so this is a cheap way to
+    /// compare the chains of several nodes at the same height. If the
+    /// chain is shorter than `height`, the future fails with an error
+    /// of kind `ErrorKind::NotFound`.
+    fn block_id_at_height(&mut self, height: u64) -> Self::BlockIdAtHeightFuture;
+
+    /// The type of asynchronous futures returned by method `has_blocks`.
+    type HasBlocksFuture: Future<Item = Vec<bool>, Error = Error>;
+
+    /// Checks which of the blocks identified by `ids` are known to
+    /// the serving node, without retrieving them.
+    ///
+    /// The future resolves to a vector with an element for each
+    /// identifier, in the order of `ids`, that is true if the block
+    /// is known.
+    fn has_blocks(&mut self, ids: &[T::Id]) -> Self::HasBlocksFuture;
+
+    /// The type of an asynchronous stream that provides blocks in
+    /// response to method `block_subscription`.
+    type BlockSubscriptionStream: Stream<Item = T, Error = Error>;
+
+    /// The type of asynchronous futures returned by method `block_subscription`.
+    ///
+    /// The future resolves to a stream of blocks appended to the chain
+    /// of the serving node.
+    type BlockSubscriptionFuture: Future<Item = Self::BlockSubscriptionStream, Error = Error>;
+
+    /// Subscribes to blocks appended to the chain of the serving node.
+    ///
+    /// The blocks are streamed in the order they are appended to the chain.
+    /// The stream does not end under normal operation; it ends only when
+    /// the serving node closes the subscription. A transport failure
+    /// is reported as an error of kind `ErrorKind::Rpc`.
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture;
+
+    /// The type of asynchronous futures returned by method `upload_blocks`.
+    ///
+    /// The future resolves to the number of blocks accepted
+    /// by the serving node.
+    type UploadBlocksFuture: Future<Item = u64, Error = Error>;
+
+    /// Uploads blocks to the serving node, e.g. to seed a fresh peer
+    /// with a range of blocks.
+    ///
+    /// The blocks are taken from the stream and sent as fast as the
+    /// serving node receives them, so the stream is not buffered in memory
+    /// beyond what the transport flow control allows. An error from
+    /// the stream aborts the upload.
+    fn upload_blocks<S>(&mut self, blocks: S) -> Self::UploadBlocksFuture
+    where
+        S: Stream<Item = T, Error = Error> + Send + 'static;
 }
 
 /// Interface for the blockchain node service responsible for
 /// providing access to block headers.
-pub trait HeaderService<T: HasHeader> {
+pub trait HeaderService<T: Block + HasHeader> {
     /// The type of an asynchronous stream that provides block headers in
     /// response to method `get_headers`.
     type GetHeadersStream: Stream<Item = T::Header, Error = Error>;
@@ -50,4 +253,76 @@ pub trait HeaderService<T: HasHeader> {
     /// The future resolves to a stream that will be used by the protocol
     /// implementation to produce a server-streamed response.
     type GetHeadersFuture: Future<Item = Self::GetHeadersStream, Error = Error>;
+
+    /// Retrieves the headers of the blocks identified by `ids`.
+    ///
+    /// An empty list of identifiers results in an empty stream.
+    /// If any of the blocks is not known to the serving node,
+    /// the stream fails with an error rather than skipping the block.
+    fn get_headers(&mut self, ids: &[T::Id]) -> Self::GetHeadersFuture;
+
+    /// The type of asynchronous futures returned by method `tip_header`.
+    ///
+    /// The future resolves to the header of the current chain tip
+    /// as known by the serving node.
+    type TipHeaderFuture: Future<Item = T::Header, Error = Error>;
+
+    /// Requests the header of the current chain tip.
+    ///
+    /// This provides everything returned by `BlockService::tip`
+    /// and the rest of the header fields in one round trip.
+    fn tip_header(&mut self) -> Self::TipHeaderFuture;
+
+    /// The type of an asynchronous stream that provides block headers in
+    /// response to method `tip_headers`.
+    type TipHeadersStream: Stream<Item = T::Header, Error = Error>;
+
+    /// The type of asynchronous futures returned by method `tip_headers`.
+    ///
+    /// The future resolves to a stream that will be used by the protocol
+    /// implementation to produce a server-streamed response.
+    type TipHeadersFuture: Future<Item = Self::TipHeadersStream, Error = Error>;
+
+    /// Retrieves the headers of the last `count` blocks of the chain,
+    /// starting from the tip and going back towards the genesis block.
+    ///
+    /// The serving node caps the number of headers it sends, so the
+    /// stream may end before `count` headers have been received even
+    /// if the chain is long enough. The gRPC server implementation
+    /// in `network-grpc` sends at most 2160 headers, the maximum
+    /// rollback depth (security parameter `k`) of the Cardano mainnet.
+    fn tip_headers(&mut self, count: u32) -> Self::TipHeadersFuture;
+
+    /// The type of an asynchronous stream that provides block headers in
+    /// response to method `pull_headers_to_tip`.
+    type PullHeadersStream: Stream<Item = T::Header, Error = Error>;
+
+    /// The type of asynchronous futures returned by method `pull_headers_to_tip`.
+    ///
+    /// The future resolves to a stream that will be used by the protocol
+    /// implementation to produce a server-streamed response.
+    type PullHeadersFuture: Future<Item = Self::PullHeadersStream, Error = Error>;
+
+    /// Retrieves the headers of the blocks following the latest of
+    /// the `from` blocks known to the serving node, up to the tip of
+    /// its chain.
+    ///
+    /// This is the header counterpart of `BlockService::pull_blocks_to_tip`,
+    /// with the same interpretation of the `from` checkpoints: an empty
+    /// list requests the headers from the genesis block, and the request
+    /// fails with an error of kind `ErrorKind::NotFound` if the node
+    /// recognizes none of the blocks.
+    /// Downloading the header chain first lets the caller plan the
+    /// validation of a long chain before fetching the block bodies.
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> Self::PullHeadersFuture;
+
+    /// The type of asynchronous futures returned by method `push_headers`.
+    ///
+    /// The future resolves when the serving node has acknowledged
+    /// the headers. If the node rejects a header, the future fails
+    /// with an error, possibly before all headers have been sent.
+    type PushHeadersFuture: Future<Item = (), Error = Error>;
+
+    /// Announces headers of new blocks to the serving node.
+    fn push_headers(&mut self, headers: &[T::Header]) -> Self::PushHeadersFuture;
 }
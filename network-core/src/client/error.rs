@@ -1,10 +1,11 @@
-use std::{error, fmt};
+use std::{error, fmt, time::Duration};
 
 /// Represents errors that can be returned by the node client implementation.
 #[derive(Debug)]
 pub struct Error {
     kind: ErrorKind,
     source: Box<dyn error::Error + Send + Sync>,
+    retry_after: Option<Duration>,
 }
 
 /// A list of general causes of client request errors.
@@ -17,6 +18,24 @@ pub enum ErrorKind {
     Format,
     /// An error with the protocol RPC call
     Rpc,
+    /// The request did not complete within the allotted time
+    Timeout,
+    /// The service is currently unavailable; the request may be retried
+    Unavailable,
+    /// The requested entity was not found
+    NotFound,
+    /// The serving node has run out of some resource, or the client
+    /// has exceeded a quota
+    ResourceExhausted,
+    /// The request lacks valid authentication credentials
+    Unauthenticated,
+    /// The serving node does not implement the requested method
+    Unimplemented,
+    /// The request has been canceled by the client
+    Canceled,
+    /// The client implementation has been used incorrectly,
+    /// e.g. a future was polled after it had completed
+    Internal,
 }
 
 impl Error {
@@ -27,12 +46,37 @@ impl Error {
         Error {
             kind,
             source: source.into(),
+            retry_after: None,
+        }
+    }
+
+    /// Attaches a hint from the serving node on how long the client
+    /// should wait before retrying the request.
+    pub fn with_retry_after(self, delay: Duration) -> Self {
+        Error {
+            retry_after: Some(delay),
+            ..self
         }
     }
 
     pub fn kind(&self) -> ErrorKind {
         self.kind
     }
+
+    /// Returns the delay the serving node has asked the client to wait
+    /// before retrying the request, if the node has provided one.
+    ///
+    /// Such hints typically come with errors of kind
+    /// `ErrorKind::ResourceExhausted` or `ErrorKind::Unavailable`.
+    /// Callers implementing their own retry logic should wait
+    /// at least this long.
+    ///
+    /// Whether hints are available depends on the client implementation;
+    /// an implementation that receives them attaches them to its errors
+    /// with `with_retry_after`.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
 }
 
 impl error::Error for Error {
@@ -43,9 +87,19 @@ impl error::Error for Error {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.kind {
-            ErrorKind::Format => write!(f, "malformed payload received"),
-            ErrorKind::Rpc => write!(f, "protocol error"),
-        }
+        let msg = match self.kind {
+            ErrorKind::Format => "malformed payload received",
+            ErrorKind::Rpc => "protocol error",
+            ErrorKind::Timeout => "request timed out",
+            ErrorKind::Unavailable => "service unavailable",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::ResourceExhausted => "resource exhausted",
+            ErrorKind::Unauthenticated => "unauthenticated",
+            ErrorKind::Unimplemented => "not implemented",
+            ErrorKind::Canceled => "request canceled",
+            ErrorKind::Internal => "internal error",
+        };
+        // The source is returned by `source`, so it is not repeated here.
+        f.write_str(msg)
     }
 }
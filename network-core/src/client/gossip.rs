@@ -0,0 +1,22 @@
+use super::Error;
+use crate::peer::Peer;
+
+use futures::prelude::*;
+
+/// Interface for receiving gossip about other nodes from the peer.
+pub trait GossipService {
+    /// The type of an asynchronous stream that provides gossip entries
+    /// in response to method `peers`.
+    type PeersStream: Stream<Item = Peer, Error = Error>;
+
+    /// The type of asynchronous futures returned by method `peers`.
+    ///
+    /// The future resolves to a stream of gossip entries describing
+    /// other nodes known to the peer.
+    type PeersFuture: Future<Item = Self::PeersStream, Error = Error>;
+
+    /// Subscribes to the peer's gossip about other nodes, streaming
+    /// the address and node identifier of each node as the peer
+    /// learns about it.
+    fn peers(&mut self) -> Self::PeersFuture;
+}
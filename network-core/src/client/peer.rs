@@ -0,0 +1,22 @@
+use super::Error;
+use crate::peer::PeerInfo;
+
+use chain_core::property::Block;
+
+use futures::prelude::*;
+
+/// Interface for querying the state of the peer node itself.
+pub trait PeerService<T: Block> {
+    /// The type of asynchronous futures returned by method `ping`.
+    ///
+    /// The future resolves to the information provided by the peer
+    /// about itself.
+    type PingFuture: Future<Item = PeerInfo<T::Id>, Error = Error>;
+
+    /// Checks that the peer is alive, and retrieves its node identifier,
+    /// protocol version, and the genesis block of its blockchain.
+    ///
+    /// This is a lightweight request that can be used to weed out
+    /// peers serving a different blockchain before requesting blocks.
+    fn ping(&mut self) -> Self::PingFuture;
+}
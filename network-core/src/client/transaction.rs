@@ -0,0 +1,99 @@
+use super::Error;
+use crate::codes::{TransactionAcceptance, TransactionState};
+
+use chain_core::property::Transaction;
+
+use futures::prelude::*;
+
+/// An update on the state of a transaction, as streamed in response to
+/// `TransactionService::subscribe_transaction_status`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionStatusUpdate<Id> {
+    /// The identifier of the transaction.
+    pub id: Id,
+    /// The state the transaction has entered.
+    pub state: TransactionState,
+}
+
+/// The occupancy of the mempool of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MempoolStats {
+    /// The number of pending transactions.
+    pub count: u64,
+    /// The total serialized size of the pending transactions, in bytes.
+    pub bytes: u64,
+}
+
+/// Interface for the blockchain node service responsible for
+/// accepting transactions.
+pub trait TransactionService<T: Transaction> {
+    /// The type of asynchronous futures returned by method `submit_transactions`.
+    ///
+    /// The future resolves to the results of processing the submitted
+    /// transactions by the serving node, given in the same order as
+    /// the transactions in the request.
+    type SubmitTransactionsFuture: Future<Item = Vec<TransactionAcceptance>, Error = Error>;
+
+    /// Submits transactions to the serving node.
+    ///
+    /// Each transaction is processed individually, so some transactions
+    /// in a call may be accepted while others are rejected.
+    fn submit_transactions(&mut self, txs: &[T]) -> Self::SubmitTransactionsFuture;
+
+    /// The type of an asynchronous stream that provides transactions in
+    /// response to method `get_transactions`.
+    type GetTransactionsStream: Stream<Item = T, Error = Error>;
+
+    /// The type of asynchronous futures returned by method `get_transactions`.
+    ///
+    /// The future resolves to a stream of transactions sent by the
+    /// serving node.
+    type GetTransactionsFuture: Future<Item = Self::GetTransactionsStream, Error = Error>;
+
+    /// Retrieves the transactions identified by `ids`, such as pending
+    /// transactions in the mempool of the serving node.
+    ///
+    /// Unlike `BlockService::get_blocks`, transactions unknown to
+    /// the serving node are omitted from the stream rather than failing it.
+    fn get_transactions(&mut self, ids: &[T::Id]) -> Self::GetTransactionsFuture;
+
+    /// The type of an asynchronous stream that provides updates on
+    /// the state of transactions in response to method
+    /// `subscribe_transaction_status`.
+    type TransactionStatusStream: Stream<Item = TransactionStatusUpdate<T::Id>, Error = Error>;
+
+    /// The type of asynchronous futures returned by method
+    /// `subscribe_transaction_status`.
+    ///
+    /// The future resolves to a stream of updates pushed by
+    /// the serving node.
+    type TransactionStatusFuture: Future<Item = Self::TransactionStatusStream, Error = Error>;
+
+    /// Subscribes to updates on the state of the transactions
+    /// identified by `ids`, such as transactions submitted earlier.
+    ///
+    /// The stream starts with the current state of each transaction,
+    /// followed by an update whenever a transaction is included in
+    /// a block or rejected as the chain of the serving node advances.
+    /// The stream ends once every transaction has reached one of
+    /// the terminal states `TransactionState::InBlock` or
+    /// `TransactionState::Rejected`.
+    ///
+    /// A transaction unknown to the serving node is reported as
+    /// `TransactionState::Pending` and stays so until the node receives
+    /// it; there is no timeout, so a subscription to a transaction that
+    /// never reaches the node does not end. Callers should apply
+    /// a timeout of their own if that is a concern.
+    fn subscribe_transaction_status(&mut self, ids: &[T::Id]) -> Self::TransactionStatusFuture;
+
+    /// The type of asynchronous futures returned by method `mempool_size`.
+    type MempoolSizeFuture: Future<Item = MempoolStats, Error = Error>;
+
+    /// Requests the number and the total size of the transactions
+    /// pending in the mempool of the serving node.
+    ///
+    /// This is meant for throttling the submission of transactions
+    /// to a node whose mempool is filling up. The figures are
+    /// a snapshot that may be stale by the time they are received.
+    fn mempool_size(&mut self) -> Self::MempoolSizeFuture;
+}
@@ -5,9 +5,14 @@
 #[macro_use]
 extern crate prost_derive;
 
+pub mod bloom;
 pub mod client;
+pub mod peer;
 pub mod server;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 /// Common type definitions generated from protobuf.
 pub mod codes {
     include!(concat!(env!("OUT_DIR"), "/iohk.chain.codes.rs"));
@@ -0,0 +1,84 @@
+//! Information on blockchain nodes exchanged between peers.
+
+use std::{error, fmt, net::SocketAddr, num::ParseIntError, str::FromStr};
+
+/// Information on a node, reported to its peers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerInfo<I> {
+    /// Opaque identifier of the node.
+    pub node_id: Vec<u8>,
+    /// Version of the network protocol implemented by the node.
+    pub protocol_version: ProtocolVersion,
+    /// Identifier of the genesis block of the node's blockchain.
+    pub genesis_hash: I,
+}
+
+/// Gossip about another node, received from a peer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Peer {
+    /// Opaque identifier of the node.
+    pub node_id: Vec<u8>,
+    /// The address at which the node accepts connections.
+    pub address: SocketAddr,
+}
+
+/// Version of the network protocol, in the form `major.minor`.
+///
+/// Nodes with different major versions are not expected to interoperate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ProtocolVersion {
+    pub fn new(major: u32, minor: u32) -> Self {
+        ProtocolVersion { major, minor }
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = ParseProtocolVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '.');
+        let major = parts.next().unwrap();
+        let minor = parts.next().ok_or(ParseProtocolVersionError::Format)?;
+        let major = major.parse().map_err(ParseProtocolVersionError::Number)?;
+        let minor = minor.parse().map_err(ParseProtocolVersionError::Number)?;
+        Ok(ProtocolVersion { major, minor })
+    }
+}
+
+/// The error type for parsing a `ProtocolVersion` from a string.
+#[derive(Debug)]
+pub enum ParseProtocolVersionError {
+    Format,
+    Number(ParseIntError),
+}
+
+impl fmt::Display for ParseProtocolVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseProtocolVersionError::Format => {
+                write!(f, "protocol version is not in the major.minor format")
+            }
+            ParseProtocolVersionError::Number(e) => write!(f, "invalid version number: {}", e),
+        }
+    }
+}
+
+impl error::Error for ParseProtocolVersionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseProtocolVersionError::Format => None,
+            ParseProtocolVersionError::Number(e) => Some(e),
+        }
+    }
+}
@@ -3,6 +3,8 @@
 pub mod block;
 pub mod transaction;
 
+use crate::peer::PeerInfo;
+
 /// Interface to application logic of the blockchain node server.
 ///
 /// An implementation of a blockchain node implements this trait to
@@ -31,4 +33,8 @@ pub trait Node {
     /// Instantiates the transaction service,
     /// if supported by this node.
     fn transaction_service(&self) -> Option<Self::TransactionService>;
+
+    /// Returns the information on this node served to peers
+    /// in response to a ping.
+    fn peer_info(&self) -> PeerInfo<<Self::BlockService as block::BlockService>::BlockId>;
 }
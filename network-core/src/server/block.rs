@@ -24,6 +24,12 @@ pub trait BlockService {
     /// of the current chain tip as known by the serving node.
     type TipFuture: Future<Item = (Self::BlockId, Self::BlockDate), Error = BlockError>;
 
+    /// The type of asynchronous futures returned by method `chain_length`.
+    ///
+    /// The future resolves to the height of the current chain tip
+    /// as known by the serving node.
+    type ChainLengthFuture: Future<Item = u64, Error = BlockError>;
+
     /// The type of an asynchronous stream that provides blocks in
     /// response to method `get_blocks`.
     type GetBlocksStream: Stream<Item = Self::Block, Error = BlockError>;
@@ -34,6 +40,12 @@ pub trait BlockService {
     /// implementation to produce a server-streamed response.
     type GetBlocksFuture: Future<Item = Self::GetBlocksStream, Error = BlockError>;
 
+    /// The type of asynchronous futures returned by method `has_blocks`.
+    ///
+    /// The future resolves to a vector of flags telling whether each
+    /// of the requested blocks is known to the node.
+    type HasBlocksFuture: Future<Item = Vec<bool>, Error = BlockError>;
+
     /// The type of an asynchronous stream that provides blocks in
     /// response to method `pull_blocks_to_tip`.
     type PullBlocksToTipStream: Stream<Item = Self::Block, Error = BlockError>;
@@ -44,14 +56,80 @@ pub trait BlockService {
     /// implementation to produce a server-streamed response.
     type PullBlocksFuture: Future<Item = Self::PullBlocksToTipStream, Error = BlockError>;
 
+    /// The type of an asynchronous stream that provides blocks in
+    /// response to method `block_subscription`.
+    type BlockSubscriptionStream: Stream<Item = Self::Block, Error = BlockError>;
+
+    /// The type of asynchronous futures returned by method `block_subscription`.
+    ///
+    /// The future resolves to a stream that will be used by the protocol
+    /// implementation to produce a server-streamed response.
+    type BlockSubscriptionFuture: Future<Item = Self::BlockSubscriptionStream, Error = BlockError>;
+
+    /// Returns the identifier and the date of the current chain tip.
+    ///
+    /// If the chain consists only of the genesis block, the future
+    /// should resolve to the genesis block identifier and the date
+    /// at epoch 0, slot 0.
     fn tip(&mut self) -> Self::TipFuture;
+
+    /// Returns the length of the chain, as the height of the current
+    /// chain tip; the genesis block is at height 0.
+    fn chain_length(&mut self) -> Self::ChainLengthFuture;
+
+    /// Get the blocks identified by `ids`.
+    ///
+    /// The blocks should be streamed in the order of the identifiers
+    /// in the request. An unknown block identifier should fail the stream
+    /// after all blocks preceding it have been sent.
+    fn get_blocks(&mut self, ids: &[Self::BlockId]) -> Self::GetBlocksFuture;
+
+    /// Get `count` consecutive blocks of the chain in ascending order,
+    /// starting from the block at height `from_height`.
+    ///
+    /// The genesis block is at height 0. The stream should end at the tip
+    /// of the chain, and be empty if `from_height` is beyond the tip.
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture;
+
+    /// Check which of the blocks identified by `ids` are known to the node.
+    ///
+    /// The resulting vector should have an element for each identifier,
+    /// in the order of the request.
+    fn has_blocks(&mut self, ids: &[Self::BlockId]) -> Self::HasBlocksFuture;
+
+    /// Stream the blocks following the latest block in the chain
+    /// that is identified in `from`, up to the tip.
+    ///
+    /// Identifiers of unknown blocks in `from` should be ignored.
+    /// An empty `from` list designates the genesis block as the starting
+    /// point. If none of the `from` blocks is known, the implementation
+    /// should fail the request.
     fn pull_blocks_to_tip(&mut self, from: &[Self::BlockId]) -> Self::PullBlocksFuture;
 
+    /// Stream the blocks following the latest of the blocks identified
+    /// by `from` that is an ancestor of `to`, up to and including `to`.
+    ///
+    /// An empty `from` list designates the genesis block as the starting
+    /// point. If `to` is unknown or does not descend from any block in
+    /// `from`, the implementation should fail the request.
     fn pull_blocks_to(
         &mut self,
         from: &[Self::BlockId],
         to: &Self::BlockId,
     ) -> Self::PullBlocksFuture;
+
+    /// Stream the blocks of the chain in descending order, starting
+    /// from the tip.
+    ///
+    /// The stream should end before the first block identified in `to`,
+    /// or after the genesis block if the chain contains none of them.
+    fn pull_blocks_from_tip(&mut self, to: &[Self::BlockId]) -> Self::PullBlocksFuture;
+
+    /// Stream blocks as they are appended to the chain.
+    ///
+    /// The stream should remain open for as long as the node
+    /// serves the subscription.
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture;
 }
 
 /// Interface for the blockchain node service implementation responsible for
@@ -72,6 +150,24 @@ pub trait HeaderService {
     /// implementation to produce a server-streamed response.
     type GetHeadersFuture: Future<Item = Self::GetHeadersStream, Error = BlockError>;
 
+    /// The type of asynchronous futures returned by method `tip_header`.
+    ///
+    /// The future resolves to the header of the current chain tip
+    /// as known by the serving node.
+    type TipHeaderFuture: Future<Item = Self::Header, Error = BlockError>;
+
+    fn tip_header(&mut self) -> Self::TipHeaderFuture;
+
+    /// Get the headers of the last `count` blocks of the chain,
+    /// streamed from the tip backwards.
+    fn tip_headers(&mut self, count: u32) -> Self::GetHeadersFuture;
+
+    /// Get headers of the blocks identified by `ids`.
+    ///
+    /// The implementation should fail the stream with an error
+    /// for identifiers of blocks unknown to the node.
+    fn get_headers(&mut self, ids: &[Self::HeaderId]) -> Self::GetHeadersFuture;
+
     /// Get block headers between two dates.
     fn block_headers(
         &mut self,
@@ -79,7 +175,13 @@ pub trait HeaderService {
         to: &Self::HeaderId,
     ) -> Self::GetHeadersFuture;
 
-    // Stream blocks to the provided tip.
+    /// Stream the headers of the blocks following the latest block in
+    /// the chain that is identified in `from`, up to the tip.
+    ///
+    /// The checkpoints are interpreted as in
+    /// `BlockService::pull_blocks_to_tip`: an empty `from` list designates
+    /// the genesis block as the starting point, and if none of the blocks
+    /// are found in the chain, the future should fail.
     fn block_headers_to_tip(&mut self, from: &[Self::HeaderId]) -> Self::GetHeadersFuture;
 }
 
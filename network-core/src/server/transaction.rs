@@ -2,7 +2,7 @@
 
 use crate::codes;
 
-use chain_core::property::{Serialize, TransactionId};
+use chain_core::property::{Deserialize, Serialize, Transaction, TransactionId};
 
 use futures::prelude::*;
 
@@ -12,7 +12,10 @@ use std::fmt;
 /// validating and accepting transactions.
 pub trait TransactionService {
     /// The transaction identifier type for the blockchain.
-    type TransactionId: TransactionId + Serialize;
+    type TransactionId: TransactionId + Serialize + Deserialize;
+
+    /// The transaction type for the blockchain.
+    type Transaction: Transaction;
 
     /// The type of asynchronous futures returned by method `propose_transactions`.
     type ProposeTransactionsFuture: Future<
@@ -32,6 +35,31 @@ pub trait TransactionService {
         &mut self,
         ids: &[Self::TransactionId],
     ) -> Self::ProposeTransactionsFuture;
+
+    /// The type of asynchronous futures returned by method `submit_transactions`.
+    type SubmitTransactionsFuture: Future<
+        Item = Vec<codes::TransactionAcceptance>,
+        Error = TransactionError,
+    >;
+
+    /// Processes the submitted transactions, returning the result for
+    /// each transaction in the order of submission.
+    fn submit_transactions(&mut self, txs: &[Self::Transaction]) -> Self::SubmitTransactionsFuture;
+
+    /// The type of an asynchronous stream that provides transactions in
+    /// response to method `get_transactions`.
+    type GetTransactionsStream: Stream<Item = Self::Transaction, Error = TransactionError>;
+
+    /// The type of asynchronous futures returned by method `get_transactions`.
+    ///
+    /// The future resolves to a stream that will be used by the protocol
+    /// implementation to produce a server-streamed response.
+    type GetTransactionsFuture: Future<Item = Self::GetTransactionsStream, Error = TransactionError>;
+
+    /// Get the transactions identified by `ids`.
+    ///
+    /// Transactions unknown to the node should be omitted from the stream.
+    fn get_transactions(&mut self, ids: &[Self::TransactionId]) -> Self::GetTransactionsFuture;
 }
 
 /// Represents errors that can be returned by the node service implementation.
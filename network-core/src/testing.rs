@@ -0,0 +1,493 @@
+//! Test doubles for the client-side network interface.
+//!
+//! This module is available with the `testing` feature.
+
+use crate::{
+    bloom::BloomFilter,
+    client::{
+        block::{BlockService, HeaderService},
+        Error, ErrorKind,
+    },
+};
+
+use chain_core::property::{Block, HasHeader};
+
+use futures::{future, prelude::*, stream, sync::mpsc};
+
+use std::{
+    sync::{Arc, Mutex},
+    vec,
+};
+
+/// An in-memory client that serves a scripted chain of blocks.
+///
+/// The blocks are served in the order they have been added, the last
+/// block being the tip of the chain. Errors can be injected for a single
+/// subsequent request with the `fail_next_*` methods.
+pub struct MockClient<T: Block + HasHeader> {
+    chain: Vec<T>,
+    pushed_headers: Vec<T::Header>,
+    uploaded_blocks: Arc<Mutex<Vec<T>>>,
+    subscribers: Vec<mpsc::UnboundedSender<T>>,
+    fail_next_tip: Option<ErrorKind>,
+    fail_next_pull_blocks_to_tip: Option<ErrorKind>,
+    fail_next_pull_blocks: Option<ErrorKind>,
+    fail_next_get_blocks: Option<ErrorKind>,
+    fail_next_get_headers: Option<ErrorKind>,
+    fail_next_push_headers: Option<ErrorKind>,
+}
+
+impl<T> MockClient<T>
+where
+    T: Block + HasHeader + Clone,
+{
+    /// Creates a client serving the given chain.
+    pub fn new(chain: Vec<T>) -> Self {
+        MockClient {
+            chain,
+            pushed_headers: Vec::new(),
+            uploaded_blocks: Arc::new(Mutex::new(Vec::new())),
+            subscribers: Vec::new(),
+            fail_next_tip: None,
+            fail_next_pull_blocks_to_tip: None,
+            fail_next_pull_blocks: None,
+            fail_next_get_blocks: None,
+            fail_next_get_headers: None,
+            fail_next_push_headers: None,
+        }
+    }
+
+    /// Returns the chain served by this client.
+    pub fn chain(&self) -> &[T] {
+        &self.chain
+    }
+
+    /// Appends a block to the chain, making it the new tip.
+    ///
+    /// The block is also sent to all streams returned by
+    /// `block_subscription` that have not been dropped.
+    pub fn append_block(&mut self, block: T) {
+        self.subscribers
+            .retain(|tx| tx.unbounded_send(block.clone()).is_ok());
+        self.chain.push(block);
+    }
+
+    /// Returns the headers received with `push_headers`.
+    pub fn pushed_headers(&self) -> &[T::Header] {
+        &self.pushed_headers
+    }
+
+    /// Returns the blocks received so far with `upload_blocks`.
+    pub fn uploaded_blocks(&self) -> Vec<T> {
+        self.uploaded_blocks.lock().unwrap().clone()
+    }
+
+    /// Makes the next `tip`, `chain_length`, `tip_header`, or `tip_headers`
+    /// request fail with an error of the given kind.
+    pub fn fail_next_tip(&mut self, kind: ErrorKind) {
+        self.fail_next_tip = Some(kind);
+    }
+
+    /// Makes the next `pull_blocks_to_tip` request fail with an error
+    /// of the given kind.
+    pub fn fail_next_pull_blocks_to_tip(&mut self, kind: ErrorKind) {
+        self.fail_next_pull_blocks_to_tip = Some(kind);
+    }
+
+    /// Makes the next `pull_blocks` or `pull_blocks_from_tip` request fail
+    /// with an error of the given kind.
+    pub fn fail_next_pull_blocks(&mut self, kind: ErrorKind) {
+        self.fail_next_pull_blocks = Some(kind);
+    }
+
+    /// Makes the next `get_blocks`, `get_block`, `get_blocks_pipelined`,
+    /// `get_blocks_by_height`, `block_id_at_height`, or `has_blocks`
+    /// request fail with an error of the given kind.
+    pub fn fail_next_get_blocks(&mut self, kind: ErrorKind) {
+        self.fail_next_get_blocks = Some(kind);
+    }
+
+    /// Makes the next `get_headers` or `pull_headers_to_tip` request
+    /// fail with an error of the given kind.
+    pub fn fail_next_get_headers(&mut self, kind: ErrorKind) {
+        self.fail_next_get_headers = Some(kind);
+    }
+
+    /// Makes the next `push_headers` request fail with an error
+    /// of the given kind.
+    pub fn fail_next_push_headers(&mut self, kind: ErrorKind) {
+        self.fail_next_push_headers = Some(kind);
+    }
+
+    fn tip_block(&mut self) -> Result<&T, Error> {
+        if let Some(kind) = self.fail_next_tip.take() {
+            return Err(injected_error(kind));
+        }
+        self.chain
+            .last()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "the chain is empty"))
+    }
+
+    // Finds the position in the chain following the latest block
+    // identified in `from`, or the genesis if `from` is empty.
+    fn pull_to_tip_start(&self, from: &[T::Id]) -> Result<usize, Error> {
+        if from.is_empty() {
+            return Ok(0);
+        }
+        match self
+            .chain
+            .iter()
+            .rposition(|block| from.contains(&block.id()))
+        {
+            Some(pos) => Ok(pos + 1),
+            None => {
+                let msg = format!("none of the blocks {:?} found", from);
+                Err(Error::new(ErrorKind::NotFound, msg))
+            }
+        }
+    }
+
+    // Looks up the blocks in the order of the identifiers, failing
+    // the stream at the first unknown identifier.
+    fn lookup_blocks<F, R>(&self, ids: &[T::Id], f: F) -> vec::IntoIter<Result<R, Error>>
+    where
+        F: Fn(&T) -> R,
+    {
+        let mut res = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.chain.iter().find(|block| block.id() == *id) {
+                Some(block) => res.push(Ok(f(block))),
+                None => {
+                    let msg = format!("block {:?} not found", id);
+                    res.push(Err(Error::new(ErrorKind::NotFound, msg)));
+                    break;
+                }
+            }
+        }
+        res.into_iter()
+    }
+}
+
+fn injected_error(kind: ErrorKind) -> Error {
+    Error::new(kind, "injected error")
+}
+
+/// The type of streams returned by `MockClient`.
+pub type MockStream<T> = stream::IterResult<vec::IntoIter<Result<T, Error>>>;
+
+/// The type of the stream returned by `MockClient::block_subscription`.
+pub type MockSubscription<T> = stream::MapErr<mpsc::UnboundedReceiver<T>, fn(()) -> Error>;
+
+fn subscription_closed(_: ()) -> Error {
+    Error::new(ErrorKind::Rpc, "subscription closed")
+}
+
+/// The type of the future returned by `MockClient::upload_blocks`.
+pub type MockUpload = Box<dyn Future<Item = u64, Error = Error> + Send>;
+
+/// The type of the stream returned by `MockClient::get_blocks_pipelined`.
+pub type MockPipelinedStream<T> = Box<dyn Stream<Item = T, Error = Error> + Send>;
+
+impl<T> BlockService<T> for MockClient<T>
+where
+    T: Block + HasHeader + Clone + Send + 'static,
+{
+    type TipFuture = future::FutureResult<(T::Id, T::Date), Error>;
+
+    type ChainLengthFuture = future::FutureResult<u64, Error>;
+
+    type PullBlocksToTipStream = MockStream<T>;
+    type PullBlocksToTipFuture = future::FutureResult<Self::PullBlocksToTipStream, Error>;
+
+    type PullBlocksStream = MockStream<T>;
+    type PullBlocksFuture = future::FutureResult<Self::PullBlocksStream, Error>;
+
+    type GetBlocksStream = MockStream<T>;
+    type GetBlocksFuture = future::FutureResult<Self::GetBlocksStream, Error>;
+
+    type GetBlockFuture = future::FutureResult<T, Error>;
+
+    type BlockIdAtHeightFuture = future::FutureResult<T::Id, Error>;
+
+    type GetBlocksPipelinedStream = MockPipelinedStream<T>;
+    type GetBlocksPipelinedFuture = future::FutureResult<Self::GetBlocksPipelinedStream, Error>;
+
+    type HasBlocksFuture = future::FutureResult<Vec<bool>, Error>;
+
+    type BlockSubscriptionStream = MockSubscription<T>;
+    type BlockSubscriptionFuture = future::FutureResult<Self::BlockSubscriptionStream, Error>;
+
+    fn tip(&mut self) -> Self::TipFuture {
+        let res = self.tip_block().map(|block| (block.id(), block.date()));
+        future::result(res)
+    }
+
+    fn chain_length(&mut self) -> Self::ChainLengthFuture {
+        if let Err(e) = self.tip_block() {
+            return future::err(e);
+        }
+        future::ok(self.chain.len() as u64 - 1)
+    }
+
+    /// Streams the blocks following the latest block in the chain
+    /// that is identified in `from`, or the whole chain if `from` is empty.
+    /// Fails with `ErrorKind::NotFound` if none of the blocks are in
+    /// the chain.
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture {
+        if let Some(kind) = self.fail_next_pull_blocks_to_tip.take() {
+            return future::err(injected_error(kind));
+        }
+        let start = match self.pull_to_tip_start(from) {
+            Ok(start) => start,
+            Err(e) => return future::err(e),
+        };
+        let blocks: Vec<_> = self.chain[start..].iter().cloned().map(Ok).collect();
+        future::ok(stream::iter_result(blocks))
+    }
+
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> Self::PullBlocksFuture {
+        if let Some(kind) = self.fail_next_pull_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        let end = match self.chain.iter().position(|block| block.id() == *to) {
+            Some(pos) => pos + 1,
+            None => {
+                let msg = format!("block {:?} not found", to);
+                return future::err(Error::new(ErrorKind::NotFound, msg));
+            }
+        };
+        let start = if from.is_empty() {
+            0
+        } else {
+            match self.chain[..end]
+                .iter()
+                .rposition(|block| from.contains(&block.id()))
+            {
+                Some(pos) => pos + 1,
+                None => {
+                    let msg = format!("block {:?} does not descend from any of {:?}", to, from);
+                    return future::err(Error::new(ErrorKind::NotFound, msg));
+                }
+            }
+        };
+        let blocks: Vec<_> = self.chain[start..end].iter().cloned().map(Ok).collect();
+        future::ok(stream::iter_result(blocks))
+    }
+
+    /// Streams the chain backwards from the tip, stopping before the latest
+    /// block in the chain that is identified in `to`.
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> Self::PullBlocksFuture {
+        if let Some(kind) = self.fail_next_pull_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        let blocks: Vec<_> = self
+            .chain
+            .iter()
+            .rev()
+            .take_while(|block| !to.contains(&block.id()))
+            .cloned()
+            .map(Ok)
+            .collect();
+        future::ok(stream::iter_result(blocks))
+    }
+
+    /// Streams the blocks as `pull_blocks_to_tip` does, skipping
+    /// the blocks whose identifiers are in the filter.
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Self::PullBlocksFuture {
+        if let Some(kind) = self.fail_next_pull_blocks_to_tip.take() {
+            return future::err(injected_error(kind));
+        }
+        let start = match self.pull_to_tip_start(from) {
+            Ok(start) => start,
+            Err(e) => return future::err(e),
+        };
+        let blocks: Vec<_> = self.chain[start..]
+            .iter()
+            .filter(|block| !known.contains(&block.id()).unwrap_or(false))
+            .cloned()
+            .map(Ok)
+            .collect();
+        future::ok(stream::iter_result(blocks))
+    }
+
+    fn get_blocks(&mut self, ids: &[T::Id]) -> Self::GetBlocksFuture {
+        if let Some(kind) = self.fail_next_get_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        let blocks = self.lookup_blocks(ids, T::clone);
+        future::ok(stream::iter_result(blocks))
+    }
+
+    fn get_block(&mut self, id: T::Id) -> Self::GetBlockFuture {
+        if let Some(kind) = self.fail_next_get_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        match self.chain.iter().find(|block| block.id() == id) {
+            Some(block) => future::ok(block.clone()),
+            None => {
+                let msg = format!("block {:?} not found", id);
+                future::err(Error::new(ErrorKind::NotFound, msg))
+            }
+        }
+    }
+
+    /// Looks up the blocks in the chain as it is when the request is made.
+    fn get_blocks_pipelined<S>(&mut self, ids: S) -> Self::GetBlocksPipelinedFuture
+    where
+        S: Stream<Item = T::Id, Error = Error> + Send + 'static,
+    {
+        if let Some(kind) = self.fail_next_get_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        let chain = self.chain.clone();
+        let blocks = ids.and_then(
+            move |id| match chain.iter().find(|block| block.id() == id) {
+                Some(block) => Ok(block.clone()),
+                None => {
+                    let msg = format!("block {:?} not found", id);
+                    Err(Error::new(ErrorKind::NotFound, msg))
+                }
+            },
+        );
+        let blocks: MockPipelinedStream<T> = Box::new(blocks);
+        future::ok(blocks)
+    }
+
+    /// Streams the blocks at the given positions in the mock chain,
+    /// the first block being at height 0.
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture {
+        if let Some(kind) = self.fail_next_get_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        let blocks: Vec<_> = self
+            .chain
+            .iter()
+            .skip(from_height as usize)
+            .take(count as usize)
+            .cloned()
+            .map(Ok)
+            .collect();
+        future::ok(stream::iter_result(blocks))
+    }
+
+    fn block_id_at_height(&mut self, height: u64) -> Self::BlockIdAtHeightFuture {
+        if let Some(kind) = self.fail_next_get_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        match self.chain.get(height as usize) {
+            Some(block) => future::ok(block.id()),
+            None => {
+                let msg = format!("no block at height {}", height);
+                future::err(Error::new(ErrorKind::NotFound, msg))
+            }
+        }
+    }
+
+    fn has_blocks(&mut self, ids: &[T::Id]) -> Self::HasBlocksFuture {
+        if let Some(kind) = self.fail_next_get_blocks.take() {
+            return future::err(injected_error(kind));
+        }
+        let present = ids
+            .iter()
+            .map(|id| self.chain.iter().any(|block| block.id() == *id))
+            .collect();
+        future::ok(present)
+    }
+
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture {
+        let (tx, rx) = mpsc::unbounded();
+        self.subscribers.push(tx);
+        future::ok(rx.map_err(subscription_closed as fn(()) -> Error))
+    }
+
+    type UploadBlocksFuture = MockUpload;
+
+    /// Receives the blocks into the list returned by `uploaded_blocks`
+    /// and resolves to the number of blocks received.
+    fn upload_blocks<S>(&mut self, blocks: S) -> Self::UploadBlocksFuture
+    where
+        S: Stream<Item = T, Error = Error> + Send + 'static,
+    {
+        let uploaded = self.uploaded_blocks.clone();
+        let future = blocks.fold(0, move |count, block| {
+            uploaded.lock().unwrap().push(block);
+            Ok::<_, Error>(count + 1)
+        });
+        Box::new(future)
+    }
+}
+
+impl<T> HeaderService<T> for MockClient<T>
+where
+    T: Block + HasHeader + Clone,
+    T::Header: Clone,
+{
+    type GetHeadersStream = MockStream<T::Header>;
+    type GetHeadersFuture = future::FutureResult<Self::GetHeadersStream, Error>;
+
+    fn get_headers(&mut self, ids: &[T::Id]) -> Self::GetHeadersFuture {
+        if let Some(kind) = self.fail_next_get_headers.take() {
+            return future::err(injected_error(kind));
+        }
+        let headers = self.lookup_blocks(ids, T::header);
+        future::ok(stream::iter_result(headers))
+    }
+
+    type TipHeaderFuture = future::FutureResult<T::Header, Error>;
+
+    fn tip_header(&mut self) -> Self::TipHeaderFuture {
+        let res = self.tip_block().map(|block| block.header());
+        future::result(res)
+    }
+
+    type TipHeadersStream = MockStream<T::Header>;
+    type TipHeadersFuture = future::FutureResult<Self::TipHeadersStream, Error>;
+
+    fn tip_headers(&mut self, count: u32) -> Self::TipHeadersFuture {
+        if let Some(kind) = self.fail_next_tip.take() {
+            return future::err(injected_error(kind));
+        }
+        let headers: Vec<_> = self
+            .chain
+            .iter()
+            .rev()
+            .take(count as usize)
+            .map(|block| Ok(block.header()))
+            .collect();
+        future::ok(stream::iter_result(headers))
+    }
+
+    type PullHeadersStream = MockStream<T::Header>;
+    type PullHeadersFuture = future::FutureResult<Self::PullHeadersStream, Error>;
+
+    /// Streams the headers of the blocks that `pull_blocks_to_tip`
+    /// would stream for the same `from` list.
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> Self::PullHeadersFuture {
+        if let Some(kind) = self.fail_next_get_headers.take() {
+            return future::err(injected_error(kind));
+        }
+        let start = match self.pull_to_tip_start(from) {
+            Ok(start) => start,
+            Err(e) => return future::err(e),
+        };
+        let headers: Vec<_> = self.chain[start..]
+            .iter()
+            .map(|block| Ok(block.header()))
+            .collect();
+        future::ok(stream::iter_result(headers))
+    }
+
+    type PushHeadersFuture = future::FutureResult<(), Error>;
+
+    fn push_headers(&mut self, headers: &[T::Header]) -> Self::PushHeadersFuture {
+        if let Some(kind) = self.fail_next_push_headers.take() {
+            return future::err(injected_error(kind));
+        }
+        self.pushed_headers.extend_from_slice(headers);
+        future::ok(())
+    }
+}
@@ -1,6 +1,7 @@
 extern crate tower_grpc_build;
 
-use std::io::{stderr, Write};
+use std::env;
+use std::io::{self, stderr, Write};
 use std::process;
 
 fn main() {
@@ -8,8 +9,20 @@ fn main() {
         .enable_client(true)
         .enable_server(true)
         .build(&["../network-proto/node.proto"], &["../network-proto/"])
-        .unwrap_or_else(|e| {
-            writeln!(stderr(), "{}", e).unwrap();
-            process::exit(1)
-        });
+        .unwrap_or_else(fail);
+
+    if env::var_os("CARGO_FEATURE_REFLECTION").is_some() {
+        tower_grpc_build::Config::new()
+            .enable_client(true)
+            .build(
+                &["../network-proto/reflection.proto"],
+                &["../network-proto/"],
+            )
+            .unwrap_or_else(fail);
+    }
+}
+
+fn fail(e: io::Error) {
+    writeln!(stderr(), "{}", e).unwrap();
+    process::exit(1)
 }
@@ -1,31 +1,198 @@
-use crate::gen::{self, node::client as gen_client};
+#[cfg(unix)]
+use crate::peer::UnixPeer;
+use crate::{
+    gen::{self, node::client as gen_client},
+    peer::Origin,
+};
 
-use chain_core::property::{Block, BlockDate, BlockId, Deserialize, HasHeader, Header, Serialize};
-use network_core::client::{
-    self as core_client,
-    block::{BlockService, HeaderService},
+use chain_core::property::{
+    Block, BlockDate, BlockId, Deserialize, HasHeader, Header, Serialize, Transaction,
+};
+use network_core::{
+    bloom::BloomFilter,
+    client::{
+        self as core_client,
+        block::{BlockService, HeaderService},
+        gossip::GossipService,
+        peer::PeerService,
+        transaction::{MempoolStats, TransactionService, TransactionStatusUpdate},
+    },
+    codes::{TransactionAcceptance, TransactionState},
+    peer::{Peer, PeerInfo},
 };
 
-use futures::future::Executor;
+use bytes::Bytes;
+use futures::{future::Executor, sync::mpsc};
 use tokio::io;
 use tokio::prelude::*;
-use tower_grpc::{BoxBody, Request, Streaming};
-use tower_h2::client::{Background, Connect, ConnectError, Connection};
-use tower_util::MakeService;
+use tokio::timer::Delay;
+use tower_grpc::{BoxBody, Code, Request, Streaming};
+use tower_h2::client::{Background, ConnectError, Connection};
+use tower_http::add_origin::AddOrigin;
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+use self::activity::{ActiveRequest, Activity, RequestKind};
+use self::auth::Authorized;
+use self::cancel::CancelToken;
+use self::connection::ConnectionStatus;
+use self::limit::{StreamLimit, StreamPermit};
+use self::metrics::{Recorder, RequestTimer};
+use self::service_path::{RetryHint, ServicePath};
+use self::span::RequestSpan;
+use self::stream::ItemState;
+use self::stream_future::StreamingCall;
+use self::unary_future::Retry;
 
 use std::{
+    convert::identity,
     error,
     fmt::{self, Debug},
     marker::PhantomData,
+    mem,
+    net::SocketAddr,
     str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::path::Path;
+
+mod activity;
+mod auth;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod boxed;
+mod builder;
+mod cancel;
+mod chunks;
+mod coalesce;
+mod connection;
+mod dedup;
+mod host;
+mod idle;
+mod intercept;
+mod limit;
+mod linkage;
+mod merge;
+mod meta;
+mod metrics;
+mod multi;
+mod pool;
+mod progress;
+mod proxy;
+mod raw;
+mod reconnect;
+#[cfg(feature = "reflection")]
+mod reflection;
+mod results;
+mod resume;
+mod retry;
+mod select;
+mod service_path;
+mod sink;
+mod size;
+mod skip;
+mod span;
+mod summary;
+mod typed;
+
+pub use activity::ActiveRequests;
+pub use auth::{AuthProvider, TokenFuture};
+#[cfg(feature = "blocking")]
+pub use blocking::BlockingClient;
+pub use boxed::BoxClient;
+pub use builder::ClientBuilder;
+pub use cancel::{CancelToken, StreamHandle};
+pub use chunks::Chunks;
+pub use coalesce::{CoalescedFuture, CoalescingClient};
+pub use dedup::DedupById;
+pub use idle::IdleTimeout;
+pub use intercept::Interceptor;
+pub use limit::StreamLimitBehavior;
+pub use linkage::CheckChainLinkage;
+pub use merge::MergedBlockStream;
+pub use meta::{BlockMeta, BlockWithMetaFuture, BlockWithMetaStream, BlockWithMetaStreamFuture};
+pub use metrics::{ConnectErrorKind, Metrics};
+pub use multi::{LoadTracked, LoadTrackedFuture, MultiConnectClient};
+pub use pool::ClientPool;
+pub use progress::{Progress, ProgressInterval, SyncProgress};
+pub use proxy::HttpProxy;
+pub use raw::{RawResponseStream, RawResponseStreamFuture};
+pub use reconnect::{Backoff, ReconnectingClient, ReconnectingFuture};
+#[cfg(feature = "reflection")]
+pub use reflection::{ReflectionClient, ServiceInfo};
+pub use results::Results;
+pub use retry::RetryPolicy;
+pub use select::{PeerSelector, PeerStats};
+pub use sink::TransactionSink;
+pub use size::{
+    BlockSizeStream, BlockSizeStreamFuture, BlockWithSizeStream, BlockWithSizeStreamFuture,
 };
+pub use skip::{SkipKnownBlocks, SkipKnownBlocksFuture};
+pub use summary::StreamSummary;
+pub use typed::TypedClient;
 
 /// gRPC client for blockchain node.
 ///
 /// This type encapsulates the gRPC protocol client that can
 /// make connections and perform requests towards other blockchain nodes.
+///
+/// Cloning a client is cheap: the clones share the same HTTP/2
+/// connection, so they can be used by different tasks to issue
+/// concurrent requests to the peer. Each pending request occupies
+/// an HTTP/2 stream, and the number of concurrent streams
+/// is limited by the `SETTINGS_MAX_CONCURRENT_STREAMS` value advertised
+/// by the peer; requests in excess of that limit wait until a stream
+/// is released by completion or cancellation of another request.
+///
+/// With the `tracing` feature, the connection attempt and each request
+/// are polled within a `tracing` span at the debug level, carrying
+/// the origin URI of the peer, the gRPC method name, and a request
+/// identifier unique within the process. The span of a request with
+/// a streamed response stays with the `ResponseStream`, which records
+/// the number of items it has yielded and its terminal status.
 pub struct Client<S, E> {
-    node: gen_client::Node<Connection<S, E, BoxBody>>,
+    node: NodeClient<S, E>,
+    origin: http::Uri,
+    connection: ConnectionStatus,
+    request_timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    auth: Option<Arc<dyn AuthProvider>>,
+    interceptor: Option<Arc<dyn Interceptor>>,
+    metrics: Recorder,
+    stream_limit: Option<StreamLimit>,
+    stream_limit_behavior: StreamLimitBehavior,
+    cancel_token: Option<CancelToken>,
+    verify_checksums: bool,
+    activity: Activity,
+}
+
+type NodeClient<S, E> = gen_client::Node<ServicePath<AddOrigin<Connection<S, E, BoxBody>>>>;
+
+impl<S, E> Clone for Client<S, E>
+where
+    NodeClient<S, E>: Clone,
+{
+    fn clone(&self) -> Self {
+        Client {
+            node: self.node.clone(),
+            origin: self.origin.clone(),
+            connection: self.connection.clone(),
+            request_timeout: self.request_timeout,
+            retry_policy: self.retry_policy.clone(),
+            auth: self.auth.clone(),
+            interceptor: self.interceptor.clone(),
+            metrics: self.metrics.clone(),
+            stream_limit: self.stream_limit.clone(),
+            stream_limit_behavior: self.stream_limit_behavior,
+            cancel_token: self.cancel_token.clone(),
+            verify_checksums: self.verify_checksums,
+            activity: self.activity.clone(),
+        }
+    }
 }
 
 impl<S, E> Client<S, E>
@@ -33,334 +200,2788 @@ where
     S: AsyncRead + AsyncWrite,
     E: Executor<Background<S, BoxBody>> + Clone,
 {
+    /// Connects to the peer, using the origin derived from the peer's
+    /// connection details for requests.
     pub fn connect<P>(peer: P, executor: E) -> impl Future<Item = Self, Error = Error>
     where
-        P: tokio_connect::Connect<Connected = S, Error = io::Error> + 'static,
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
     {
-        let mut make_client = Connect::new(peer, Default::default(), executor);
-        make_client
-            .make_service(())
-            .map_err(|e| Error::Connect(e))
-            .map(|conn| {
-                // TODO: add origin URL with add_origin middleware from tower-http
-
-                Client {
-                    node: gen_client::Node::new(conn),
-                }
-            })
+        ClientBuilder::new().build_connect(peer, executor)
     }
-}
-
-type GrpcFuture<R> = tower_grpc::client::unary::ResponseFuture<
-    R,
-    tower_h2::client::ResponseFuture,
-    tower_h2::RecvBody,
->;
-
-type GrpcStreamFuture<R> =
-    tower_grpc::client::server_streaming::ResponseFuture<R, tower_h2::client::ResponseFuture>;
 
-type GrpcError = tower_grpc::Error<tower_h2::client::Error>;
-
-type GrpcStreamError = tower_grpc::Error<()>;
+    /// Connects to the peer and verifies that it serves the blockchain
+    /// with the genesis block identified by `genesis_hash`.
+    ///
+    /// This guards against peers on a different network, which would
+    /// otherwise only be found out when their blocks fail to validate.
+    /// See `ClientBuilder::build_connect_verified` for details.
+    pub fn connect_verified<T, P>(
+        peer: P,
+        executor: E,
+        genesis_hash: T::Id,
+    ) -> impl Future<Item = Self, Error = Error>
+    where
+        T: Block,
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+        Self: PeerService<T>,
+    {
+        ClientBuilder::new().build_connect_verified::<T, _, _, _>(peer, executor, genesis_hash)
+    }
 
-pub struct ResponseFuture<T, R> {
-    state: unary_future::State<T, R>,
-}
+    /// Connects to the peer, setting the scheme and authority of
+    /// the specified origin URI on all requests.
+    pub fn connect_with_origin<P>(
+        peer: P,
+        origin: http::Uri,
+        executor: E,
+    ) -> impl Future<Item = Self, Error = Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + 'static,
+    {
+        ClientBuilder::new().build_connect_with_origin(peer, origin, executor)
+    }
 
-impl<T, R> ResponseFuture<T, R> {
-    fn new(future: GrpcFuture<R>) -> Self {
-        ResponseFuture {
-            state: unary_future::State::Pending(future),
+    /// Sets the timeout for unary requests made with this client.
+    ///
+    /// A response future that does not resolve within the timeout fails
+    /// with an error of kind `ErrorKind::Timeout`. The pending request is
+    /// dropped at that point, releasing its resources on the connection.
+    ///
+    /// The timeout is also sent to the server in the `grpc-timeout`
+    /// request header, so that the server can abandon processing of
+    /// a request whose deadline has passed. The header is sent with
+    /// streaming requests as well, such as `pull_blocks_to_tip` or
+    /// `upload_blocks`; these are not timed out by the client,
+    /// but the server may end a stream that outlasts the timeout.
+    pub fn with_request_timeout(self, timeout: Duration) -> Self {
+        Client {
+            request_timeout: Some(timeout),
+            ..self
         }
     }
-}
-
-pub struct ResponseStreamFuture<T, R> {
-    state: stream_future::State<T, R>,
-}
 
-impl<T, R> ResponseStreamFuture<T, R> {
-    fn new(future: GrpcStreamFuture<R>) -> Self {
-        ResponseStreamFuture {
-            state: stream_future::State::Pending(future),
+    /// Sets the policy for retrying idempotent unary requests
+    /// made with this client.
+    ///
+    /// Retries are made within the request timeout, if one is set:
+    /// a retry that could not be made before the deadline is not
+    /// attempted, and the last error is returned instead.
+    pub fn with_retry_policy(self, policy: RetryPolicy) -> Self {
+        Client {
+            retry_policy: Some(policy),
+            ..self
         }
     }
-}
-
-pub struct ResponseStream<T, R> {
-    inner: Streaming<R, tower_h2::RecvBody>,
-    _phantom: PhantomData<T>,
-}
 
-fn convert_error<T>(e: tower_grpc::Error<T>) -> core_client::Error
-where
-    T: Debug + Send + Sync + 'static,
-{
-    core_client::Error::new(core_client::ErrorKind::Rpc, e)
-}
+    /// Limits the number of concurrent requests with streamed responses,
+    /// such as `pull_blocks_to_tip`, made with this client.
+    ///
+    /// The limit is shared with clones of the client made afterwards.
+    /// A request takes up a slot from the moment it is sent until its
+    /// response stream ends, fails, is canceled, or is dropped.
+    /// This makes it possible to keep the number of pending streams within
+    /// the `SETTINGS_MAX_CONCURRENT_STREAMS` value of the peer, rather
+    /// than have excess requests stall on the connection. What happens
+    /// to a request made when all slots are taken is set with
+    /// `with_stream_limit_behavior`; by default, it waits for a slot.
+    ///
+    /// Unary requests and requests with streamed request bodies
+    /// are not counted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is 0.
+    pub fn with_stream_limit(self, limit: usize) -> Self {
+        assert!(limit > 0, "the stream limit should not be 0");
+        Client {
+            stream_limit: Some(StreamLimit::new(limit)),
+            ..self
+        }
+    }
 
-pub trait ConvertResponse<T> {
-    fn convert_response(self) -> Result<T, core_client::Error>;
-}
+    /// Sets what happens to requests with streamed responses made
+    /// when the limit set with `with_stream_limit` has been reached.
+    pub fn with_stream_limit_behavior(self, behavior: StreamLimitBehavior) -> Self {
+        Client {
+            stream_limit_behavior: behavior,
+            ..self
+        }
+    }
 
-mod unary_future {
-    use super::{
-        convert_error, core_client, ConvertResponse, GrpcError, GrpcFuture, ResponseFuture,
-    };
-    use futures::prelude::*;
-    use std::marker::PhantomData;
-    use tower_grpc::Response;
+    /// Sets the token to cancel all requests made with this client
+    /// and its clones made afterwards.
+    ///
+    /// When the token is triggered with `CancelToken::cancel`,
+    /// the pending response futures and streams of the requests fail
+    /// with an error of kind `ErrorKind::Canceled` on their next poll,
+    /// and so do requests made after that. The gRPC calls in progress
+    /// are canceled by resetting their HTTP/2 streams.
+    pub fn with_cancel_token(self, token: CancelToken) -> Self {
+        Client {
+            cancel_token: Some(token),
+            ..self
+        }
+    }
 
-    fn poll_and_convert_response<T, R, F>(future: &mut F) -> Poll<T, core_client::Error>
+    /// Installs a hook invoked on every request made with this client
+    /// and its clones made afterwards, before the request is sent.
+    ///
+    /// See the documentation of `Interceptor` for details.
+    pub fn with_interceptor<I>(self, interceptor: I) -> Self
     where
-        F: Future<Item = Response<R>, Error = GrpcError>,
-        R: ConvertResponse<T>,
+        I: Interceptor + 'static,
     {
-        match future.poll() {
-            Ok(Async::NotReady) => Ok(Async::NotReady),
-            Ok(Async::Ready(res)) => {
-                let item = res.into_inner().convert_response()?;
-                Ok(Async::Ready(item))
-            }
-            Err(e) => Err(convert_error(e)),
+        Client {
+            interceptor: Some(Arc::new(interceptor)),
+            ..self
         }
     }
 
-    pub enum State<T, R> {
-        Pending(GrpcFuture<R>),
-        Finished(PhantomData<T>),
+    /// Returns the origin URI identifying the peer, as set on
+    /// the requests made with this client.
+    pub fn origin(&self) -> &http::Uri {
+        &self.origin
     }
 
-    impl<T, R> Future for ResponseFuture<T, R>
-    where
-        R: prost::Message + Default + ConvertResponse<T>,
-    {
-        type Item = T;
-        type Error = core_client::Error;
+    /// Checks whether the HTTP/2 connection to the peer is believed
+    /// to be open.
+    ///
+    /// This does not block or probe the connection: the status reflects
+    /// the outcome of the latest request or response stream polled
+    /// on the connection by this client or any of its clones.
+    /// A transport or HTTP/2 failure observed there flips the status to
+    /// `false`; a freshly connected client is reported as connected.
+    /// A client that reports `false` should be discarded and a new
+    /// connection made.
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_open()
+    }
 
-        fn poll(&mut self) -> Poll<T, core_client::Error> {
-            if let State::Pending(ref mut f) = self.state {
-                let res = poll_and_convert_response(f);
-                if let Ok(Async::NotReady) = res {
-                    return Ok(Async::NotReady);
-                }
-                self.state = State::Finished(PhantomData);
-                res
-            } else {
-                match self.state {
-                    State::Pending(_) => unreachable!(),
-                    State::Finished(_) => panic!("polled a finished response"),
-                }
-            }
-        }
+    /// Returns a snapshot of the requests in progress on this client
+    /// and its clones, by gRPC method.
+    ///
+    /// A unary request is counted from the moment it is made until its
+    /// response future resolves or is dropped. A request with a streamed
+    /// response is counted until its response stream ends, fails,
+    /// is canceled, or is dropped before reaching its end.
+    /// Requests with streamed request bodies are not counted.
+    pub fn active_requests(&self) -> ActiveRequests {
+        self.activity.snapshot()
     }
-}
 
-mod stream_future {
-    use super::{
-        convert_error, core_client, GrpcError, GrpcStreamFuture, ResponseStream,
-        ResponseStreamFuture,
-    };
-    use futures::prelude::*;
-    use std::marker::PhantomData;
-    use tower_grpc::{Response, Streaming};
+    /// Returns a future that resolves when the client is ready to make
+    /// a request.
+    ///
+    /// Readiness is a property of the shared HTTP/2 connection: the future
+    /// resolves once the connection can accept a new stream, and fails
+    /// if the connection has failed. A connection pool can use this to
+    /// hand out only clients that are ready, rather than have the first
+    /// request made on a fresh connection wait for the connection.
+    pub fn ready(&mut self) -> impl Future<Item = (), Error = core_client::Error>
+    where
+        NodeClient<S, E>: Clone,
+    {
+        let mut node = self.node.clone();
+        let connection = self.connection.clone();
+        futures::future::poll_fn(move || {
+            let res = node.poll_ready();
+            connection.observe(&res);
+            res.map_err(convert_error)
+        })
+    }
 
-    fn poll_and_convert_response<T, R, F>(
-        future: &mut F,
-    ) -> Poll<ResponseStream<T, R>, core_client::Error>
+    /// Measures the round-trip time to the peer.
+    ///
+    /// The HTTP/2 connection does not give access to its PING frames,
+    /// so this times a `Ping` request from the moment it is made until
+    /// the response arrives. The request is small and cheap for the peer
+    /// to serve, so it can be repeated periodically; it is not retried,
+    /// and it is subject to the request timeout. The measurement includes
+    /// obtaining a token from the authentication provider, if one is
+    /// set, and waiting for the connection to become ready: use `ready`
+    /// before measuring the latency of a new connection.
+    pub fn rtt(&mut self) -> impl Future<Item = Duration, Error = core_client::Error>
     where
-        F: Future<Item = Response<Streaming<R, tower_h2::RecvBody>>, Error = GrpcError>,
+        NodeClient<S, E>: Clone + Send + 'static,
     {
-        match future.poll() {
-            Ok(Async::NotReady) => Ok(Async::NotReady),
-            Ok(Async::Ready(res)) => {
-                let stream = ResponseStream {
-                    inner: res.into_inner(),
-                    _phantom: PhantomData,
-                };
-                Ok(Async::Ready(stream))
-            }
-            Err(e) => Err(convert_error(e)),
+        let started = Instant::now();
+        let context = self.request_context("Ping");
+        let req = context.hint_request(self.new_request(gen::node::PingRequest {}));
+        let future = self.authorized(req, |node, req| node.ping(req));
+        let future: ResponseFuture<(), gen::node::PingResponse> =
+            ResponseFuture::new(future, self.deadline()).with_context(context);
+        future.map(move |()| started.elapsed())
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.request_timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    fn request_context(&self, method: &'static str) -> RequestContext {
+        RequestContext {
+            method,
+            timer: self.metrics.start_request(method),
+            connection: self.connection.clone(),
+            span: RequestSpan::new(&self.origin, method),
+            cancel_token: self.cancel_token.clone(),
+            verify_checksums: self.verify_checksums,
+            activity: self.activity.clone(),
+            active: None,
+            retry_hint: RetryHint::default(),
         }
     }
 
-    pub enum State<T, R> {
-        Pending(GrpcStreamFuture<R>),
-        Finished(PhantomData<T>),
+    // Creates a request, propagating the request timeout to the server.
+    fn new_request<M>(&self, message: M) -> Request<M> {
+        timed_request(message, self.request_timeout)
     }
 
-    impl<T, R> Future for ResponseStreamFuture<T, R>
+    // Makes a gRPC call, authenticated with a token from the
+    // authentication provider if one is set.
+    fn authorized<M, F, C>(&mut self, req: Request<M>, call: C) -> Authorized<F>
     where
-        R: prost::Message + Default,
+        NodeClient<S, E>: Clone + Send + 'static,
+        M: Send + 'static,
+        C: FnOnce(&mut NodeClient<S, E>, Request<M>) -> F + Send + 'static,
     {
-        type Item = ResponseStream<T, R>;
-        type Error = core_client::Error;
-
-        fn poll(&mut self) -> Poll<ResponseStream<T, R>, core_client::Error> {
-            if let State::Pending(ref mut f) = self.state {
-                let res = poll_and_convert_response(f);
-                if let Ok(Async::NotReady) = res {
-                    return Ok(Async::NotReady);
-                }
-                self.state = State::Finished(PhantomData);
-                res
-            } else {
-                match self.state {
-                    State::Pending(_) => unreachable!(),
-                    State::Finished(_) => panic!("polled a finished response"),
-                }
-            }
-        }
+        auth::authorize(
+            &mut self.node,
+            self.auth.as_ref(),
+            self.interceptor.as_ref(),
+            req,
+            call,
+        )
     }
-}
-
-mod stream {
-    use super::{convert_error, core_client, ConvertResponse, GrpcStreamError, ResponseStream};
-    use futures::prelude::*;
 
-    fn poll_and_convert_item<T, S, R>(stream: &mut S) -> Poll<Option<T>, core_client::Error>
+    // Makes a call to a method with a streamed response, subject to
+    // the limit of concurrent response streams if one is set.
+    // The message can be a stream of request messages, for
+    // a bidirectional streaming method.
+    fn streaming_call<M, T, R, F, C>(
+        &mut self,
+        method: &'static str,
+        message: M,
+        call: C,
+    ) -> ResponseStreamFuture<T, R>
     where
-        S: Stream<Item = R, Error = GrpcStreamError>,
-        R: ConvertResponse<T>,
+        NodeClient<S, E>: Clone + Send + 'static,
+        M: Send + 'static,
+        C: FnOnce(&mut NodeClient<S, E>, Request<M>) -> F + Send + 'static,
+        F: Into<StreamingCall<R>>,
     {
-        match stream.poll() {
-            Ok(Async::NotReady) => Ok(Async::NotReady),
-            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
-            Ok(Async::Ready(Some(item))) => {
-                let item = item.convert_response()?;
-                Ok(Async::Ready(Some(item)))
+        let call = move |node: &mut NodeClient<S, E>, req: Request<M>| -> StreamingCall<R> {
+            call(node, req).into()
+        };
+        let req = self.new_request(message);
+        let limit = match self.stream_limit {
+            None => {
+                let future = self.authorized(req, call);
+                return ResponseStreamFuture::new(future)
+                    .with_context(self.request_context(method));
+            }
+            Some(ref limit) => limit.clone(),
+        };
+        if let Some(permit) = limit.try_acquire() {
+            let future = self.authorized(req, call);
+            return ResponseStreamFuture::new(future)
+                .with_permit(permit)
+                .with_context(self.request_context(method));
+        }
+        match self.stream_limit_behavior {
+            StreamLimitBehavior::Fail => ResponseStreamFuture::error(core_client::Error::new(
+                core_client::ErrorKind::ResourceExhausted,
+                "the limit of concurrent response streams has been reached",
+            )),
+            StreamLimitBehavior::Wait => {
+                let mut node = self.node.clone();
+                let auth = self.auth.clone();
+                let interceptor = self.interceptor.clone();
+                let mut args = Some((req, call));
+                let future = ResponseStreamFuture::queued(
+                    limit,
+                    Box::new(move || {
+                        let (req, call) = args.take().expect("queued call made twice");
+                        auth::authorize(&mut node, auth.as_ref(), interceptor.as_ref(), req, call)
+                    }),
+                );
+                future.with_context(self.request_context(method))
             }
-            Err(e) => Err(convert_error(e)),
         }
     }
 
-    impl<T, R> Stream for ResponseStream<T, R>
+    // Makes a call to an idempotent unary method. If a retry policy is
+    // set, the future keeps a clone of the connection handle and
+    // the request message to re-issue the call on a transient failure.
+    fn idempotent_call<M, T, R, F>(
+        &mut self,
+        method: &'static str,
+        message: M,
+        call: F,
+    ) -> ResponseFuture<T, R>
     where
-        R: prost::Message + Default + ConvertResponse<T>,
+        M: Clone + Send + 'static,
+        F: FnOnce(&mut NodeClient<S, E>, Request<M>) -> UnaryCall<R> + Clone + Send + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
     {
-        type Item = T;
-        type Error = core_client::Error;
-
-        fn poll(&mut self) -> Poll<Option<T>, core_client::Error> {
-            poll_and_convert_item(&mut self.inner)
+        let context = self.request_context(method);
+        let retry_hint = context.retry_hint.clone();
+        let req = context.hint_request(self.new_request(message.clone()));
+        let future = self.authorized(req, call.clone());
+        let future = ResponseFuture::new(future, self.deadline()).with_context(context);
+        match self.retry_policy {
+            None => future,
+            Some(ref policy) => {
+                let mut node = self.node.clone();
+                let auth = self.auth.clone();
+                let interceptor = self.interceptor.clone();
+                let retry = Retry::new(
+                    policy.clone(),
+                    Box::new(move |timeout| {
+                        let req = retry_hint.attach(timed_request(message.clone(), timeout));
+                        auth::authorize(
+                            &mut node,
+                            auth.as_ref(),
+                            interceptor.as_ref(),
+                            req,
+                            call.clone(),
+                        )
+                    }),
+                );
+                future.with_retry(retry)
+            }
         }
     }
 }
 
-fn deserialize_bytes<T>(mut buf: &[u8]) -> Result<T, core_client::Error>
+#[cfg(unix)]
+impl<E> Client<UnixStream, E>
 where
-    T: Deserialize,
-    T::Error: Send + Sync + 'static,
+    E: Executor<Background<UnixStream, BoxBody>> + Clone,
 {
-    T::deserialize(&mut buf).map_err(|e| core_client::Error::new(core_client::ErrorKind::Format, e))
+    /// Connects to a peer listening on the Unix domain socket at `path`.
+    ///
+    /// This is a shorthand for `Client::connect` with a `UnixPeer`;
+    /// the requests are made with the origin `http://localhost`.
+    ///
+    /// This function is only available on Unix.
+    pub fn connect_uds<P>(path: P, executor: E) -> impl Future<Item = Self, Error = Error>
+    where
+        P: AsRef<Path>,
+    {
+        let peer = UnixPeer::new(path.as_ref().to_path_buf());
+        Client::connect(peer, executor)
+    }
 }
 
-fn parse_str<T>(s: &str) -> Result<T, core_client::Error>
-where
-    T: FromStr,
-    T::Err: error::Error + Send + Sync + 'static,
-{
-    T::from_str(s).map_err(|e| core_client::Error::new(core_client::ErrorKind::Format, e))
+fn timed_request<M>(message: M, timeout: Option<Duration>) -> Request<M> {
+    let req = Request::new(message);
+    match timeout {
+        None => req,
+        Some(timeout) => {
+            let mut req = req.into_http();
+            let value = http::header::HeaderValue::from_str(&grpc_timeout_value(timeout))
+                .expect("grpc-timeout value should be a valid header value");
+            req.headers_mut().insert(GRPC_TIMEOUT_HEADER, value);
+            Request::from_http(req)
+        }
+    }
 }
 
-fn serialize_to_vec<T>(values: &[T]) -> Vec<Vec<u8>>
-where
-    T: Serialize,
-{
-    values
-        .iter()
-        .map(|x| {
-            let mut v = Vec::new();
-            x.serialize(&mut v).unwrap();
-            v
-        })
-        .collect()
-}
+const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
 
-impl<I, D> ConvertResponse<(I, D)> for gen::node::TipResponse
-where
-    I: BlockId + Deserialize,
-    D: BlockDate + FromStr,
-    <I as Deserialize>::Error: Send + Sync + 'static,
-    <D as FromStr>::Err: error::Error + Send + Sync + 'static,
-{
-    fn convert_response(self) -> Result<(I, D), core_client::Error> {
-        let id = deserialize_bytes(&self.id)?;
-        let blockdate = parse_str(&self.blockdate)?;
-        Ok((id, blockdate))
+// Encodes the timeout for the grpc-timeout header, using the most precise
+// unit that fits the value into the maximum of 8 digits. The value is
+// rounded up so that the server does not give up before the client does.
+fn grpc_timeout_value(timeout: Duration) -> String {
+    const MAX_VALUE: u64 = 99_999_999;
+    const UNITS: [(u64, char); 6] = [
+        (1, 'n'),
+        (1_000, 'u'),
+        (1_000_000, 'm'),
+        (1_000_000_000, 'S'),
+        (60 * 1_000_000_000, 'M'),
+        (3600 * 1_000_000_000, 'H'),
+    ];
+    let nanos = timeout
+        .as_secs()
+        .saturating_mul(1_000_000_000)
+        .saturating_add(u64::from(timeout.subsec_nanos()));
+    for &(unit_nanos, unit) in UNITS.iter() {
+        let value = nanos / unit_nanos + if nanos % unit_nanos == 0 { 0 } else { 1 };
+        if value <= MAX_VALUE {
+            return format!("{}{}", value, unit);
+        }
     }
+    format!("{}H", MAX_VALUE)
 }
 
-impl<T> ConvertResponse<T> for gen::node::Block
-where
-    T: Block,
-    <T as Deserialize>::Error: Send + Sync + 'static,
-{
-    fn convert_response(self) -> Result<T, core_client::Error> {
-        let block = deserialize_bytes(&self.content)?;
-        Ok(block)
-    }
+// The future of an HTTP/2 response, as returned by the service stack of
+// the generated client.
+type ServiceFuture = service_path::ResponseFuture<tower_h2::client::ResponseFuture>;
+
+type UnaryCall<R> = tower_grpc::client::unary::ResponseFuture<R, ServiceFuture, tower_h2::RecvBody>;
+
+type GrpcFuture<R> = Authorized<UnaryCall<R>>;
+
+type GrpcClientStreamingFuture<R> = Authorized<
+    tower_grpc::client::client_streaming::ResponseFuture<R, ServiceFuture, tower_h2::RecvBody>,
+>;
+
+type GrpcStreamFuture<R> = Authorized<StreamingCall<R>>;
+
+type GrpcError = tower_grpc::Error<tower_h2::client::Error>;
+
+type GrpcStreamError = tower_grpc::Error<()>;
+
+// Per-request state tied to the client that made the request.
+struct RequestContext {
+    method: &'static str,
+    timer: Option<RequestTimer>,
+    connection: ConnectionStatus,
+    span: RequestSpan,
+    cancel_token: Option<CancelToken>,
+    verify_checksums: bool,
+    activity: Activity,
+    // The accounting of the request while it is in progress,
+    // if it is counted.
+    active: Option<ActiveRequest>,
+    // The retry hint of the response to a request with a unary
+    // response, if the request has been made with `hint_request`.
+    retry_hint: RetryHint,
 }
 
-impl<T> ConvertResponse<T> for gen::node::Header
-where
-    T: Header,
-    <T as Deserialize>::Error: Send + Sync + 'static,
-{
-    fn convert_response(self) -> Result<T, core_client::Error> {
-        let block = deserialize_bytes(&self.content)?;
-        Ok(block)
+impl RequestContext {
+    // Prepares the request to receive the retry hint of the response,
+    // which is attached to the error if the request fails.
+    fn hint_request<M>(&self, req: Request<M>) -> Request<M> {
+        self.retry_hint.attach(req)
     }
-}
 
-impl<T, S, E> BlockService<T> for Client<S, E>
-where
-    T: Block,
-    S: AsyncRead + AsyncWrite,
-    E: Executor<Background<S, BoxBody>> + Clone,
-    T::Date: FromStr,
-    <T as Deserialize>::Error: Send + Sync + 'static,
-    <T::Id as Deserialize>::Error: Send + Sync + 'static,
-    <T::Date as FromStr>::Err: error::Error + Send + Sync + 'static,
-{
-    type TipFuture = ResponseFuture<(T::Id, T::Date), gen::node::TipResponse>;
+    // Starts counting the request as one in progress, until
+    // the context is dropped or the accounting is passed on.
+    fn count_active(mut self, kind: RequestKind) -> Self {
+        self.active = Some(self.activity.start(self.method, kind));
+        self
+    }
 
-    type PullBlocksToTipStream = ResponseStream<T, gen::node::Block>;
-    type PullBlocksToTipFuture = ResponseStreamFuture<T, gen::node::Block>;
+    fn finish<T>(self, result: &Result<T, core_client::Error>) {
+        self.span.record_status(result);
+        self.finish_timer(result);
+    }
 
-    type GetBlocksStream = ResponseStream<T, gen::node::Block>;
-    type GetBlocksFuture = ResponseStreamFuture<T, gen::node::Block>;
+    // Finishes a request that opens a response stream. A successfully
+    // opened stream records its terminal status in the span itself.
+    fn finish_stream<T>(self, result: &Result<T, core_client::Error>) {
+        if result.is_err() {
+            self.span.record_status(result);
+        }
+        self.finish_timer(result);
+    }
 
-    fn tip(&mut self) -> Self::TipFuture {
-        let req = gen::node::TipRequest {};
-        let future = self.node.tip(Request::new(req));
-        ResponseFuture::new(future)
+    fn finish_timer<T>(self, result: &Result<T, core_client::Error>) {
+        if let Some(timer) = self.timer {
+            timer.finish(result);
+        }
     }
 
-    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture {
-        let from = serialize_to_vec(from);
-        let req = gen::node::PullBlocksToTipRequest { from };
-        let future = self.node.pull_blocks_to_tip(Request::new(req));
-        ResponseStreamFuture::new(future)
+    fn span(&self) -> RequestSpan {
+        self.span.clone()
     }
-}
 
-impl<T, S, E> HeaderService<T> for Client<S, E>
-where
-    T: HasHeader,
-    S: AsyncRead + AsyncWrite,
-    E: Executor<Background<S, BoxBody>> + Clone,
-    <T::Header as Deserialize>::Error: Send + Sync + 'static,
-{
-    type GetHeadersStream = ResponseStream<T::Header, gen::node::Header>;
-    type GetHeadersFuture = ResponseStreamFuture<T::Header, gen::node::Header>;
+    // Checks whether the cancellation token of the client, if set,
+    // has been triggered.
+    fn poll_canceled(&mut self) -> bool {
+        match self.cancel_token {
+            None => false,
+            Some(ref mut token) => token.poll_canceled(),
+        }
+    }
 }
 
-/// The error type for gRPC client operations.
-#[derive(Debug)]
-pub enum Error {
-    Connect(ConnectError<io::Error>),
+// Polls a request or a response stream within its tracing span, if any.
+fn poll_in_span<R>(span: Option<RequestSpan>, poll: impl FnOnce() -> R) -> R {
+    match span {
+        None => poll(),
+        Some(span) => span.in_scope(poll),
+    }
+}
+
+/// Future of the response to a unary request.
+///
+/// Once the future has resolved, it fails with an error of kind
+/// `ErrorKind::Internal` if it is polled again.
+pub struct ResponseFuture<T, R> {
+    state: unary_future::State<T, R>,
+    deadline: Option<Delay>,
+    context: Option<RequestContext>,
+    retry: Option<Retry<R>>,
+    check: Option<ResponseCheck<T>>,
+}
+
+// Validates a converted response against the request it answers.
+type ResponseCheck<T> = Box<dyn Fn(&T) -> Result<(), core_client::Error> + Send>;
+
+impl<T, R> ResponseFuture<T, R> {
+    fn new(future: GrpcFuture<R>, deadline: Option<Instant>) -> Self {
+        ResponseFuture {
+            state: unary_future::State::Pending(future),
+            deadline: deadline.map(Delay::new),
+            context: None,
+            retry: None,
+            check: None,
+        }
+    }
+
+    fn error(err: core_client::Error) -> Self {
+        ResponseFuture {
+            state: unary_future::State::Failed(err),
+            deadline: None,
+            context: None,
+            retry: None,
+            check: None,
+        }
+    }
+
+    fn with_context(self, context: RequestContext) -> Self {
+        ResponseFuture {
+            context: Some(context.count_active(RequestKind::Unary)),
+            ..self
+        }
+    }
+
+    fn with_retry(self, retry: Retry<R>) -> Self {
+        ResponseFuture {
+            retry: Some(retry),
+            ..self
+        }
+    }
+
+    fn with_check(self, check: ResponseCheck<T>) -> Self {
+        ResponseFuture {
+            check: Some(check),
+            ..self
+        }
+    }
+}
+
+pub struct ClientStreamingFuture<T, R> {
+    state: client_streaming_future::State<T, R>,
+    context: Option<RequestContext>,
+}
+
+impl<T, R> ClientStreamingFuture<T, R> {
+    fn new(future: GrpcClientStreamingFuture<R>) -> Self {
+        ClientStreamingFuture {
+            state: client_streaming_future::State::Pending(future),
+            context: None,
+        }
+    }
+
+    fn error(err: core_client::Error) -> Self {
+        ClientStreamingFuture {
+            state: client_streaming_future::State::Failed(err),
+            context: None,
+        }
+    }
+
+    fn with_context(self, context: RequestContext) -> Self {
+        ClientStreamingFuture {
+            context: Some(context),
+            ..self
+        }
+    }
+}
+
+/// Future of the response stream to a request with a streamed response.
+///
+/// Once the future has resolved, it fails with an error of kind
+/// `ErrorKind::Internal` if it is polled again.
+pub struct ResponseStreamFuture<T, R> {
+    state: stream_future::State<T, R>,
+    handle: StreamHandle,
+    context: Option<RequestContext>,
+    permit: Option<StreamPermit>,
+}
+
+impl<T, R> ResponseStreamFuture<T, R> {
+    fn new(future: GrpcStreamFuture<R>) -> Self {
+        ResponseStreamFuture {
+            state: stream_future::State::Pending(future),
+            handle: StreamHandle::new(),
+            context: None,
+            permit: None,
+        }
+    }
+
+    // Creates a future that makes the call once a stream slot
+    // can be acquired from the limit.
+    fn queued(limit: StreamLimit, call: Box<dyn FnMut() -> GrpcStreamFuture<R> + Send>) -> Self {
+        ResponseStreamFuture {
+            state: stream_future::State::Queued(limit, call),
+            handle: StreamHandle::new(),
+            context: None,
+            permit: None,
+        }
+    }
+
+    fn error(err: core_client::Error) -> Self {
+        ResponseStreamFuture {
+            state: stream_future::State::Failed(err),
+            handle: StreamHandle::new(),
+            context: None,
+            permit: None,
+        }
+    }
+
+    fn with_permit(self, permit: StreamPermit) -> Self {
+        ResponseStreamFuture {
+            permit: Some(permit),
+            ..self
+        }
+    }
+
+    fn with_context(self, context: RequestContext) -> Self {
+        ResponseStreamFuture {
+            context: Some(context.count_active(RequestKind::Stream)),
+            ..self
+        }
+    }
+
+    /// Returns a handle that can be used to cancel the request.
+    ///
+    /// If the request is canceled before the response arrives, the future
+    /// resolves to an empty stream.
+    pub fn handle(&self) -> StreamHandle {
+        self.handle.clone()
+    }
+}
+
+/// Stream of items received in a streaming response.
+///
+/// The stream reads from the transport only when it is polled, one
+/// message at a time, and does not buffer decoded items. Received data
+/// is acknowledged to the peer with HTTP/2 flow control updates as the
+/// messages are taken from the receive buffer, so a consumer that stops
+/// polling stops the updates, and the peer stalls once it has used up
+/// the stream's flow control window. The data buffered for a stream
+/// is thereby bounded by the initial stream window size, which can be
+/// set with `ClientBuilder::initial_stream_window_size`.
+///
+/// The stream is fused: after it has ended or failed with an error,
+/// polling it again yields `None`.
+pub struct ResponseStream<T, R> {
+    inner: Option<Streaming<R, tower_h2::RecvBody>>,
+    size_hint: Option<u64>,
+    handle: StreamHandle,
+    connection: Option<ConnectionStatus>,
+    method: Option<&'static str>,
+    // Number of items yielded so far, which is also the index
+    // of the next item in the stream.
+    yielded: u64,
+    // The number of items after which the stream is canceled,
+    // if set with `take_and_cancel`.
+    max_items: Option<u64>,
+    items: ItemState,
+    span: Option<RequestSpan>,
+    // The stream slot taken from the client's limit, if one is set.
+    permit: Option<StreamPermit>,
+    // The accounting of the request as one in progress on the client.
+    active: Option<ActiveRequest>,
+    cancel_token: Option<CancelToken>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, R> ResponseStream<T, R> {
+    /// Returns a handle that can be used to cancel the stream.
+    ///
+    /// After cancellation, the stream yields no more items.
+    pub fn handle(&self) -> StreamHandle {
+        self.handle.clone()
+    }
+
+    /// Returns the number of items the server has announced it will send
+    /// in this stream, or `None` if the server did not provide a hint.
+    ///
+    /// The hint is informational: the server may send a different number
+    /// of items, e.g. if its chain has changed while streaming.
+    pub fn size_hint(&self) -> Option<u64> {
+        self.size_hint
+    }
+
+    /// Limits the total encoded size of the messages received in
+    /// this stream to `max` bytes.
+    ///
+    /// Once a received message brings the total over the limit,
+    /// the stream fails with an error of kind
+    /// `ErrorKind::ResourceExhausted` instead of yielding the item,
+    /// and the HTTP/2 stream is reset. This guards against a peer
+    /// that streams an unbounded amount of data, and is independent
+    /// of the limit on the size of a single message.
+    pub fn with_max_total_bytes(mut self, max: u64) -> Self {
+        self.items.max_total_bytes = Some(max);
+        self
+    }
+
+    /// Limits the stream to the first `n` items, canceling the request
+    /// once they have been yielded.
+    ///
+    /// Unlike `Stream::take`, which merely stops polling the stream,
+    /// this resets the HTTP/2 stream with the `CANCEL` code as soon as
+    /// the `n`-th item is yielded, so the server sees the gRPC call
+    /// canceled and stops sending, and the stream slot on the connection
+    /// is released without waiting for the stream to be dropped.
+    /// The stream then ends, yielding `None`. Items that fail to convert
+    /// with the `Results` adapter count towards the limit. If `n` items
+    /// have already been yielded, the request is canceled right away.
+    pub fn take_and_cancel(mut self, n: u64) -> Self {
+        self.max_items = Some(n);
+        self.cancel_if_taken();
+        self
+    }
+
+    /// Wraps the stream into an adapter that filters out blocks with
+    /// identifiers already seen in the stream, remembering up to
+    /// `capacity` most recently seen identifiers.
+    ///
+    /// See the documentation of `DedupById` for details.
+    pub fn dedup_by_id(self, capacity: usize) -> DedupById<Self>
+    where
+        T: Block,
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        DedupById::new(self, capacity)
+    }
+
+    /// Wraps the stream into an adapter that checks that each block
+    /// has the preceding block in the stream as its parent, failing at
+    /// the first block that does not.
+    ///
+    /// See the documentation of `CheckChainLinkage` for details.
+    pub fn check_chain_linkage(self) -> CheckChainLinkage<Self>
+    where
+        T: Block,
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        CheckChainLinkage::new(self)
+    }
+
+    /// Wraps the stream into an adapter that fails with an error
+    /// of kind `ErrorKind::Timeout` if no item arrives within `timeout`
+    /// since the previous item.
+    ///
+    /// See the documentation of `IdleTimeout` for details.
+    pub fn idle_timeout(self, timeout: Duration) -> IdleTimeout<Self>
+    where
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        IdleTimeout::new(self, timeout)
+    }
+
+    /// Wraps the stream into an adapter that collects the items
+    /// into chunks of up to `max_len` items, yielding a partial chunk
+    /// when `flush_after` has elapsed since its first item arrived.
+    ///
+    /// See the documentation of `Chunks` for details.
+    pub fn chunks(self, max_len: usize, flush_after: Duration) -> Chunks<Self>
+    where
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        Chunks::new(self, max_len, flush_after)
+    }
+
+    /// Wraps the stream into an adapter that yields the failures
+    /// to deserialize individual items as `Err` items, continuing
+    /// with the rest of the stream.
+    ///
+    /// See the documentation of `Results` for details.
+    pub fn results(self) -> Results<T, R>
+    where
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        Results::new(self)
+    }
+
+    /// Wraps the stream into an adapter that sends snapshots of
+    /// the download progress to `sender` at the given `interval`,
+    /// while yielding the blocks as they are received.
+    ///
+    /// See the documentation of `Progress` for details.
+    pub fn progress(
+        self,
+        sender: mpsc::Sender<SyncProgress<T::Date>>,
+        interval: ProgressInterval,
+    ) -> Progress<T, R>
+    where
+        T: Block,
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        Progress::new(self, sender, interval)
+    }
+
+    // Returns the total encoded size of the messages received so far.
+    fn received_bytes(&self) -> u64 {
+        self.items.received_bytes
+    }
+
+    // Creates a stream over the body of a response, or a stream that
+    // is ended from the start if `inner` is `None`. The stream holds
+    // on to the stream slot in `permit` until it is dropped.
+    fn new(
+        inner: Option<Streaming<R, tower_h2::RecvBody>>,
+        size_hint: Option<u64>,
+        handle: StreamHandle,
+        context: Option<&RequestContext>,
+        permit: Option<StreamPermit>,
+    ) -> Self {
+        ResponseStream {
+            inner,
+            size_hint,
+            handle,
+            connection: context.map(|ctx| ctx.connection.clone()),
+            method: context.map(|ctx| ctx.method),
+            yielded: 0,
+            max_items: None,
+            items: ItemState::new(context.map_or(false, |ctx| ctx.verify_checksums)),
+            span: context.map(RequestContext::span),
+            permit,
+            active: None,
+            cancel_token: context.and_then(|ctx| ctx.cancel_token.clone()),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+// Name of the response header that carries the number of items
+// the server is going to stream.
+const STREAM_SIZE_HINT_HEADER: &str = "stream-size-hint";
+
+fn convert_error<T>(e: tower_grpc::Error<T>) -> core_client::Error
+where
+    T: Debug + Send + Sync + 'static,
+{
+    match e {
+        tower_grpc::Error::Grpc(status) => {
+            let code = status.code();
+            let kind = match code {
+                Code::DeadlineExceeded => core_client::ErrorKind::Timeout,
+                Code::Unavailable => core_client::ErrorKind::Unavailable,
+                Code::NotFound => core_client::ErrorKind::NotFound,
+                Code::ResourceExhausted => core_client::ErrorKind::ResourceExhausted,
+                Code::Unauthenticated => core_client::ErrorKind::Unauthenticated,
+                Code::Unimplemented => core_client::ErrorKind::Unimplemented,
+                _ => core_client::ErrorKind::Rpc,
+            };
+            let msg = format!("gRPC status {:?}: {}", code, status.error_message());
+            // The status as decoded by tower-grpc does not retain
+            // the other metadata of the response, so a retry hint
+            // the peer sends alongside it is read from the response
+            // headers by the service stack and attached by the caller.
+            core_client::Error::new(kind, msg)
+        }
+        // Transport and HTTP/2 protocol errors are not mapped from
+        // a status returned by the peer.
+        e => core_client::Error::new(core_client::ErrorKind::Rpc, e),
+    }
+}
+
+// Returned instead of panicking when a response future is polled again
+// after it has resolved, which some combinators do on spurious wakeups.
+fn polled_after_finish() -> core_client::Error {
+    core_client::Error::new(
+        core_client::ErrorKind::Internal,
+        "polled a finished response",
+    )
+}
+
+// Returned when a response stream exceeds the limit set with
+// `ResponseStream::with_max_total_bytes`.
+fn total_bytes_exceeded(max: u64) -> core_client::Error {
+    core_client::Error::new(
+        core_client::ErrorKind::ResourceExhausted,
+        format!("the response stream exceeded the limit of {} bytes", max),
+    )
+}
+
+// Returned when a request is canceled with the client's cancellation token.
+fn request_canceled() -> core_client::Error {
+    core_client::Error::new(
+        core_client::ErrorKind::Canceled,
+        "the request has been canceled with the client's cancellation token",
+    )
+}
+
+fn poll_deadline<T>(deadline: &mut Option<Delay>) -> Poll<T, core_client::Error> {
+    match deadline {
+        None => Ok(Async::NotReady),
+        Some(delay) => match delay.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(())) => Err(core_client::Error::new(
+                core_client::ErrorKind::Timeout,
+                "the deadline has elapsed",
+            )),
+            Err(e) => Err(core_client::Error::new(core_client::ErrorKind::Rpc, e)),
+        },
+    }
+}
+
+pub trait ConvertResponse<T> {
+    fn convert_response(self) -> Result<T, core_client::Error>;
+
+    /// Verifies the checksum of the message content, if the message
+    /// carries one. This is done before the conversion when enabled
+    /// with `ClientBuilder::verify_block_checksums`.
+    ///
+    /// The default implementation accepts any message.
+    fn verify_checksum(&self) -> Result<(), core_client::Error> {
+        Ok(())
+    }
+
+    /// Takes the serialized identifier of the item out of the message,
+    /// if the message carries one, before the message is converted.
+    /// This is how a `ResponseStream` keeps track of the last item
+    /// without deserializing or re-serializing its identifier.
+    ///
+    /// The default implementation returns `None`.
+    fn take_item_id(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Takes the continuation token that the peer may send with
+    /// the item out of the message.
+    ///
+    /// The default implementation returns `None`.
+    fn take_continuation_token(&mut self) -> Option<Bytes> {
+        None
+    }
+}
+
+mod unary_future {
+    use super::{
+        convert_error, core_client, poll_deadline, poll_in_span, polled_after_finish,
+        request_canceled, ConvertResponse, GrpcError, GrpcFuture, RequestContext, ResponseFuture,
+        RetryPolicy,
+    };
+    use futures::prelude::*;
+    use std::{
+        fmt,
+        marker::PhantomData,
+        mem,
+        time::{Duration, Instant},
+    };
+    use tokio::timer::Delay;
+    use tower_grpc::Response;
+
+    pub fn poll_and_convert_response<T, R, F>(
+        future: &mut F,
+        context: Option<&RequestContext>,
+    ) -> Poll<T, core_client::Error>
+    where
+        F: Future<Item = Response<R>, Error = GrpcError>,
+        R: ConvertResponse<T>,
+    {
+        let res = future.poll();
+        if let Some(context) = context {
+            context.connection.observe(&res);
+        }
+        match res {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(res)) => {
+                let message = res.into_inner();
+                if context.map_or(false, |ctx| ctx.verify_checksums) {
+                    message.verify_checksum()?;
+                }
+                let item = message.convert_response()?;
+                Ok(Async::Ready(item))
+            }
+            Err(e) => {
+                let err = convert_error(e);
+                match context.and_then(|ctx| ctx.retry_hint.take()) {
+                    Some(delay) => Err(err.with_retry_after(delay)),
+                    None => Err(err),
+                }
+            }
+        }
+    }
+
+    // Re-issues a unary call after a transient failure.
+    // The closure is passed the timeout to send with the request.
+    pub struct Retry<R> {
+        policy: RetryPolicy,
+        failed_attempts: u32,
+        call: Box<dyn FnMut(Option<Duration>) -> GrpcFuture<R> + Send>,
+    }
+
+    impl<R> Retry<R> {
+        pub fn new(
+            policy: RetryPolicy,
+            call: Box<dyn FnMut(Option<Duration>) -> GrpcFuture<R> + Send>,
+        ) -> Self {
+            Retry {
+                policy,
+                failed_attempts: 0,
+                call,
+            }
+        }
+    }
+
+    pub enum State<T, R> {
+        Pending(GrpcFuture<R>),
+        Waiting(Delay),
+        Failed(core_client::Error),
+        Finished(PhantomData<T>),
+    }
+
+    impl<T, R> ResponseFuture<T, R> {
+        // Schedules a retry of the call if the error is transient and
+        // the retry can be made before the deadline.
+        fn schedule_retry(&mut self, err: &core_client::Error) -> bool {
+            let retry = match self.retry {
+                Some(ref mut retry) => retry,
+                None => return false,
+            };
+            if !retry.policy.is_retryable(err) {
+                return false;
+            }
+            retry.failed_attempts += 1;
+            let delay = match retry.policy.delay_after(retry.failed_attempts, err) {
+                Some(delay) => delay,
+                None => return false,
+            };
+            let retry_at = Instant::now() + delay;
+            if let Some(ref deadline) = self.deadline {
+                if retry_at >= deadline.deadline() {
+                    return false;
+                }
+            }
+            self.state = State::Waiting(Delay::new(retry_at));
+            true
+        }
+
+        fn reissue(&mut self) -> GrpcFuture<R> {
+            let timeout = self.deadline.as_ref().map(|deadline| {
+                let deadline = deadline.deadline();
+                let now = Instant::now();
+                if deadline > now {
+                    deadline - now
+                } else {
+                    Duration::from_secs(0)
+                }
+            });
+            let retry = self
+                .retry
+                .as_mut()
+                .expect("waiting to retry without a retry policy");
+            (retry.call)(timeout)
+        }
+
+        fn finish(&mut self, res: Poll<T, core_client::Error>) -> Poll<T, core_client::Error> {
+            let res = match (res, self.check.take()) {
+                (Ok(Async::Ready(item)), Some(check)) => check(&item).map(|()| Async::Ready(item)),
+                (res, _) => res,
+            };
+            if let Some(context) = self.context.take() {
+                context.finish(&res);
+            }
+            self.state = State::Finished(PhantomData);
+            res
+        }
+
+        fn poll_response(&mut self) -> Poll<T, core_client::Error>
+        where
+            R: prost::Message + Default + ConvertResponse<T>,
+        {
+            loop {
+                let in_progress = match self.state {
+                    State::Pending(_) | State::Waiting(_) => true,
+                    State::Failed(_) | State::Finished(_) => false,
+                };
+                if in_progress
+                    && self
+                        .context
+                        .as_mut()
+                        .map_or(false, RequestContext::poll_canceled)
+                {
+                    // Dropping the pending request resets the HTTP/2 stream.
+                    return self.finish(Err(request_canceled()));
+                }
+                if let State::Waiting(ref mut delay) = self.state {
+                    match delay.poll() {
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Ok(Async::Ready(())) => {}
+                        Err(e) => {
+                            let err = core_client::Error::new(core_client::ErrorKind::Rpc, e);
+                            return self.finish(Err(err));
+                        }
+                    }
+                    self.state = State::Pending(self.reissue());
+                }
+                if let State::Pending(ref mut f) = self.state {
+                    // The response is polled before the deadline, so that
+                    // a response arriving together with the expiry of the timer
+                    // is still delivered.
+                    let mut res = poll_and_convert_response(f, self.context.as_ref());
+                    if let Ok(Async::NotReady) = res {
+                        res = poll_deadline(&mut self.deadline);
+                        if let Ok(Async::NotReady) = res {
+                            return Ok(Async::NotReady);
+                        }
+                    }
+                    if let Err(ref e) = res {
+                        if self.schedule_retry(e) {
+                            continue;
+                        }
+                    }
+                    return self.finish(res);
+                } else {
+                    return match mem::replace(&mut self.state, State::Finished(PhantomData)) {
+                        State::Pending(_) | State::Waiting(_) => unreachable!(),
+                        State::Failed(e) => Err(e),
+                        State::Finished(_) => Err(polled_after_finish()),
+                    };
+                }
+            }
+        }
+    }
+
+    impl<T, R> Future for ResponseFuture<T, R>
+    where
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        type Item = T;
+        type Error = core_client::Error;
+
+        fn poll(&mut self) -> Poll<T, core_client::Error> {
+            let span = self.context.as_ref().map(RequestContext::span);
+            poll_in_span(span, || self.poll_response())
+        }
+    }
+
+    impl<T, R> fmt::Debug for ResponseFuture<T, R> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let state = match self.state {
+                State::Pending(_) => "pending",
+                State::Waiting(_) => "waiting to retry",
+                State::Failed(_) => "failed",
+                State::Finished(_) => "finished",
+            };
+            let failed_attempts = self.retry.as_ref().map_or(0, |retry| retry.failed_attempts);
+            f.debug_struct("ResponseFuture")
+                .field("method", &self.context.as_ref().map(|ctx| ctx.method))
+                .field("state", &format_args!("{}", state))
+                .field("failed_attempts", &failed_attempts)
+                .finish()
+        }
+    }
+}
+
+mod client_streaming_future {
+    use super::{
+        core_client, poll_in_span, polled_after_finish, request_canceled,
+        unary_future::poll_and_convert_response, ClientStreamingFuture, ConvertResponse,
+        GrpcClientStreamingFuture, RequestContext,
+    };
+    use futures::prelude::*;
+    use std::{marker::PhantomData, mem};
+
+    pub enum State<T, R> {
+        Pending(GrpcClientStreamingFuture<R>),
+        Failed(core_client::Error),
+        Finished(PhantomData<T>),
+    }
+
+    impl<T, R> ClientStreamingFuture<T, R>
+    where
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        fn poll_response(&mut self) -> Poll<T, core_client::Error> {
+            if let State::Pending(ref mut f) = self.state {
+                let res = if self
+                    .context
+                    .as_mut()
+                    .map_or(false, RequestContext::poll_canceled)
+                {
+                    // Dropping the pending request resets the HTTP/2 stream.
+                    Err(request_canceled())
+                } else {
+                    let res = poll_and_convert_response(f, self.context.as_ref());
+                    if let Ok(Async::NotReady) = res {
+                        return Ok(Async::NotReady);
+                    }
+                    res
+                };
+                if let Some(context) = self.context.take() {
+                    context.finish(&res);
+                }
+                self.state = State::Finished(PhantomData);
+                res
+            } else {
+                match mem::replace(&mut self.state, State::Finished(PhantomData)) {
+                    State::Pending(_) => unreachable!(),
+                    State::Failed(e) => Err(e),
+                    State::Finished(_) => Err(polled_after_finish()),
+                }
+            }
+        }
+    }
+
+    impl<T, R> Future for ClientStreamingFuture<T, R>
+    where
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        type Item = T;
+        type Error = core_client::Error;
+
+        fn poll(&mut self) -> Poll<T, core_client::Error> {
+            let span = self.context.as_ref().map(RequestContext::span);
+            poll_in_span(span, || self.poll_response())
+        }
+    }
+}
+
+mod stream_future {
+    use super::{
+        convert_error, core_client, poll_in_span, polled_after_finish, request_canceled, GrpcError,
+        GrpcStreamFuture, RequestContext, ResponseStream, ResponseStreamFuture, ServiceFuture,
+        StreamHandle, StreamLimit, StreamPermit, STREAM_SIZE_HINT_HEADER,
+    };
+    use futures::prelude::*;
+    use std::{fmt, marker::PhantomData, mem};
+    use tower_grpc::{
+        client::{server_streaming, streaming},
+        Response, Streaming,
+    };
+
+    // The response future of a call to a method with a streamed
+    // response, made with a single request message or with a stream
+    // of request messages.
+    pub enum StreamingCall<R> {
+        ServerStreaming(server_streaming::ResponseFuture<R, ServiceFuture>),
+        Bidirectional(streaming::ResponseFuture<R, ServiceFuture>),
+    }
+
+    impl<R> From<server_streaming::ResponseFuture<R, ServiceFuture>> for StreamingCall<R> {
+        fn from(future: server_streaming::ResponseFuture<R, ServiceFuture>) -> Self {
+            StreamingCall::ServerStreaming(future)
+        }
+    }
+
+    impl<R> From<streaming::ResponseFuture<R, ServiceFuture>> for StreamingCall<R> {
+        fn from(future: streaming::ResponseFuture<R, ServiceFuture>) -> Self {
+            StreamingCall::Bidirectional(future)
+        }
+    }
+
+    impl<R> Future for StreamingCall<R>
+    where
+        R: prost::Message + Default,
+    {
+        type Item = Response<Streaming<R, tower_h2::RecvBody>>;
+        type Error = GrpcError;
+
+        fn poll(&mut self) -> Poll<Self::Item, GrpcError> {
+            match self {
+                StreamingCall::ServerStreaming(f) => f.poll(),
+                StreamingCall::Bidirectional(f) => f.poll(),
+            }
+        }
+    }
+
+    fn poll_and_convert_response<T, R, F>(
+        future: &mut F,
+        handle: &StreamHandle,
+        context: Option<&RequestContext>,
+        permit: &mut Option<StreamPermit>,
+    ) -> Poll<ResponseStream<T, R>, core_client::Error>
+    where
+        F: Future<Item = Response<Streaming<R, tower_h2::RecvBody>>, Error = GrpcError>,
+    {
+        let res = future.poll();
+        if let Some(context) = context {
+            context.connection.observe(&res);
+        }
+        match res {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(res)) => {
+                let res = res.into_http();
+                let size_hint = res
+                    .headers()
+                    .get(STREAM_SIZE_HINT_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|s| s.parse().ok());
+                let stream = ResponseStream::new(
+                    Some(res.into_body()),
+                    size_hint,
+                    handle.clone(),
+                    context,
+                    permit.take(),
+                );
+                Ok(Async::Ready(stream))
+            }
+            Err(e) => Err(convert_error(e)),
+        }
+    }
+
+    pub enum State<T, R> {
+        // Waiting for a stream slot to make the call.
+        Queued(StreamLimit, Box<dyn FnMut() -> GrpcStreamFuture<R> + Send>),
+        Pending(GrpcStreamFuture<R>),
+        Failed(core_client::Error),
+        Finished(PhantomData<T>),
+    }
+
+    impl<T, R> ResponseStreamFuture<T, R>
+    where
+        R: prost::Message + Default,
+    {
+        fn poll_response(&mut self) -> Poll<ResponseStream<T, R>, core_client::Error> {
+            let canceled = match self.state {
+                State::Queued(..) | State::Pending(_) => self
+                    .context
+                    .as_mut()
+                    .map_or(false, RequestContext::poll_canceled),
+                State::Failed(_) | State::Finished(_) => false,
+            };
+            if let State::Queued(ref limit, ref mut call) = self.state {
+                if !canceled && !self.handle.poll_canceled() {
+                    match limit.poll_acquire() {
+                        Async::NotReady => return Ok(Async::NotReady),
+                        Async::Ready(permit) => {
+                            self.permit = Some(permit);
+                            let future = call();
+                            self.state = State::Pending(future);
+                        }
+                    }
+                }
+            }
+            let mut res = match self.state {
+                // Dropping the pending request resets the HTTP/2 stream.
+                _ if canceled => Err(request_canceled()),
+                State::Queued(..) | State::Pending(_) if self.handle.poll_canceled() => {
+                    // Dropping the pending request resets the HTTP/2 stream.
+                    let stream = ResponseStream::new(None, None, self.handle.clone(), None, None);
+                    if let Some(ref context) = self.context {
+                        context.span.record_canceled();
+                    }
+                    Ok(Async::Ready(stream))
+                }
+                State::Pending(ref mut f) => {
+                    let res = poll_and_convert_response(
+                        f,
+                        &self.handle,
+                        self.context.as_ref(),
+                        &mut self.permit,
+                    );
+                    if let Ok(Async::NotReady) = res {
+                        return Ok(Async::NotReady);
+                    }
+                    res
+                }
+                _ => {
+                    return match mem::replace(&mut self.state, State::Finished(PhantomData)) {
+                        State::Queued(..) | State::Pending(_) => unreachable!(),
+                        State::Failed(e) => Err(e),
+                        State::Finished(_) => Err(polled_after_finish()),
+                    };
+                }
+            };
+            if let Some(mut context) = self.context.take() {
+                // An opened stream is counted as in progress until
+                // it is finished or dropped.
+                if let Ok(Async::Ready(ref mut stream)) = res {
+                    if stream.inner.is_some() {
+                        stream.active = context.active.take();
+                    }
+                }
+                context.finish_stream(&res);
+            }
+            // Unless it has been passed on to the response stream,
+            // the stream slot is released.
+            self.permit = None;
+            self.state = State::Finished(PhantomData);
+            res
+        }
+    }
+
+    impl<T, R> Future for ResponseStreamFuture<T, R>
+    where
+        R: prost::Message + Default,
+    {
+        type Item = ResponseStream<T, R>;
+        type Error = core_client::Error;
+
+        fn poll(&mut self) -> Poll<ResponseStream<T, R>, core_client::Error> {
+            let span = self.context.as_ref().map(RequestContext::span);
+            poll_in_span(span, || self.poll_response())
+        }
+    }
+
+    impl<T, R> fmt::Debug for ResponseStreamFuture<T, R> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let state = match self.state {
+                State::Queued(..) => "queued",
+                State::Pending(_) => "pending",
+                State::Failed(_) => "failed",
+                State::Finished(_) => "finished",
+            };
+            f.debug_struct("ResponseStreamFuture")
+                .field("method", &self.context.as_ref().map(|ctx| ctx.method))
+                .field("state", &format_args!("{}", state))
+                .finish()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::gen;
+
+        use futures::future;
+
+        #[test]
+        fn polling_past_completion_is_well_defined() {
+            // A queued request canceled before it is made resolves
+            // to an empty stream.
+            let limit = StreamLimit::new(1);
+            let mut future = ResponseStreamFuture::<u64, gen::node::UploadBlocksResponse>::queued(
+                limit,
+                Box::new(|| panic!("the canceled request should not be made")),
+            );
+            future.handle().cancel();
+            future::lazy(move || {
+                let mut stream = match future.poll() {
+                    Ok(Async::Ready(stream)) => stream,
+                    res => panic!("unexpected poll result {:?}", res),
+                };
+                for _ in 0..3 {
+                    match future.poll() {
+                        Err(ref e) => match e.kind() {
+                            core_client::ErrorKind::Internal => {}
+                            kind => panic!("unexpected error kind {:?}", kind),
+                        },
+                        res => panic!("unexpected poll result {:?}", res),
+                    }
+                }
+                for _ in 0..3 {
+                    match stream.poll() {
+                        Ok(Async::Ready(None)) => {}
+                        res => panic!("unexpected poll result {:?}", res),
+                    }
+                }
+                Ok::<_, ()>(())
+            })
+            .wait()
+            .unwrap();
+        }
+    }
+}
+
+mod stream {
+    use super::{
+        convert_error, core_client, poll_in_span, request_canceled, total_bytes_exceeded,
+        CancelToken, ConnectionStatus, ConvertResponse, GrpcStreamError, InvalidStreamItem,
+        ResponseStream,
+    };
+    use bytes::Bytes;
+    use futures::prelude::*;
+    use std::fmt;
+
+    // The state of a response stream that is updated with every
+    // message received.
+    #[derive(Default)]
+    pub(super) struct ItemState {
+        // Total encoded size of the messages received so far.
+        pub(super) received_bytes: u64,
+        // The limit on the total size of received messages, if one is set.
+        pub(super) max_total_bytes: Option<u64>,
+        // Whether the checksums sent by the peer with the items are verified.
+        verify_checksums: bool,
+        // The serialized identifier of the last item yielded, if the peer
+        // has sent it with the item.
+        pub(super) last_item_id: Option<Vec<u8>>,
+        // The latest continuation token sent by the peer with an item.
+        pub(super) continuation_token: Option<Bytes>,
+    }
+
+    impl ItemState {
+        pub(super) fn new(verify_checksums: bool) -> Self {
+            ItemState {
+                verify_checksums,
+                ..ItemState::default()
+            }
+        }
+
+        // Polls the stream for the next message and converts it.
+        //
+        // tower-grpc reports a non-OK status in the trailers of the response
+        // as an error of the stream, so a stream that the peer ends with
+        // an error status fails here rather than ending cleanly.
+        // A message that fails to convert is returned as an `Err` item,
+        // leaving it to the caller to decide whether that ends the stream.
+        fn poll_item<T, S, R>(
+            &mut self,
+            stream: &mut S,
+            connection: Option<&ConnectionStatus>,
+        ) -> Poll<Option<Result<T, core_client::Error>>, core_client::Error>
+        where
+            S: Stream<Item = R, Error = GrpcStreamError>,
+            R: prost::Message + ConvertResponse<T>,
+        {
+            let res = stream.poll();
+            if let Some(connection) = connection {
+                connection.observe(&res);
+            }
+            match res {
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+                Ok(Async::Ready(Some(item))) => {
+                    self.received_bytes += item.encoded_len() as u64;
+                    if let Some(max) = self.max_total_bytes {
+                        if self.received_bytes > max {
+                            return Err(total_bytes_exceeded(max));
+                        }
+                    }
+                    Ok(Async::Ready(Some(self.convert_item(item))))
+                }
+                Err(e) => Err(convert_error(e)),
+            }
+        }
+
+        fn convert_item<T, R>(&mut self, mut item: R) -> Result<T, core_client::Error>
+        where
+            R: ConvertResponse<T>,
+        {
+            if self.verify_checksums {
+                item.verify_checksum()?;
+            }
+            let id = item.take_item_id();
+            let token = item.take_continuation_token();
+            let item = item.convert_response()?;
+            self.last_item_id = id;
+            if token.is_some() {
+                self.continuation_token = token;
+            }
+            Ok(item)
+        }
+    }
+
+    // Fails the stream with the error of an item that failed to convert.
+    fn item_error_as_stream_error<T>(
+        res: Poll<Option<Result<T, core_client::Error>>, core_client::Error>,
+    ) -> Poll<Option<T>, core_client::Error> {
+        match res {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+            Ok(Async::Ready(Some(Ok(item)))) => Ok(Async::Ready(Some(item))),
+            Ok(Async::Ready(Some(Err(e)))) | Err(e) => Err(e),
+        }
+    }
+
+    impl<T, R> ResponseStream<T, R> {
+        // Attributes an error in converting the item to its position
+        // in the stream.
+        fn attribute_error(&self, e: core_client::Error) -> core_client::Error {
+            match e.kind() {
+                core_client::ErrorKind::Format => core_client::Error::new(
+                    core_client::ErrorKind::Format,
+                    InvalidStreamItem {
+                        index: self.yielded,
+                        method: self.method,
+                        source: e,
+                    },
+                ),
+                _ => e,
+            }
+        }
+
+        fn count_item(
+            &mut self,
+            res: Poll<Option<T>, core_client::Error>,
+        ) -> Poll<Option<T>, core_client::Error> {
+            let res = match res {
+                Ok(Async::Ready(Some(item))) => {
+                    self.yielded += 1;
+                    Ok(Async::Ready(Some(item)))
+                }
+                Err(e) => Err(self.attribute_error(e)),
+                res => res,
+            };
+            match res {
+                Ok(Async::Ready(None)) | Err(_) => {
+                    // The stream is fused: once it has ended or failed,
+                    // it yields `None` on every poll. Dropping the response
+                    // body of a failed stream resets the HTTP/2 stream.
+                    if self.inner.take().is_some() {
+                        if let Some(ref span) = self.span {
+                            span.record_items(self.yielded);
+                            span.record_status(&res);
+                        }
+                    }
+                    self.permit = None;
+                    self.active = None;
+                }
+                _ => {}
+            }
+            res
+        }
+
+        // Counts an item that failed to convert as yielded,
+        // without ending the stream.
+        fn count_result(
+            &mut self,
+            res: Poll<Option<Result<T, core_client::Error>>, core_client::Error>,
+        ) -> Poll<Option<Result<T, core_client::Error>>, core_client::Error> {
+            if let Ok(Async::Ready(Some(Err(e)))) = res {
+                let e = self.attribute_error(e);
+                self.yielded += 1;
+                return Ok(Async::Ready(Some(Err(e))));
+            }
+            self.count_item(item_error_as_stream_error(res))
+                .map(|polled| polled.map(|item| item.map(Ok)))
+        }
+
+        // Polls for the next item, as by the `Results` adapter.
+        pub(super) fn poll_result(
+            &mut self,
+        ) -> Poll<Option<Result<T, core_client::Error>>, core_client::Error>
+        where
+            R: prost::Message + Default + ConvertResponse<T>,
+        {
+            poll_in_span(self.span.clone(), || {
+                let res = self.poll_converted();
+                let res = self.count_result(res);
+                self.cancel_if_taken();
+                res
+            })
+        }
+
+        fn poll_item(&mut self) -> Poll<Option<T>, core_client::Error>
+        where
+            R: prost::Message + Default + ConvertResponse<T>,
+        {
+            let res = self.poll_converted();
+            let res = self.count_item(item_error_as_stream_error(res));
+            self.cancel_if_taken();
+            res
+        }
+
+        // Cancels the request once the number of items set with
+        // `take_and_cancel` has been yielded.
+        pub(super) fn cancel_if_taken(&mut self) {
+            if self.max_items.map_or(false, |max| self.yielded >= max) {
+                self.cancel();
+            }
+        }
+
+        fn cancel(&mut self) {
+            // Dropping the response body resets the HTTP/2 stream.
+            self.permit = None;
+            self.active = None;
+            if self.inner.take().is_some() {
+                if let Some(ref span) = self.span {
+                    span.record_items(self.yielded);
+                    span.record_canceled();
+                }
+            }
+        }
+
+        // Polls for the next message and converts it, returning
+        // a failure to convert it as an item.
+        fn poll_converted(
+            &mut self,
+        ) -> Poll<Option<Result<T, core_client::Error>>, core_client::Error>
+        where
+            R: prost::Message + Default + ConvertResponse<T>,
+        {
+            if self.inner.is_some() && self.handle.poll_canceled() {
+                self.cancel();
+            }
+            if self.inner.is_some()
+                && self
+                    .cancel_token
+                    .as_mut()
+                    .map_or(false, CancelToken::poll_canceled)
+            {
+                // The response body is dropped when the error is counted,
+                // which resets the HTTP/2 stream.
+                return Err(request_canceled());
+            }
+            // Exceeding the limit on the total size fails the stream,
+            // so the response body gets dropped when the error is counted.
+            match self.inner {
+                None => Ok(Async::Ready(None)),
+                Some(ref mut inner) => self.items.poll_item(inner, self.connection.as_ref()),
+            }
+        }
+    }
+
+    impl<T, R> Stream for ResponseStream<T, R>
+    where
+        R: prost::Message + Default + ConvertResponse<T>,
+    {
+        type Item = T;
+        type Error = core_client::Error;
+
+        fn poll(&mut self) -> Poll<Option<T>, core_client::Error> {
+            poll_in_span(self.span.clone(), || self.poll_item())
+        }
+    }
+
+    impl<T, R> fmt::Debug for ResponseStream<T, R> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let state = if self.inner.is_some() {
+                "active"
+            } else {
+                "finished"
+            };
+            f.debug_struct("ResponseStream")
+                .field("method", &self.method)
+                .field("state", &format_args!("{}", state))
+                .field("yielded", &self.yielded)
+                .field("received_bytes", &self.items.received_bytes)
+                .field("size_hint", &self.size_hint)
+                .finish()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::gen;
+
+        use crate::client::raw::RawBlock;
+        use crate::client::{StreamHandle, StreamLimit};
+
+        use network_core::peer::Peer;
+
+        use futures::stream;
+        use tower_grpc::{Code, Status};
+
+        // A stub of the message stream decoded by tower-grpc for
+        // a response whose trailers carry the status `code`.
+        fn stub_stream(
+            code: Code,
+        ) -> impl Stream<Item = gen::node::UploadBlocksResponse, Error = GrpcStreamError> {
+            let trailer = match code {
+                Code::Ok => None,
+                code => Some(Err(tower_grpc::Error::Grpc(Status::with_code(code)))),
+            };
+            let messages = vec![Ok(gen::node::UploadBlocksResponse { accepted: 1 })];
+            stream::iter_result(messages.into_iter().chain(trailer))
+        }
+
+        // Polls for the next item as a stream with no connection does.
+        fn poll_with<T, S, R>(
+            items: &mut ItemState,
+            stream: &mut S,
+        ) -> Poll<Option<Result<T, core_client::Error>>, core_client::Error>
+        where
+            S: Stream<Item = R, Error = GrpcStreamError>,
+            R: prost::Message + ConvertResponse<T>,
+        {
+            items.poll_item(stream, None)
+        }
+
+        fn poll_twice<S>(stream: &mut S) -> Poll<Option<u64>, core_client::Error>
+        where
+            S: Stream<Item = gen::node::UploadBlocksResponse, Error = GrpcStreamError>,
+        {
+            let mut items = ItemState::default();
+            match poll_with(&mut items, stream) {
+                Ok(Async::Ready(Some(Ok(1)))) => {}
+                res => panic!("unexpected first poll result {:?}", res),
+            }
+            item_error_as_stream_error(poll_with(&mut items, stream))
+        }
+
+        #[test]
+        fn error_status_in_trailers_fails_the_stream() {
+            match poll_twice(&mut stub_stream(Code::Internal)) {
+                Err(ref e) => match e.kind() {
+                    core_client::ErrorKind::Rpc => {}
+                    kind => panic!("unexpected error kind {:?}", kind),
+                },
+                res => panic!("unexpected poll result {:?}", res),
+            }
+        }
+
+        #[test]
+        fn ok_status_in_trailers_ends_the_stream() {
+            match poll_twice(&mut stub_stream(Code::Ok)) {
+                Ok(Async::Ready(None)) => {}
+                res => panic!("unexpected poll result {:?}", res),
+            }
+        }
+
+        #[test]
+        fn conversion_failure_is_returned_as_an_item() {
+            let messages = vec![
+                Ok(gen::node::Gossip {
+                    node_id: Vec::new(),
+                    address: "127.0.0.1:3000".into(),
+                }),
+                Ok(gen::node::Gossip {
+                    node_id: vec![1],
+                    address: "127.0.0.1:3001".into(),
+                }),
+            ];
+            let mut stream = stream::iter_result(messages);
+            let mut items = ItemState::default();
+            match poll_with::<Peer, _, _>(&mut items, &mut stream) {
+                Ok(Async::Ready(Some(Err(ref e)))) => match e.kind() {
+                    core_client::ErrorKind::Format => {}
+                    kind => panic!("unexpected error kind {:?}", kind),
+                },
+                res => panic!("unexpected poll result {:?}", res),
+            }
+            match poll_with::<Peer, _, _>(&mut items, &mut stream) {
+                Ok(Async::Ready(Some(Ok(peer)))) => assert_eq!(peer.node_id, vec![1]),
+                res => panic!("unexpected poll result {:?}", res),
+            }
+            match poll_with::<Peer, _, _>(&mut items, &mut stream) {
+                Ok(Async::Ready(None)) => {}
+                res => panic!("unexpected poll result {:?}", res),
+            }
+        }
+
+        #[test]
+        fn take_and_cancel_releases_the_stream_slot() {
+            let limit = StreamLimit::new(1);
+            let stream = ResponseStream::<u64, gen::node::UploadBlocksResponse>::new(
+                None,
+                None,
+                StreamHandle::new(),
+                None,
+                limit.try_acquire(),
+            );
+            assert!(limit.try_acquire().is_none());
+            let mut stream = stream.take_and_cancel(0);
+            assert!(limit.try_acquire().is_some());
+            match stream.poll() {
+                Ok(Async::Ready(None)) => {}
+                res => panic!("unexpected poll result {:?}", res),
+            }
+        }
+
+        #[test]
+        fn block_with_mismatching_checksum_fails_to_convert() {
+            let content = vec![1, 2, 3];
+            let block = |checksum| {
+                Ok(gen::node::Block {
+                    content: content.clone(),
+                    id: Vec::new(),
+                    serve_info: None,
+                    checksum,
+                    continuation_token: Vec::new(),
+                })
+            };
+            let checksum = crc32fast::hash(&content).to_be_bytes().to_vec();
+            let messages = vec![block(checksum), block(vec![0, 0, 0, 0]), block(Vec::new())];
+            let mut stream = stream::iter_result(messages);
+            let mut items = ItemState::new(true);
+            match poll_with::<RawBlock, _, _>(&mut items, &mut stream) {
+                Ok(Async::Ready(Some(Ok(_)))) => {}
+                res => panic!("unexpected poll result {:?}", res.map(|_| ())),
+            }
+            match poll_with::<RawBlock, _, _>(&mut items, &mut stream) {
+                Ok(Async::Ready(Some(Err(ref e)))) => match e.kind() {
+                    core_client::ErrorKind::Format => {}
+                    kind => panic!("unexpected error kind {:?}", kind),
+                },
+                res => panic!("unexpected poll result {:?}", res.map(|_| ())),
+            }
+            // A block without a checksum is not verified.
+            match poll_with::<RawBlock, _, _>(&mut items, &mut stream) {
+                Ok(Async::Ready(Some(Ok(_)))) => {}
+                res => panic!("unexpected poll result {:?}", res.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn latest_continuation_token_is_kept() {
+            let block = |token: &[u8]| {
+                Ok(gen::node::Block {
+                    content: vec![1, 2, 3],
+                    id: Vec::new(),
+                    serve_info: None,
+                    checksum: Vec::new(),
+                    continuation_token: token.to_vec(),
+                })
+            };
+            let messages = vec![block(b"a"), block(b""), block(b"b"), block(b"")];
+            let mut stream = stream::iter_result(messages);
+            let mut items = ItemState::default();
+            let mut expected = vec!["a", "a", "b", "b"].into_iter();
+            loop {
+                match poll_with::<RawBlock, _, _>(&mut items, &mut stream) {
+                    Ok(Async::Ready(Some(Ok(_)))) => {
+                        let expected = expected.next().map(Bytes::from);
+                        assert_eq!(items.continuation_token, expected);
+                    }
+                    Ok(Async::Ready(None)) => break,
+                    res => panic!("unexpected poll result {:?}", res.map(|_| ())),
+                }
+            }
+            assert!(expected.next().is_none());
+        }
+
+        #[test]
+        fn stream_fails_past_max_total_bytes() {
+            // Each message is encoded in 2 bytes: the field tag
+            // and the varint value.
+            let messages = (0..5).map(|_| Ok(gen::node::UploadBlocksResponse { accepted: 1 }));
+            let mut stream = stream::iter_result(messages);
+            let mut items = ItemState::default();
+            items.max_total_bytes = Some(6);
+            for _ in 0..3 {
+                match poll_with::<u64, _, _>(&mut items, &mut stream) {
+                    Ok(Async::Ready(Some(Ok(1)))) => {}
+                    res => panic!("unexpected poll result {:?}", res),
+                }
+            }
+            assert_eq!(items.received_bytes, 6);
+            match poll_with::<u64, _, _>(&mut items, &mut stream) {
+                Err(ref e) => match e.kind() {
+                    core_client::ErrorKind::ResourceExhausted => {}
+                    kind => panic!("unexpected error kind {:?}", kind),
+                },
+                res => panic!("unexpected poll result {:?}", res),
+            }
+        }
+    }
+}
+
+// Request stream that serializes the items from the wrapped stream
+// on demand, as the request body is polled for more data, and wraps
+// the bytes into request messages.
+struct RequestStream<S, M> {
+    inner: S,
+    message: fn(Vec<u8>) -> M,
+}
+
+impl<S, M> RequestStream<S, M> {
+    fn new(inner: S, message: fn(Vec<u8>) -> M) -> Self {
+        RequestStream { inner, message }
+    }
+}
+
+impl<S, M> Stream for RequestStream<S, M>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Serialize,
+    <S::Item as Serialize>::Error: Send + Sync + 'static,
+{
+    type Item = M;
+    type Error = tower_grpc::Error;
+
+    fn poll(&mut self) -> Poll<Option<M>, tower_grpc::Error> {
+        let item = match self.inner.poll() {
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Ok(Async::Ready(None)) => return Ok(Async::Ready(None)),
+            Ok(Async::Ready(Some(item))) => item,
+            Err(e) => return Err(abort_request(e)),
+        };
+        let bytes = serialize_to_bytes(&item).map_err(abort_request)?;
+        Ok(Async::Ready(Some((self.message)(bytes))))
+    }
+}
+
+fn abort_request(e: core_client::Error) -> tower_grpc::Error {
+    let msg = match error::Error::source(&e) {
+        Some(source) => format!("{}: {}", e, source),
+        None => e.to_string(),
+    };
+    let status = tower_grpc::Status::with_code_and_message(Code::Aborted, msg);
+    tower_grpc::Error::Grpc(status)
+}
+
+fn deserialize_bytes<T>(mut buf: &[u8]) -> Result<T, core_client::Error>
+where
+    T: Deserialize,
+    T::Error: Send + Sync + 'static,
+{
+    T::deserialize(&mut buf).map_err(|e| core_client::Error::new(core_client::ErrorKind::Format, e))
+}
+
+// Compares the checksum sent by the peer with the block, if any,
+// against the checksum of the received content.
+fn verify_block_checksum(block: &gen::node::Block) -> Result<(), core_client::Error> {
+    if block.checksum.is_empty() {
+        return Ok(());
+    }
+    if block.checksum[..] == crc32fast::hash(&block.content).to_be_bytes()[..] {
+        Ok(())
+    } else {
+        Err(core_client::Error::new(
+            core_client::ErrorKind::Format,
+            "block content checksum mismatch",
+        ))
+    }
+}
+
+// Takes the continuation token out of the block message, if the peer
+// has sent one with the block.
+fn take_block_continuation_token(block: &mut gen::node::Block) -> Option<Bytes> {
+    if block.continuation_token.is_empty() {
+        None
+    } else {
+        Some(mem::replace(&mut block.continuation_token, Vec::new()).into())
+    }
+}
+
+// A well-behaved peer never sends an empty serialized block or header,
+// so this is reported as a protocol violation rather than left to fail
+// somewhere in the deserialization code.
+fn empty_content(what: &str) -> core_client::Error {
+    core_client::Error::new(
+        core_client::ErrorKind::Format,
+        format!("empty {} content", what),
+    )
+}
+
+fn parse_str<T>(s: &str) -> Result<T, core_client::Error>
+where
+    T: FromStr,
+    T::Err: error::Error + Send + Sync + 'static,
+{
+    T::from_str(s).map_err(|e| core_client::Error::new(core_client::ErrorKind::Format, e))
+}
+
+// Parses a block date received from the peer.
+//
+// An empty string denotes the date of the genesis block. Otherwise,
+// the string must have the shape EPOCH[.SLOT], where the interpretation
+// of SLOT is left to the FromStr implementation of the date type.
+// Errors include the offending string.
+fn parse_block_date<D>(s: &str) -> Result<D, core_client::Error>
+where
+    D: BlockDate + FromStr,
+    D::Err: error::Error + Send + Sync + 'static,
+{
+    if s.is_empty() {
+        return Ok(D::from_epoch_slot_id(0, 0));
+    }
+    let mut parts = s.splitn(2, '.');
+    let epoch = parts.next().unwrap();
+    let valid_shape = !epoch.is_empty()
+        && epoch.bytes().all(|c| c.is_ascii_digit())
+        && parts.next().map_or(true, |slot| !slot.is_empty());
+    let res = if valid_shape {
+        D::from_str(s).map_err(|e| InvalidBlockDate {
+            value: s.to_owned(),
+            source: Some(Box::new(e)),
+        })
+    } else {
+        Err(InvalidBlockDate {
+            value: s.to_owned(),
+            source: None,
+        })
+    };
+    res.map_err(|e| core_client::Error::new(core_client::ErrorKind::Format, e))
+}
+
+#[derive(Debug)]
+struct InvalidBlockDate {
+    value: String,
+    source: Option<Box<dyn error::Error + Send + Sync>>,
+}
+
+impl fmt::Display for InvalidBlockDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid block date {:?}", self.value)?;
+        match self.source {
+            Some(ref e) => write!(f, ": {}", e),
+            None => write!(f, ", expected EPOCH[.SLOT]"),
+        }
+    }
+}
+
+// Error converting an item of a response stream, with the position
+// of the item in the stream.
+#[derive(Debug)]
+struct InvalidStreamItem {
+    index: u64,
+    method: Option<&'static str>,
+    source: core_client::Error,
+}
+
+impl fmt::Display for InvalidStreamItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to deserialize item #{} from ", self.index)?;
+        match self.method {
+            Some(method) => write!(f, "the {} stream", method)?,
+            None => write!(f, "the stream")?,
+        }
+        // The source is a client error of the same kind, so skip its
+        // generic description.
+        match error::Error::source(&self.source) {
+            Some(e) => write!(f, ": {}", e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl error::Error for InvalidStreamItem {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl error::Error for InvalidBlockDate {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.source {
+            Some(ref e) => Some(e.as_ref()),
+            None => None,
+        }
+    }
+}
+
+fn serialize_to_bytes<T>(value: &T) -> Result<Vec<u8>, core_client::Error>
+where
+    T: Serialize,
+    T::Error: Send + Sync + 'static,
+{
+    value
+        .serialize_as_vec()
+        .map_err(|e| core_client::Error::new(core_client::ErrorKind::Format, e))
+}
+
+fn serialize_to_vec<T>(values: &[T]) -> Result<Vec<Vec<u8>>, core_client::Error>
+where
+    T: Serialize,
+    T::Error: Send + Sync + 'static,
+{
+    values.iter().map(serialize_to_bytes).collect()
+}
+
+// Implements ConvertResponse for a message that carries a serialized
+// value in a bytes field, deserializing the field into any type that
+// implements the given trait:
+//
+//     impl_convert_bytes_field!(gen::node::Transaction, content => T: Transaction);
+//
+// The conversions of blocks and headers are written out, because they
+// also reject empty content.
+macro_rules! impl_convert_bytes_field {
+    ($message:ty, $field:ident => $t:ident: $bound:path) => {
+        impl<$t> ConvertResponse<$t> for $message
+        where
+            $t: $bound + Deserialize,
+            <$t as Deserialize>::Error: Send + Sync + 'static,
+        {
+            fn convert_response(self) -> Result<$t, core_client::Error> {
+                deserialize_bytes(&self.$field)
+            }
+        }
+    };
+}
+
+impl<I, D> ConvertResponse<(I, D)> for gen::node::TipResponse
+where
+    I: BlockId + Deserialize,
+    D: BlockDate + FromStr,
+    <I as Deserialize>::Error: Send + Sync + 'static,
+    <D as FromStr>::Err: error::Error + Send + Sync + 'static,
+{
+    fn convert_response(self) -> Result<(I, D), core_client::Error> {
+        let id = deserialize_bytes(&self.id)?;
+        let blockdate = parse_block_date(&self.blockdate)?;
+        Ok((id, blockdate))
+    }
+}
+
+impl<I> ConvertResponse<PeerInfo<I>> for gen::node::PingResponse
+where
+    I: BlockId + Deserialize,
+    <I as Deserialize>::Error: Send + Sync + 'static,
+{
+    fn convert_response(self) -> Result<PeerInfo<I>, core_client::Error> {
+        let protocol_version = parse_str(&self.protocol_version)?;
+        let genesis_hash = deserialize_bytes(&self.genesis_hash)?;
+        Ok(PeerInfo {
+            node_id: self.node_id,
+            protocol_version,
+            genesis_hash,
+        })
+    }
+}
+
+impl ConvertResponse<Peer> for gen::node::Gossip {
+    fn convert_response(self) -> Result<Peer, core_client::Error> {
+        if self.node_id.is_empty() {
+            return Err(core_client::Error::new(
+                core_client::ErrorKind::Format,
+                "empty node id in gossip entry",
+            ));
+        }
+        let address = parse_str(&self.address)?;
+        Ok(Peer {
+            node_id: self.node_id,
+            address,
+        })
+    }
+}
+
+// Gossip relays what the peer has heard from other nodes, so
+// a malformed entry is skipped with a warning rather than failing
+// the whole stream.
+impl ConvertResponse<Option<Peer>> for gen::node::Gossip {
+    fn convert_response(self) -> Result<Option<Peer>, core_client::Error> {
+        match ConvertResponse::<Peer>::convert_response(self) {
+            Ok(peer) => Ok(Some(peer)),
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("skipping malformed gossip entry: {}", _e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+// A response to a ping is received only to time it, in `Client::rtt`.
+impl ConvertResponse<()> for gen::node::PingResponse {
+    fn convert_response(self) -> Result<(), core_client::Error> {
+        Ok(())
+    }
+}
+
+impl<T> ConvertResponse<T> for gen::node::Block
+where
+    T: Block,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+{
+    fn convert_response(self) -> Result<T, core_client::Error> {
+        if self.content.is_empty() {
+            return Err(empty_content("block"));
+        }
+        let block = deserialize_bytes(&self.content)?;
+        Ok(block)
+    }
+
+    fn verify_checksum(&self) -> Result<(), core_client::Error> {
+        verify_block_checksum(self)
+    }
+
+    fn take_item_id(&mut self) -> Option<Vec<u8>> {
+        if self.id.is_empty() {
+            None
+        } else {
+            Some(mem::replace(&mut self.id, Vec::new()))
+        }
+    }
+
+    fn take_continuation_token(&mut self) -> Option<Bytes> {
+        take_block_continuation_token(self)
+    }
+}
+
+impl<T> ConvertResponse<T> for gen::node::Header
+where
+    T: Header,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+{
+    fn convert_response(self) -> Result<T, core_client::Error> {
+        if self.content.is_empty() {
+            return Err(empty_content("header"));
+        }
+        let block = deserialize_bytes(&self.content)?;
+        Ok(block)
+    }
+}
+
+impl_convert_bytes_field!(gen::node::BlockIdResponse, id => I: BlockId);
+impl_convert_bytes_field!(gen::node::Transaction, content => T: Transaction);
+
+impl ConvertResponse<Vec<bool>> for gen::node::HasBlocksResponse {
+    fn convert_response(self) -> Result<Vec<bool>, core_client::Error> {
+        Ok(self.present)
+    }
+}
+
+impl ConvertResponse<u64> for gen::node::ChainLengthResponse {
+    fn convert_response(self) -> Result<u64, core_client::Error> {
+        Ok(self.length)
+    }
+}
+
+impl ConvertResponse<u64> for gen::node::UploadTransactionsResponse {
+    fn convert_response(self) -> Result<u64, core_client::Error> {
+        Ok(self.accepted)
+    }
+}
+
+impl<I> ConvertResponse<TransactionStatusUpdate<I>> for gen::node::TransactionStatusUpdate
+where
+    I: Deserialize,
+    <I as Deserialize>::Error: Send + Sync + 'static,
+{
+    fn convert_response(self) -> Result<TransactionStatusUpdate<I>, core_client::Error> {
+        let id = deserialize_bytes(&self.id)?;
+        let state = TransactionState::from_i32(self.state).ok_or_else(|| {
+            core_client::Error::new(
+                core_client::ErrorKind::Format,
+                format!("invalid transaction state code {}", self.state),
+            )
+        })?;
+        Ok(TransactionStatusUpdate { id, state })
+    }
+}
+
+impl ConvertResponse<MempoolStats> for gen::node::MempoolStatsResponse {
+    fn convert_response(self) -> Result<MempoolStats, core_client::Error> {
+        Ok(MempoolStats {
+            count: self.count,
+            bytes: self.bytes,
+        })
+    }
+}
+
+impl ConvertResponse<u64> for gen::node::UploadBlocksResponse {
+    fn convert_response(self) -> Result<u64, core_client::Error> {
+        Ok(self.accepted)
+    }
+}
+
+impl ConvertResponse<()> for gen::node::PushHeadersResponse {
+    fn convert_response(self) -> Result<(), core_client::Error> {
+        Ok(())
+    }
+}
+
+impl ConvertResponse<Vec<TransactionAcceptance>> for gen::node::SubmitTransactionsResponse {
+    fn convert_response(self) -> Result<Vec<TransactionAcceptance>, core_client::Error> {
+        self.results
+            .into_iter()
+            .map(|code| {
+                TransactionAcceptance::from_i32(code).ok_or_else(|| {
+                    core_client::Error::new(
+                        core_client::ErrorKind::Format,
+                        format!("invalid transaction acceptance code {}", code),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl<T, S, E> BlockService<T> for Client<S, E>
+where
+    T: Block,
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+    NodeClient<S, E>: Clone + Send + 'static,
+    T::Date: FromStr,
+    <T as Serialize>::Error: Send + Sync + 'static,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+    <T::Date as FromStr>::Err: error::Error + Send + Sync + 'static,
+{
+    type TipFuture = ResponseFuture<(T::Id, T::Date), gen::node::TipResponse>;
+
+    type ChainLengthFuture = ResponseFuture<u64, gen::node::ChainLengthResponse>;
+
+    type PullBlocksToTipStream = ResponseStream<T, gen::node::Block>;
+    type PullBlocksToTipFuture = ResponseStreamFuture<T, gen::node::Block>;
+
+    type PullBlocksStream = ResponseStream<T, gen::node::Block>;
+    type PullBlocksFuture = ResponseStreamFuture<T, gen::node::Block>;
+
+    type GetBlocksStream = ResponseStream<T, gen::node::Block>;
+    type GetBlocksFuture = ResponseStreamFuture<T, gen::node::Block>;
+
+    type GetBlockFuture = ResponseFuture<T, gen::node::Block>;
+
+    type BlockIdAtHeightFuture = ResponseFuture<T::Id, gen::node::BlockIdResponse>;
+
+    type GetBlocksPipelinedStream = ResponseStream<T, gen::node::Block>;
+    type GetBlocksPipelinedFuture = ResponseStreamFuture<T, gen::node::Block>;
+
+    type HasBlocksFuture = ResponseFuture<Vec<bool>, gen::node::HasBlocksResponse>;
+
+    type BlockSubscriptionStream = ResponseStream<T, gen::node::Block>;
+    type BlockSubscriptionFuture = ResponseStreamFuture<T, gen::node::Block>;
+
+    fn tip(&mut self) -> Self::TipFuture {
+        let req = gen::node::TipRequest {};
+        self.idempotent_call("Tip", req, |node, req| node.tip(req))
+    }
+
+    fn chain_length(&mut self) -> Self::ChainLengthFuture {
+        let req = gen::node::ChainLengthRequest {};
+        self.idempotent_call("ChainLength", req, |node, req| node.chain_length(req))
+    }
+
+    fn get_blocks(&mut self, ids: &[T::Id]) -> Self::GetBlocksFuture {
+        let ids = match serialize_to_vec(ids) {
+            Ok(ids) => ids,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::GetBlocksRequest { ids };
+        self.streaming_call("GetBlocks", req, |node, req| node.get_blocks(req))
+    }
+
+    fn get_block(&mut self, id: T::Id) -> Self::GetBlockFuture {
+        let id = match serialize_to_bytes(&id) {
+            Ok(id) => id,
+            Err(e) => return ResponseFuture::error(e),
+        };
+        let expected = id.clone();
+        let req = gen::node::GetBlockRequest { id };
+        self.idempotent_call("GetBlock", req, |node, req| node.get_block(req))
+            .with_check(Box::new(move |block: &T| {
+                if serialize_to_bytes(&block.id())? == expected {
+                    Ok(())
+                } else {
+                    let msg = format!(
+                        "received block {:?} instead of the requested one",
+                        block.id()
+                    );
+                    Err(core_client::Error::new(core_client::ErrorKind::Format, msg))
+                }
+            }))
+    }
+
+    fn get_blocks_pipelined<St>(&mut self, ids: St) -> Self::GetBlocksPipelinedFuture
+    where
+        St: Stream<Item = T::Id, Error = core_client::Error> + Send + 'static,
+    {
+        let requests = RequestStream::new(ids, |id| gen::node::GetBlockRequest { id });
+        self.streaming_call("GetBlocksPipelined", requests, |node, req| {
+            node.get_blocks_pipelined(req)
+        })
+    }
+
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture {
+        let req = gen::node::BlockRangeRequest { from_height, count };
+        self.streaming_call("GetBlockRange", req, |node, req| node.get_block_range(req))
+    }
+
+    fn block_id_at_height(&mut self, height: u64) -> Self::BlockIdAtHeightFuture {
+        let req = gen::node::BlockIdAtHeightRequest { height };
+        self.idempotent_call("BlockIdAtHeight", req, |node, req| {
+            node.block_id_at_height(req)
+        })
+    }
+
+    fn has_blocks(&mut self, ids: &[T::Id]) -> Self::HasBlocksFuture {
+        let ids = match serialize_to_vec(ids) {
+            Ok(ids) => ids,
+            Err(e) => return ResponseFuture::error(e),
+        };
+        let expected = ids.len();
+        let req = gen::node::GetBlocksRequest { ids };
+        self.idempotent_call("HasBlocks", req, |node, req| node.has_blocks(req))
+            .with_check(Box::new(move |present: &Vec<bool>| {
+                if present.len() == expected {
+                    Ok(())
+                } else {
+                    let msg = format!(
+                        "expected presence flags for {} blocks, received {}",
+                        expected,
+                        present.len()
+                    );
+                    Err(core_client::Error::new(core_client::ErrorKind::Format, msg))
+                }
+            }))
+    }
+
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture {
+        let req = gen::node::BlockSubscriptionRequest {};
+        self.streaming_call("BlockSubscription", req, |node, req| {
+            node.block_subscription(req)
+        })
+    }
+
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture {
+        let from = match serialize_to_vec(from) {
+            Ok(from) => from,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::PullBlocksToTipRequest { from, known: None };
+        self.streaming_call("PullBlocksToTip", req, |node, req| {
+            node.pull_blocks_to_tip(req)
+        })
+    }
+
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> Self::PullBlocksFuture {
+        let from = match serialize_to_vec(from) {
+            Ok(from) => from,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let to = match serialize_to_bytes(to) {
+            Ok(to) => to,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::PullBlocksRequest { from, to };
+        self.streaming_call("PullBlocks", req, |node, req| node.pull_blocks(req))
+    }
+
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> Self::PullBlocksFuture {
+        let to = match serialize_to_vec(to) {
+            Ok(to) => to,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::PullBlocksFromTipRequest { to };
+        self.streaming_call("PullBlocksFromTip", req, |node, req| {
+            node.pull_blocks_from_tip(req)
+        })
+    }
+
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Self::PullBlocksFuture {
+        let from = match serialize_to_vec(from) {
+            Ok(from) => from,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let known = gen::node::BloomFilter {
+            bits: known.bits().to_vec(),
+            hash_count: known.hash_count(),
+        };
+        let req = gen::node::PullBlocksToTipRequest {
+            from,
+            known: Some(known),
+        };
+        self.streaming_call("PullBlocksToTip", req, |node, req| {
+            node.pull_blocks_to_tip(req)
+        })
+    }
+
+    type UploadBlocksFuture = ClientStreamingFuture<u64, gen::node::UploadBlocksResponse>;
+
+    fn upload_blocks<St>(&mut self, blocks: St) -> Self::UploadBlocksFuture
+    where
+        St: Stream<Item = T, Error = core_client::Error> + Send + 'static,
+    {
+        let stream = RequestStream::new(blocks, |content| gen::node::Block {
+            content,
+            id: Vec::new(),
+            serve_info: None,
+            checksum: Vec::new(),
+            continuation_token: Vec::new(),
+        });
+        let context = self.request_context("UploadBlocks");
+        let req = context.hint_request(self.new_request(stream));
+        let future = self.authorized(req, |node, req| node.upload_blocks(req));
+        ClientStreamingFuture::new(future).with_context(context)
+    }
+}
+
+impl<T, S, E> HeaderService<T> for Client<S, E>
+where
+    T: Block + HasHeader,
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+    NodeClient<S, E>: Clone + Send + 'static,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Header as Serialize>::Error: Send + Sync + 'static,
+    <T::Header as Deserialize>::Error: Send + Sync + 'static,
+{
+    type GetHeadersStream = ResponseStream<T::Header, gen::node::Header>;
+    type GetHeadersFuture = ResponseStreamFuture<T::Header, gen::node::Header>;
+
+    fn get_headers(&mut self, ids: &[T::Id]) -> Self::GetHeadersFuture {
+        let ids = match serialize_to_vec(ids) {
+            Ok(ids) => ids,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::GetBlocksRequest { ids };
+        self.streaming_call("GetHeaders", req, |node, req| node.get_headers(req))
+    }
+
+    type TipHeaderFuture = ResponseFuture<T::Header, gen::node::Header>;
+
+    fn tip_header(&mut self) -> Self::TipHeaderFuture {
+        let req = gen::node::TipHeaderRequest {};
+        self.idempotent_call("TipHeader", req, |node, req| node.tip_header(req))
+    }
+
+    type TipHeadersStream = ResponseStream<T::Header, gen::node::Header>;
+    type TipHeadersFuture = ResponseStreamFuture<T::Header, gen::node::Header>;
+
+    fn tip_headers(&mut self, count: u32) -> Self::TipHeadersFuture {
+        let req = gen::node::TipHeadersRequest { count };
+        self.streaming_call("TipHeaders", req, |node, req| node.tip_headers(req))
+    }
+
+    type PullHeadersStream = ResponseStream<T::Header, gen::node::Header>;
+    type PullHeadersFuture = ResponseStreamFuture<T::Header, gen::node::Header>;
+
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> Self::PullHeadersFuture {
+        let from = match serialize_to_vec(from) {
+            Ok(from) => from,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::PullBlocksToTipRequest { from, known: None };
+        self.streaming_call("PullHeadersToTip", req, |node, req| {
+            node.pull_headers_to_tip(req)
+        })
+    }
+
+    type PushHeadersFuture = ClientStreamingFuture<(), gen::node::PushHeadersResponse>;
+
+    fn push_headers(&mut self, headers: &[T::Header]) -> Self::PushHeadersFuture {
+        let headers: Vec<_> = match serialize_to_vec(headers) {
+            Ok(headers) => headers,
+            Err(e) => return ClientStreamingFuture::error(e),
+        };
+        let headers: Vec<_> = headers
+            .into_iter()
+            .map(|content| gen::node::Header { content })
+            .collect();
+        let stream = futures::stream::iter_ok::<_, tower_grpc::Error>(headers);
+        let context = self.request_context("PushHeaders");
+        let req = context.hint_request(self.new_request(stream));
+        let future = self.authorized(req, |node, req| node.push_headers(req));
+        ClientStreamingFuture::new(future).with_context(context)
+    }
+}
+
+impl<T, S, E> PeerService<T> for Client<S, E>
+where
+    T: Block,
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+    NodeClient<S, E>: Clone + Send + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+{
+    type PingFuture = ResponseFuture<PeerInfo<T::Id>, gen::node::PingResponse>;
+
+    fn ping(&mut self) -> Self::PingFuture {
+        let req = gen::node::PingRequest {};
+        self.idempotent_call("Ping", req, |node, req| node.ping(req))
+    }
+}
+
+/// The stream of gossip entries returned by the `GossipService`
+/// implementation of `Client`, with malformed entries skipped.
+pub type GossipStream = futures::stream::FilterMap<
+    ResponseStream<Option<Peer>, gen::node::Gossip>,
+    fn(Option<Peer>) -> Option<Peer>,
+>;
+
+fn skip_malformed_gossip(stream: ResponseStream<Option<Peer>, gen::node::Gossip>) -> GossipStream {
+    stream.filter_map(identity as fn(_) -> _)
+}
+
+impl<S, E> GossipService for Client<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+    NodeClient<S, E>: Clone + Send + 'static,
+{
+    type PeersStream = GossipStream;
+    type PeersFuture = futures::future::Map<
+        ResponseStreamFuture<Option<Peer>, gen::node::Gossip>,
+        fn(ResponseStream<Option<Peer>, gen::node::Gossip>) -> GossipStream,
+    >;
+
+    fn peers(&mut self) -> Self::PeersFuture {
+        let req = gen::node::PeerGossipRequest {};
+        self.streaming_call("PeerGossip", req, |node, req| node.peer_gossip(req))
+            .map(skip_malformed_gossip as fn(_) -> _)
+    }
+}
+
+impl<T, S, E> TransactionService<T> for Client<S, E>
+where
+    T: Transaction,
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+    NodeClient<S, E>: Clone + Send + 'static,
+    T::Id: Serialize + Deserialize,
+    <T as Serialize>::Error: Send + Sync + 'static,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+{
+    type SubmitTransactionsFuture =
+        ResponseFuture<Vec<TransactionAcceptance>, gen::node::SubmitTransactionsResponse>;
+
+    fn submit_transactions(&mut self, txs: &[T]) -> Self::SubmitTransactionsFuture {
+        let transactions = match serialize_to_vec(txs) {
+            Ok(transactions) => transactions,
+            Err(e) => return ResponseFuture::error(e),
+        };
+        let req = gen::node::SubmitTransactionsRequest { transactions };
+        let context = self.request_context("SubmitTransactions");
+        let req = context.hint_request(self.new_request(req));
+        let future = self.authorized(req, |node, req| node.submit_transactions(req));
+        ResponseFuture::new(future, self.deadline()).with_context(context)
+    }
+
+    type GetTransactionsStream = ResponseStream<T, gen::node::Transaction>;
+    type GetTransactionsFuture = ResponseStreamFuture<T, gen::node::Transaction>;
+
+    fn get_transactions(&mut self, ids: &[T::Id]) -> Self::GetTransactionsFuture {
+        let ids = match serialize_to_vec(ids) {
+            Ok(ids) => ids,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::GetTransactionsRequest { ids };
+        self.streaming_call("GetTransactions", req, |node, req| {
+            node.get_transactions(req)
+        })
+    }
+
+    type TransactionStatusStream =
+        ResponseStream<TransactionStatusUpdate<T::Id>, gen::node::TransactionStatusUpdate>;
+    type TransactionStatusFuture =
+        ResponseStreamFuture<TransactionStatusUpdate<T::Id>, gen::node::TransactionStatusUpdate>;
+
+    fn subscribe_transaction_status(&mut self, ids: &[T::Id]) -> Self::TransactionStatusFuture {
+        let ids = match serialize_to_vec(ids) {
+            Ok(ids) => ids,
+            Err(e) => return ResponseStreamFuture::error(e),
+        };
+        let req = gen::node::TransactionStatusSubscriptionRequest { ids };
+        self.streaming_call("TransactionStatusSubscription", req, |node, req| {
+            node.transaction_status_subscription(req)
+        })
+    }
+
+    type MempoolSizeFuture = ResponseFuture<MempoolStats, gen::node::MempoolStatsResponse>;
+
+    fn mempool_size(&mut self) -> Self::MempoolSizeFuture {
+        let req = gen::node::MempoolStatsRequest {};
+        self.idempotent_call("MempoolStats", req, |node, req| node.mempool_stats(req))
+    }
+}
+
+/// The error type for gRPC client operations.
+#[derive(Debug)]
+pub enum Error {
+    Connect(ConnectError<io::Error>),
+    InvalidOrigin(http::Uri),
+    /// The connection has not been established within the timeout
+    /// set with `ClientBuilder::connect_timeout`.
+    Timeout,
+    Canceled,
+    /// The peer could not be pinged to verify its identity.
+    Ping(core_client::Error),
+    /// The peer serves a blockchain with a genesis block other than
+    /// the expected one. The block identifiers are given in their
+    /// debug representation.
+    NetworkMismatch {
+        expected: String,
+        got: String,
+    },
+    /// The host name of the peer could not be resolved.
+    Resolve(io::Error),
+    /// None of the addresses resolved for the host name of the peer
+    /// could be connected to. The error of each attempt is given
+    /// with the address, in the order the attempts failed.
+    AllAddressesFailed(Vec<(SocketAddr, Error)>),
+    /// The tunnel to the peer could not be established through
+    /// the HTTP proxy, either because the proxy could not be connected
+    /// to or because it has not accepted the `CONNECT` request.
+    Proxy(io::Error),
+    /// The peer has responded during connection setup with data that
+    /// does not follow the expected protocol, e.g. an HTTP proxy
+    /// has sent a reply that is not an HTTP response.
+    Protocol(String),
 }
 
 impl From<ConnectError<io::Error>> for Error {
@@ -369,10 +2990,36 @@ impl From<ConnectError<io::Error>> for Error {
     }
 }
 
+impl From<futures::Canceled> for Error {
+    fn from(_: futures::Canceled) -> Self {
+        Error::Canceled
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Connect(e) => write!(f, "connection error: {}", e),
+            Error::InvalidOrigin(uri) => write!(f, "invalid origin URI: {}", uri),
+            Error::Timeout => write!(f, "connection attempt timed out"),
+            Error::Canceled => write!(f, "operation canceled"),
+            Error::Ping(e) => write!(f, "failed to ping the peer: {}", e),
+            Error::NetworkMismatch { expected, got } => write!(
+                f,
+                "the peer is on a different network: expected genesis block {}, got {}",
+                expected, got
+            ),
+            Error::Resolve(e) => write!(f, "failed to resolve the host name: {}", e),
+            Error::AllAddressesFailed(errors) => {
+                write!(f, "failed to connect to any address of the host")?;
+                for (i, (addr, e)) in errors.iter().enumerate() {
+                    let sep = if i == 0 { ": " } else { "; " };
+                    write!(f, "{}{}: {}", sep, addr, e)?;
+                }
+                Ok(())
+            }
+            Error::Proxy(e) => write!(f, "failed to connect through the proxy: {}", e),
+            Error::Protocol(msg) => write!(f, "protocol error: {}", msg),
         }
     }
 }
@@ -381,6 +3028,564 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Error::Connect(e) => Some(e),
+            Error::InvalidOrigin(_) => None,
+            Error::Timeout => None,
+            Error::Canceled => None,
+            Error::Ping(e) => Some(e),
+            Error::NetworkMismatch { .. } => None,
+            Error::Resolve(e) => Some(e),
+            Error::AllAddressesFailed(_) => None,
+            Error::Proxy(e) => Some(e),
+            Error::Protocol(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        grpc_timeout_value, service_path::parse_grpc_duration, Client, ClientBuilder,
+        ClientStreamingFuture, ConvertResponse, ResponseFuture, GRPC_TIMEOUT_HEADER,
+    };
+    use crate::gen;
+    use crate::peer::TcpPeer;
+    use crate::service::ConvertResponse as ConvertServerResponse;
+    use crate::test_types::{serve, StubNode, TestBlock, TestDate, TestHeader, TestId};
+
+    use chain_core::property::{Block, HasHeader};
+
+    use network_core::{
+        bloom::BloomFilter,
+        client::{
+            self as core_client,
+            block::{BlockService, HeaderService},
+        },
+        testing::MockClient,
+    };
+
+    use bytes::{BufMut, BytesMut};
+    use futures::{
+        future::{self, Either},
+        prelude::*,
+        sync::mpsc,
+        try_ready,
+    };
+    use prost::Message;
+    use tokio::executor::DefaultExecutor;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::runtime::current_thread::{self, Runtime};
+    use tokio::timer::Delay;
+
+    use std::{
+        cmp,
+        fmt::Debug,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    // Connects to a stub node serving the blocks at the given heights.
+    fn connect(rt: &mut Runtime, heights: Vec<u64>) -> Client<TcpStream, DefaultExecutor> {
+        let peer = serve(rt, StubNode::new(heights));
+        rt.block_on(ClientBuilder::new().build_connect(peer, DefaultExecutor::current()))
+            .unwrap()
+    }
+
+    fn mock_client(heights: &[u64]) -> MockClient<TestBlock> {
+        MockClient::new(heights.iter().map(|&height| TestBlock(height)).collect())
+    }
+
+    // Pulls the blocks to the tip from a mock client and from a client
+    // connected to a stub node, both serving the blocks at the given
+    // heights, returning the result of each for the same checks.
+    fn pull_blocks_to_tip(
+        heights: &[u64],
+        from: &[TestId],
+    ) -> Vec<Result<Vec<TestBlock>, core_client::Error>> {
+        let mut mock = mock_client(heights);
+        let mock_res = BlockService::pull_blocks_to_tip(&mut mock, from)
+            .and_then(Stream::collect)
+            .wait();
+        let mut rt = Runtime::new().unwrap();
+        let mut client = connect(&mut rt, heights.to_vec());
+        let pull = BlockService::<TestBlock>::pull_blocks_to_tip(&mut client, from);
+        let res = rt.block_on(pull.and_then(Stream::collect));
+        vec![mock_res, res]
+    }
+
+    // Polls the future to completion and then a few times more,
+    // checking that every poll past completion fails with
+    // an error of kind `Internal`. Returns the result of the completion.
+    fn poll_past_completion<F>(
+        rt: &mut Runtime,
+        mut future: F,
+    ) -> Result<F::Item, core_client::Error>
+    where
+        F: Future<Error = core_client::Error>,
+        F::Item: Debug,
+    {
+        let res = rt.block_on(future::poll_fn(|| future.poll()));
+        rt.block_on(future::lazy(|| {
+            for _ in 0..3 {
+                match future.poll() {
+                    Err(ref e) => match e.kind() {
+                        core_client::ErrorKind::Internal => {}
+                        kind => panic!("unexpected error kind {:?}", kind),
+                    },
+                    res => panic!("unexpected poll result {:?}", res),
+                }
+            }
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+        res
+    }
+
+    #[test]
+    fn polling_past_a_failure_fails_without_panicking() {
+        let mut rt = Runtime::new().unwrap();
+        let err = core_client::Error::new(core_client::ErrorKind::NotFound, "not found");
+        let future = ResponseFuture::<u64, gen::node::ChainLengthResponse>::error(err);
+        match poll_past_completion(&mut rt, future) {
+            Err(ref e) => match e.kind() {
+                core_client::ErrorKind::NotFound => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            res => panic!("unexpected result {:?}", res),
+        }
+        let err = core_client::Error::new(core_client::ErrorKind::Format, "bad block");
+        let future = ClientStreamingFuture::<u64, gen::node::UploadBlocksResponse>::error(err);
+        match poll_past_completion(&mut rt, future) {
+            Err(ref e) => match e.kind() {
+                core_client::ErrorKind::Format => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn polling_past_a_response_fails_without_panicking() {
+        let mut rt = Runtime::new().unwrap();
+        let mut client = connect(&mut rt, vec![0, 1, 2]);
+        let future = BlockService::<TestBlock>::chain_length(&mut client);
+        assert_eq!(poll_past_completion(&mut rt, future).unwrap(), 2);
+    }
+
+    fn convert_tip(blockdate: &str) -> (TestId, TestDate) {
+        let response = gen::node::TipResponse {
+            id: 7u64.to_be_bytes().to_vec(),
+            blockdate: blockdate.into(),
+        };
+        ConvertResponse::<(TestId, TestDate)>::convert_response(response).unwrap()
+    }
+
+    #[test]
+    fn genesis_tip_date_is_accepted_empty_or_formatted() {
+        let genesis = TestDate { epoch: 0, slot: 0 };
+        assert_eq!(convert_tip(""), (TestId(7), genesis));
+        assert_eq!(convert_tip("0.0"), (TestId(7), genesis));
+        assert_eq!(
+            convert_tip("1.5"),
+            (TestId(7), TestDate { epoch: 1, slot: 5 })
+        );
+    }
+
+    #[test]
+    fn header_round_trips_through_the_message() {
+        let header = TestBlock(42).header();
+        let message =
+            ConvertServerResponse::<gen::node::Header>::convert_response(header.clone()).unwrap();
+        let received = ConvertResponse::<TestHeader>::convert_response(message).unwrap();
+        assert_eq!(received, header);
+
+        let empty = gen::node::Header {
+            content: Vec::new(),
+        };
+        match ConvertResponse::<TestHeader>::convert_response(empty) {
+            Err(ref e) => match e.kind() {
+                core_client::ErrorKind::Format => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            Ok(_) => panic!("an empty header should fail to convert"),
+        }
+    }
+
+    #[test]
+    fn block_subscription_yields_pushed_blocks_and_stays_open() {
+        let mut rt = Runtime::new().unwrap();
+        let mut client = connect(&mut rt, vec![1, 2, 3]);
+        let subscription = BlockService::<TestBlock>::block_subscription(&mut client);
+        let mut stream = rt.block_on(subscription).unwrap();
+        for height in 1..=3 {
+            let (block, rest) = rt
+                .block_on(stream.into_future())
+                .map_err(|(e, _)| e)
+                .unwrap();
+            assert_eq!(block, Some(TestBlock(height)));
+            stream = rest;
+        }
+        let timeout = Delay::new(Instant::now() + Duration::from_millis(100));
+        match rt.block_on(stream.into_future().select2(timeout)) {
+            Ok(Either::B(_)) => {}
+            Ok(Either::A(((item, _), _))) => panic!("unexpected subscription item {:?}", item),
+            Err(_) => panic!("the subscription has failed"),
+        }
+    }
+
+    #[test]
+    fn cloned_clients_make_simultaneous_tip_calls_over_one_connection() {
+        let mut rt = Runtime::new().unwrap();
+        let mut client = connect(&mut rt, vec![1, 2, 3]);
+        let mut clone = client.clone();
+        let first = BlockService::<TestBlock>::tip(&mut client);
+        let second = BlockService::<TestBlock>::tip(&mut clone);
+        let (first, second) = rt.block_on(first.join(second)).unwrap();
+        let tip = (TestId(3), TestDate { epoch: 0, slot: 3 });
+        assert_eq!(first, tip);
+        assert_eq!(second, tip);
+    }
+
+    #[test]
+    fn pull_blocks_to_an_unknown_block_is_not_found() {
+        let mut rt = Runtime::new().unwrap();
+        let mut client = connect(&mut rt, vec![1, 2, 3]);
+        let blocks = BlockService::<TestBlock>::pull_blocks(&mut client, &[TestId(1)], &TestId(3));
+        let blocks = rt.block_on(blocks.and_then(|s| s.collect())).unwrap();
+        assert_eq!(blocks, vec![TestBlock(2), TestBlock(3)]);
+        let pull = BlockService::<TestBlock>::pull_blocks(&mut client, &[TestId(1)], &TestId(7));
+        match rt.block_on(pull) {
+            Err(ref e) => match e.kind() {
+                core_client::ErrorKind::NotFound => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            Ok(_) => panic!("the request should have failed"),
+        }
+    }
+
+    #[test]
+    fn tip_of_genesis_only_chain_is_genesis() {
+        let genesis = (TestId(0), TestDate { epoch: 0, slot: 0 });
+        let tip = BlockService::tip(&mut mock_client(&[0])).wait().unwrap();
+        assert_eq!(tip, genesis);
+        let mut rt = Runtime::new().unwrap();
+        let mut client = connect(&mut rt, vec![0]);
+        let tip = rt.block_on(BlockService::<TestBlock>::tip(&mut client));
+        assert_eq!(tip.unwrap(), genesis);
+        for res in pull_blocks_to_tip(&[0], &[genesis.0]) {
+            assert_eq!(res.unwrap(), vec![]);
+        }
+    }
+
+    #[test]
+    fn pull_blocks_to_tip_starts_after_the_only_known_checkpoint() {
+        let from = [TestId(100), TestId(101), TestId(2)];
+        for res in pull_blocks_to_tip(&[0, 1, 2, 3, 4], &from) {
+            assert_eq!(res.unwrap(), vec![TestBlock(3), TestBlock(4)]);
+        }
+    }
+
+    #[test]
+    fn pull_blocks_to_tip_starts_after_the_latest_known_checkpoint() {
+        let from = [TestId(1), TestId(3), TestId(100)];
+        for res in pull_blocks_to_tip(&[0, 1, 2, 3, 4], &from) {
+            assert_eq!(res.unwrap(), vec![TestBlock(4)]);
+        }
+    }
+
+    #[test]
+    fn pull_blocks_to_tip_fails_if_no_checkpoint_is_known() {
+        let from = [TestId(100), TestId(101)];
+        for res in pull_blocks_to_tip(&[0, 1, 2, 3, 4], &from) {
+            match res {
+                Err(ref e) => match e.kind() {
+                    core_client::ErrorKind::NotFound => {}
+                    kind => panic!("unexpected error kind {:?}", kind),
+                },
+                Ok(blocks) => panic!("unexpected success: {:?}", blocks),
+            }
+        }
+    }
+
+    #[test]
+    fn mock_filtered_pull_skips_known_blocks() {
+        let mut mock = mock_client(&[0, 1, 2, 3, 4, 5]);
+        let mut known = BloomFilter::new(1024, 4);
+        known.insert(&TestId(2)).unwrap();
+        known.insert(&TestId(3)).unwrap();
+        let blocks: Vec<_> = mock
+            .pull_blocks_to_tip_filtered(&[TestId(0)], &known)
+            .and_then(Stream::collect)
+            .wait()
+            .unwrap();
+        assert!(!blocks.contains(&TestBlock(2)));
+        assert!(!blocks.contains(&TestBlock(3)));
+        for block in (1..6).map(TestBlock) {
+            if !blocks.contains(&block) {
+                assert!(known.contains(&block.id()).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn mock_pull_headers_to_tip_follows_the_checkpoints_of_blocks() {
+        let mut mock = mock_client(&[0, 1, 2, 3, 4]);
+        let from = [TestId(1), TestId(3), TestId(100)];
+        let headers: Vec<_> = HeaderService::pull_headers_to_tip(&mut mock, &from)
+            .and_then(Stream::collect)
+            .wait()
+            .unwrap();
+        assert_eq!(headers, vec![TestBlock(4).header()]);
+    }
+
+    #[test]
+    fn grpc_timeout_uses_the_most_precise_unit_that_fits() {
+        assert_eq!(grpc_timeout_value(Duration::from_nanos(0)), "0n");
+        assert_eq!(
+            grpc_timeout_value(Duration::from_nanos(99_999_999)),
+            "99999999n"
+        );
+        assert_eq!(grpc_timeout_value(Duration::from_millis(100)), "100000u");
+        assert_eq!(grpc_timeout_value(Duration::from_secs(100)), "100000m");
+        assert_eq!(grpc_timeout_value(Duration::from_secs(200_000)), "200000S");
+        assert_eq!(
+            grpc_timeout_value(Duration::from_secs(u64::max_value())),
+            "5124096H"
+        );
+    }
+
+    #[test]
+    fn grpc_timeout_is_rounded_up() {
+        assert_eq!(grpc_timeout_value(Duration::new(1, 1)), "1000001u");
+        // At the limit of 8 digits, rounding up may take
+        // the value over to a coarser unit.
+        assert_eq!(
+            grpc_timeout_value(Duration::from_micros(99_999_999)),
+            "99999999u"
+        );
+        assert_eq!(
+            grpc_timeout_value(Duration::from_nanos(99_999_999_001)),
+            "100000m"
+        );
+    }
+
+    // Serves a connection on a loopback TCP socket with an HTTP/2 server
+    // that sends the value of the grpc-timeout header of each request
+    // it receives, resetting the request without responding.
+    fn capture_timeouts(rt: &mut Runtime) -> (TcpPeer, mpsc::UnboundedReceiver<Option<String>>) {
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer = TcpPeer::new(listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded();
+        let accept = listener
+            .incoming()
+            .take(1)
+            .map_err(|e| panic!("accept error: {}", e))
+            .for_each(move |sock| {
+                let tx = tx.clone();
+                let conn = h2::server::handshake(sock)
+                    .and_then(move |conn| {
+                        conn.for_each(move |(req, _)| {
+                            let timeout = req
+                                .headers()
+                                .get(GRPC_TIMEOUT_HEADER)
+                                .map(|value| value.to_str().unwrap().to_owned());
+                            let _ = tx.unbounded_send(timeout);
+                            Ok(())
+                        })
+                    })
+                    .map_err(|_| ());
+                current_thread::spawn(conn);
+                Ok(())
+            });
+        rt.spawn(accept);
+        (peer, rx)
+    }
+
+    #[test]
+    fn request_timeout_is_sent_with_unary_and_streaming_requests() {
+        let mut rt = Runtime::new().unwrap();
+        let (peer, timeouts) = capture_timeouts(&mut rt);
+        let timeout = Duration::from_secs(5);
+        let connect = ClientBuilder::new()
+            .request_timeout(timeout)
+            .build_connect(peer, DefaultExecutor::current());
+        let mut client = rt.block_on(connect).unwrap();
+        let tip = BlockService::<TestBlock>::tip(&mut client);
+        rt.spawn(tip.then(|_| Ok(())));
+        let pull = BlockService::<TestBlock>::pull_blocks_to_tip(&mut client, &[TestId(1)]);
+        rt.spawn(pull.then(|_| Ok(())));
+        let received = rt.block_on(timeouts.take(2).collect()).unwrap();
+        for value in received {
+            let value = value.expect("the grpc-timeout header should be sent");
+            let sent = parse_grpc_duration(&value).unwrap();
+            assert!(sent <= timeout, "unexpected timeout {}", value);
+            assert!(
+                sent > timeout - Duration::from_millis(100),
+                "unexpected timeout {}",
+                value
+            );
+        }
+    }
+
+    // Serves a connection on a loopback TCP socket with an HTTP/2 server
+    // that responds to every request with a trailers-only response
+    // of status UNAVAILABLE and the given additional headers.
+    fn serve_unavailable(
+        rt: &mut Runtime,
+        headers: &'static [(&'static str, &'static str)],
+    ) -> TcpPeer {
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer = TcpPeer::new(listener.local_addr().unwrap());
+        let accept = listener
+            .incoming()
+            .take(1)
+            .map_err(|e| panic!("accept error: {}", e))
+            .for_each(move |sock| {
+                let conn = h2::server::handshake(sock)
+                    .and_then(move |conn| {
+                        conn.for_each(move |(_, mut respond)| {
+                            let mut res = http::Response::builder();
+                            res.header("content-type", "application/grpc")
+                                .header("grpc-status", "14")
+                                .header("grpc-message", "overloaded");
+                            for &(name, value) in headers {
+                                res.header(name, value);
+                            }
+                            respond.send_response(res.body(()).unwrap(), true)?;
+                            Ok(())
+                        })
+                    })
+                    .map_err(|_| ());
+                current_thread::spawn(conn);
+                Ok(())
+            });
+        rt.spawn(accept);
+        peer
+    }
+
+    fn tip_error(headers: &'static [(&'static str, &'static str)]) -> core_client::Error {
+        let mut rt = Runtime::new().unwrap();
+        let peer = serve_unavailable(&mut rt, headers);
+        let connect = ClientBuilder::new().build_connect(peer, DefaultExecutor::current());
+        let mut client = rt.block_on(connect).unwrap();
+        let err = rt
+            .block_on(BlockService::<TestBlock>::tip(&mut client))
+            .unwrap_err();
+        match err.kind() {
+            core_client::ErrorKind::Unavailable => {}
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+        err
+    }
+
+    #[test]
+    fn retry_hint_is_read_from_the_response_headers() {
+        let err = tip_error(&[("grpc-retry-delay", "1500m")]);
+        assert_eq!(err.retry_after(), Some(Duration::from_millis(1500)));
+        let err = tip_error(&[("retry-after", "2")]);
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(2)));
+        let err = tip_error(&[("retry-after", "2"), ("grpc-retry-delay", "3S")]);
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn error_without_retry_hint_has_none() {
+        assert_eq!(tip_error(&[]).retry_after(), None);
+        assert_eq!(tip_error(&[("retry-after", "soon")]).retry_after(), None);
+    }
+
+    // Serves a connection on a loopback TCP socket with an HTTP/2 server
+    // that responds to every request with a stream of the blocks, sending
+    // data as soon as the flow control window of the stream allows it.
+    // Returns the total size of the response data and the counter of
+    // the data sent so far.
+    fn serve_blocks_eagerly(
+        rt: &mut Runtime,
+        blocks: impl IntoIterator<Item = TestBlock>,
+    ) -> (TcpPeer, usize, Arc<AtomicUsize>) {
+        let mut data = BytesMut::new();
+        for block in blocks {
+            let message: gen::node::Block = ConvertServerResponse::convert_response(block).unwrap();
+            data.reserve(5 + message.encoded_len());
+            data.put_u8(0);
+            data.put_u32_be(message.encoded_len() as u32);
+            message.encode(&mut data).unwrap();
         }
+        let data = data.freeze();
+        let total = data.len();
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer = TcpPeer::new(listener.local_addr().unwrap());
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent_counter = sent.clone();
+        let accept = listener
+            .incoming()
+            .take(1)
+            .map_err(|e| panic!("accept error: {}", e))
+            .for_each(move |sock| {
+                let sent = sent_counter.clone();
+                let data = data.clone();
+                let conn = h2::server::handshake(sock)
+                    .and_then(move |conn| {
+                        conn.for_each(move |(_, mut respond)| {
+                            let res = http::Response::builder()
+                                .header("content-type", "application/grpc")
+                                .body(())
+                                .unwrap();
+                            let mut send = respond.send_response(res, false)?;
+                            let mut data = data.clone();
+                            let sent = sent.clone();
+                            let send_data = future::poll_fn(move || -> Poll<(), h2::Error> {
+                                while !data.is_empty() {
+                                    send.reserve_capacity(data.len());
+                                    let capacity = match try_ready!(send.poll_capacity()) {
+                                        Some(capacity) => capacity,
+                                        None => return Ok(Async::Ready(())),
+                                    };
+                                    let chunk = data.split_to(cmp::min(capacity, data.len()));
+                                    sent.fetch_add(chunk.len(), Ordering::SeqCst);
+                                    send.send_data(chunk, false)?;
+                                }
+                                let mut trailers = http::HeaderMap::new();
+                                trailers.insert("grpc-status", "0".parse().unwrap());
+                                send.send_trailers(trailers)?;
+                                Ok(Async::Ready(()))
+                            });
+                            current_thread::spawn(send_data.map_err(|_| ()));
+                            Ok(())
+                        })
+                    })
+                    .map_err(|_| ());
+                current_thread::spawn(conn);
+                Ok(())
+            });
+        rt.spawn(accept);
+        (peer, total, sent)
+    }
+
+    #[test]
+    fn paused_consumer_closes_the_stream_window() {
+        const WINDOW: u32 = 1024;
+        let mut rt = Runtime::new().unwrap();
+        let (peer, total, sent) = serve_blocks_eagerly(&mut rt, (1..=1000).map(TestBlock));
+        assert!(total > 4 * WINDOW as usize);
+        let connect = ClientBuilder::new()
+            .initial_stream_window_size(WINDOW)
+            .build_connect(peer, DefaultExecutor::current());
+        let mut client = rt.block_on(connect).unwrap();
+        let pull = BlockService::<TestBlock>::pull_blocks_to_tip(&mut client, &[TestId(0)]);
+        let stream = rt.block_on(pull).unwrap();
+        // While the stream is not polled, the peer can only send
+        // as much as the window of the stream allows.
+        rt.block_on(Delay::new(Instant::now() + Duration::from_millis(100)))
+            .unwrap();
+        let paused = sent.load(Ordering::SeqCst);
+        assert!(paused > 0);
+        assert!(paused <= WINDOW as usize, "{} bytes sent ahead", paused);
+        // Consuming the stream reopens the window.
+        let blocks = rt.block_on(stream.collect()).unwrap();
+        assert_eq!(blocks.len(), 1000);
+        assert_eq!(sent.load(Ordering::SeqCst), total);
     }
 }
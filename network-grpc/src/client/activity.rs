@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// A snapshot of the requests in progress on a client and its clones,
+/// as returned by `Client::active_requests`.
+///
+/// The counts are keyed by the gRPC method name, e.g. `"PullBlocksToTip"`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ActiveRequests {
+    unary: HashMap<&'static str, u64>,
+    streams: HashMap<&'static str, u64>,
+}
+
+impl ActiveRequests {
+    /// Returns the number of unary requests awaiting their responses.
+    pub fn unary(&self) -> u64 {
+        self.unary.values().sum()
+    }
+
+    /// Returns the number of requests with streamed responses in
+    /// progress, counting each from the moment it is made, including
+    /// the time it may wait for a stream slot, until its response
+    /// stream ends, fails, is canceled, or is dropped.
+    pub fn streams(&self) -> u64 {
+        self.streams.values().sum()
+    }
+
+    /// Returns the number of unary requests to the method awaiting
+    /// their responses.
+    pub fn unary_for(&self, method: &str) -> u64 {
+        self.unary.get(method).cloned().unwrap_or(0)
+    }
+
+    /// Returns the number of requests with streamed responses to
+    /// the method in progress.
+    pub fn streams_for(&self, method: &str) -> u64 {
+        self.streams.get(method).cloned().unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum RequestKind {
+    Unary,
+    Stream,
+}
+
+// Counts the requests in progress on a client and its clones.
+#[derive(Clone, Default)]
+pub(super) struct Activity {
+    shared: Arc<Mutex<ActiveRequests>>,
+}
+
+impl Activity {
+    pub fn start(&self, method: &'static str, kind: RequestKind) -> ActiveRequest {
+        let mut counts = self.shared.lock().unwrap();
+        *counts.of_kind(kind).entry(method).or_insert(0) += 1;
+        ActiveRequest {
+            shared: self.shared.clone(),
+            method,
+            kind,
+        }
+    }
+
+    pub fn snapshot(&self) -> ActiveRequests {
+        self.shared.lock().unwrap().clone()
+    }
+}
+
+impl ActiveRequests {
+    fn of_kind(&mut self, kind: RequestKind) -> &mut HashMap<&'static str, u64> {
+        match kind {
+            RequestKind::Unary => &mut self.unary,
+            RequestKind::Stream => &mut self.streams,
+        }
+    }
+}
+
+// The accounting of a request in progress. The request is counted
+// until this is dropped, whether the request has completed or
+// has been abandoned.
+pub(super) struct ActiveRequest {
+    shared: Arc<Mutex<ActiveRequests>>,
+    method: &'static str,
+    kind: RequestKind,
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        // Don't panic on a poisoned lock while dropping.
+        if let Ok(mut counts) = self.shared.lock() {
+            let counts = counts.of_kind(self.kind);
+            let remove = match counts.get_mut(self.method) {
+                Some(count) => {
+                    *count -= 1;
+                    *count == 0
+                }
+                None => false,
+            };
+            if remove {
+                counts.remove(self.method);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_request_is_no_longer_counted() {
+        let activity = Activity::default();
+        let first = activity.start("PullBlocksToTip", RequestKind::Stream);
+        let second = activity.start("PullBlocksToTip", RequestKind::Stream);
+        let unary = activity.start("Tip", RequestKind::Unary);
+        let snapshot = activity.snapshot();
+        assert_eq!(snapshot.streams(), 2);
+        assert_eq!(snapshot.streams_for("PullBlocksToTip"), 2);
+        assert_eq!(snapshot.unary(), 1);
+        assert_eq!(snapshot.unary_for("PullBlocksToTip"), 0);
+        drop(first);
+        drop(unary);
+        let snapshot = activity.snapshot();
+        assert_eq!(snapshot.streams(), 1);
+        assert_eq!(snapshot.unary(), 0);
+        drop(second);
+        assert_eq!(activity.snapshot(), ActiveRequests::default());
+    }
+}
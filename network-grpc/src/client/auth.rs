@@ -0,0 +1,135 @@
+use super::{
+    intercept::{self, Interceptor},
+    NodeClient,
+};
+
+use futures::prelude::*;
+use http::header::{HeaderValue, AUTHORIZATION};
+use tower_grpc::{Code, Request, Status};
+
+use std::{error, sync::Arc};
+
+/// The type of asynchronous futures returned by `AuthProvider::token`.
+pub type TokenFuture =
+    Box<dyn Future<Item = String, Error = Box<dyn error::Error + Send + Sync>> + Send>;
+
+/// Provider of bearer tokens to authenticate requests with.
+///
+/// An implementation can be installed with `ClientBuilder::auth_provider`.
+/// A token is obtained for every request and sent in the
+/// `authorization: Bearer <token>` header, so the provider is free to
+/// rotate tokens; any caching is up to the implementation.
+///
+/// The trait is implemented for closures returning a value convertible
+/// into a future of the token, so a token held in memory can be provided
+/// with a closure like `move || Ok::<_, io::Error>(token.clone())`.
+pub trait AuthProvider: Send + Sync {
+    /// Requests the current token.
+    ///
+    /// If the future fails, the request fails with an error of kind
+    /// `ErrorKind::Unauthenticated` without being sent to the peer.
+    fn token(&self) -> TokenFuture;
+}
+
+impl<F, R> AuthProvider for F
+where
+    F: Fn() -> R + Send + Sync,
+    R: IntoFuture<Item = String>,
+    R::Future: Send + 'static,
+    R::Error: Into<Box<dyn error::Error + Send + Sync>>,
+{
+    fn token(&self) -> TokenFuture {
+        Box::new(self().into_future().map_err(Into::into))
+    }
+}
+
+// A gRPC call that is made once the authentication token is obtained.
+pub enum Authorized<F> {
+    Ready(F),
+    // The call has been rejected by the interceptor.
+    Rejected(Option<Status>),
+    Pending {
+        token: TokenFuture,
+        call: Box<dyn FnMut(HeaderValue) -> F + Send>,
+    },
+}
+
+fn unauthenticated<T>(msg: String) -> tower_grpc::Error<T> {
+    tower_grpc::Error::Grpc(Status::with_code_and_message(Code::Unauthenticated, msg))
+}
+
+impl<F, T> Future for Authorized<F>
+where
+    F: Future<Error = tower_grpc::Error<T>>,
+{
+    type Item = F::Item;
+    type Error = tower_grpc::Error<T>;
+
+    fn poll(&mut self) -> Poll<F::Item, tower_grpc::Error<T>> {
+        let future = match self {
+            Authorized::Ready(future) => return future.poll(),
+            Authorized::Rejected(status) => {
+                let status = status.take().expect("rejected call polled after failing");
+                return Err(tower_grpc::Error::Grpc(status));
+            }
+            Authorized::Pending { token, call } => {
+                let token = match token.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(token)) => token,
+                    Err(e) => {
+                        let msg = format!("failed to obtain the authentication token: {}", e);
+                        return Err(unauthenticated(msg));
+                    }
+                };
+                let value = match HeaderValue::from_str(&format!("Bearer {}", token)) {
+                    Ok(value) => value,
+                    Err(_) => {
+                        let msg = "the authentication token is not a valid header value";
+                        return Err(unauthenticated(msg.into()));
+                    }
+                };
+                call(value)
+            }
+        };
+        *self = Authorized::Ready(future);
+        self.poll()
+    }
+}
+
+// Makes the call with the authentication token from the provider, if one
+// is set, or right away otherwise. The request is first passed to
+// the interceptor, if one is set.
+pub(super) fn authorize<S, E, M, F, C>(
+    node: &mut NodeClient<S, E>,
+    auth: Option<&Arc<dyn AuthProvider>>,
+    interceptor: Option<&Arc<dyn Interceptor>>,
+    req: Request<M>,
+    call: C,
+) -> Authorized<F>
+where
+    NodeClient<S, E>: Clone + Send + 'static,
+    M: Send + 'static,
+    C: FnOnce(&mut NodeClient<S, E>, Request<M>) -> F + Send + 'static,
+{
+    let req = match interceptor {
+        None => req,
+        Some(interceptor) => match intercept::apply(&**interceptor, req) {
+            Ok(req) => req,
+            Err(status) => return Authorized::Rejected(Some(status)),
+        },
+    };
+    let auth = match auth {
+        None => return Authorized::Ready(call(node, req)),
+        Some(auth) => auth,
+    };
+    let mut args = Some((node.clone(), req, call));
+    Authorized::Pending {
+        token: auth.token(),
+        call: Box::new(move |value| {
+            let (mut node, req, call) = args.take().expect("authorized call made twice");
+            let mut req = req.into_http();
+            req.headers_mut().insert(AUTHORIZATION, value);
+            call(&mut node, Request::from_http(req))
+        }),
+    }
+}
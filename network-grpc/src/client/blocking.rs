@@ -0,0 +1,278 @@
+use super::{Client, ClientBuilder, Error};
+use crate::peer::Origin;
+
+use chain_core::property::{Block, HasHeader};
+use network_core::{
+    bloom::BloomFilter,
+    client::{
+        self as core_client,
+        block::{BlockService, HeaderService},
+        peer::PeerService,
+    },
+    peer::PeerInfo,
+};
+
+use tokio::executor::DefaultExecutor;
+use tokio::io;
+use tokio::prelude::*;
+use tokio::runtime::current_thread::Runtime;
+use tower_h2::client::ConnectError;
+
+// The default maximum number of items collected from a response stream.
+const DEFAULT_MAX_STREAM_ITEMS: usize = 10_000;
+
+/// gRPC client that performs requests synchronously.
+///
+/// A `BlockingClient` owns a single-threaded runtime that drives
+/// the connection and each request to completion on the calling thread.
+/// This is convenient for command line tools and scripts that make
+/// a few requests to a single node; asynchronous applications should use
+/// `Client` instead. The methods of this type must not be called from
+/// within a task running on another runtime.
+///
+/// Streamed responses are collected into vectors of at most
+/// `max_stream_items` items. When that many items have been received,
+/// the rest of the stream is dropped, canceling the request; to retrieve
+/// a long chain, request it in batches starting from the last block
+/// received.
+///
+/// This type is only available with the `blocking` feature.
+pub struct BlockingClient<S> {
+    runtime: Runtime,
+    client: Client<S, DefaultExecutor>,
+    max_stream_items: usize,
+}
+
+impl<S> BlockingClient<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Connects to the peer with the default connection settings.
+    pub fn connect<P>(peer: P) -> Result<Self, Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+    {
+        Self::connect_with_builder(&ClientBuilder::new(), peer)
+    }
+
+    /// Connects to the peer with the settings of the builder.
+    pub fn connect_with_builder<P>(builder: &ClientBuilder, peer: P) -> Result<Self, Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+    {
+        let mut runtime = Runtime::new().map_err(|e| Error::Connect(ConnectError::Connect(e)))?;
+        let client = runtime.block_on(builder.build_connect(peer, DefaultExecutor::current()))?;
+        Ok(BlockingClient {
+            runtime,
+            client,
+            max_stream_items: DEFAULT_MAX_STREAM_ITEMS,
+        })
+    }
+
+    /// Sets the maximum number of items collected from a response stream.
+    ///
+    /// The default is 10000.
+    pub fn set_max_stream_items(&mut self, max: usize) {
+        self.max_stream_items = max;
+    }
+
+    fn collect<F>(
+        &mut self,
+        future: F,
+    ) -> Result<Vec<<F::Item as Stream>::Item>, core_client::Error>
+    where
+        F: Future<Error = core_client::Error>,
+        F::Item: Stream<Error = core_client::Error>,
+    {
+        let max = self.max_stream_items as u64;
+        self.runtime
+            .block_on(future.and_then(move |stream| stream.take(max).collect()))
+    }
+
+    /// Requests the identifier and the date of the current chain tip.
+    pub fn tip<T>(&mut self) -> Result<(T::Id, T::Date), core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::tip(&mut self.client);
+        self.runtime.block_on(future)
+    }
+
+    /// Requests the length of the peer's chain, as the height of its tip.
+    pub fn chain_length<T>(&mut self) -> Result<u64, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::chain_length(&mut self.client);
+        self.runtime.block_on(future)
+    }
+
+    /// Retrieves the blocks identified by `ids`.
+    pub fn get_blocks<T>(&mut self, ids: &[T::Id]) -> Result<Vec<T>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::get_blocks(&mut self.client, ids);
+        self.collect(future)
+    }
+
+    /// Retrieves the block identified by `id`.
+    pub fn get_block<T>(&mut self, id: T::Id) -> Result<T, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::get_block(&mut self.client, id);
+        self.runtime.block_on(future)
+    }
+
+    /// Checks which of the blocks identified by `ids` are known to the peer.
+    pub fn has_blocks<T>(&mut self, ids: &[T::Id]) -> Result<Vec<bool>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::has_blocks(&mut self.client, ids);
+        self.runtime.block_on(future)
+    }
+
+    /// Retrieves up to `count` consecutive blocks of the chain, starting
+    /// from the block at height `from_height`.
+    pub fn get_blocks_by_height<T>(
+        &mut self,
+        from_height: u64,
+        count: u32,
+    ) -> Result<Vec<T>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::get_blocks_by_height(&mut self.client, from_height, count);
+        self.collect(future)
+    }
+
+    /// Retrieves the identifier of the block at `height` in the chain
+    /// of the peer.
+    pub fn block_id_at_height<T>(&mut self, height: u64) -> Result<T::Id, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::block_id_at_height(&mut self.client, height);
+        self.runtime.block_on(future)
+    }
+
+    /// Retrieves the blocks from the latest of the `from` blocks known
+    /// to the peer up to the tip of its chain.
+    pub fn pull_blocks_to_tip<T>(&mut self, from: &[T::Id]) -> Result<Vec<T>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::pull_blocks_to_tip(&mut self.client, from);
+        self.collect(future)
+    }
+
+    /// Retrieves the blocks from the latest of the `from` blocks that is
+    /// an ancestor of `to`, up to and including `to`.
+    pub fn pull_blocks<T>(
+        &mut self,
+        from: &[T::Id],
+        to: &T::Id,
+    ) -> Result<Vec<T>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::pull_blocks(&mut self.client, from, to);
+        self.collect(future)
+    }
+
+    /// Retrieves the blocks of the chain in descending order, from the tip
+    /// back to, but not including, the latest of the `to` blocks.
+    pub fn pull_blocks_from_tip<T>(&mut self, to: &[T::Id]) -> Result<Vec<T>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::pull_blocks_from_tip(&mut self.client, to);
+        self.collect(future)
+    }
+
+    /// Retrieves the blocks from the latest of the `from` blocks known
+    /// to the peer up to the tip of its chain, letting the peer skip
+    /// the blocks in `known`.
+    ///
+    /// See `BlockService::pull_blocks_to_tip_filtered` on the need to
+    /// reconcile the blocks received.
+    pub fn pull_blocks_to_tip_filtered<T>(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Result<Vec<T>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: BlockService<T>,
+    {
+        let future = BlockService::<T>::pull_blocks_to_tip_filtered(&mut self.client, from, known);
+        self.collect(future)
+    }
+
+    /// Requests the header of the current chain tip.
+    pub fn tip_header<T>(&mut self) -> Result<T::Header, core_client::Error>
+    where
+        T: Block + HasHeader,
+        Client<S, DefaultExecutor>: HeaderService<T>,
+    {
+        let future = HeaderService::<T>::tip_header(&mut self.client);
+        self.runtime.block_on(future)
+    }
+
+    /// Retrieves the headers of the blocks identified by `ids`.
+    pub fn get_headers<T>(&mut self, ids: &[T::Id]) -> Result<Vec<T::Header>, core_client::Error>
+    where
+        T: Block + HasHeader,
+        Client<S, DefaultExecutor>: HeaderService<T>,
+    {
+        let future = HeaderService::<T>::get_headers(&mut self.client, ids);
+        self.collect(future)
+    }
+
+    /// Retrieves the headers of the last `count` blocks of the chain,
+    /// starting from the tip.
+    pub fn tip_headers<T>(&mut self, count: u32) -> Result<Vec<T::Header>, core_client::Error>
+    where
+        T: Block + HasHeader,
+        Client<S, DefaultExecutor>: HeaderService<T>,
+    {
+        let future = HeaderService::<T>::tip_headers(&mut self.client, count);
+        self.collect(future)
+    }
+
+    /// Retrieves the headers of the blocks following the latest of
+    /// the `from` blocks known to the peer, up to the tip of its chain.
+    pub fn pull_headers_to_tip<T>(
+        &mut self,
+        from: &[T::Id],
+    ) -> Result<Vec<T::Header>, core_client::Error>
+    where
+        T: Block + HasHeader,
+        Client<S, DefaultExecutor>: HeaderService<T>,
+    {
+        let future = HeaderService::<T>::pull_headers_to_tip(&mut self.client, from);
+        self.collect(future)
+    }
+
+    /// Checks that the peer is alive and retrieves information about it.
+    pub fn ping<T>(&mut self) -> Result<PeerInfo<T::Id>, core_client::Error>
+    where
+        T: Block,
+        Client<S, DefaultExecutor>: PeerService<T>,
+    {
+        let future = PeerService::<T>::ping(&mut self.client);
+        self.runtime.block_on(future)
+    }
+}
@@ -0,0 +1,363 @@
+use super::{
+    Client, ClientStreamingFuture, NodeClient, ResponseFuture, ResponseStream, ResponseStreamFuture,
+};
+use crate::gen;
+
+use chain_core::property::{Block, Deserialize, HasHeader, Serialize};
+use network_core::{
+    bloom::BloomFilter,
+    client::{
+        self as core_client,
+        block::{BlockService, HeaderService},
+    },
+};
+
+use futures::future::Executor;
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::{error, str::FromStr};
+
+/// A client for the block type `T` with the connection and executor
+/// types erased.
+///
+/// This type implements `BlockService` and `HeaderService` like
+/// `Client`, returning the same futures and streams, so it can be stored
+/// in application structures without propagating the type parameters
+/// of the underlying `Client`. The only overhead is a dynamic dispatch
+/// on each request method, and boxing of the streams passed to
+/// `upload_blocks` and `get_blocks_pipelined`.
+///
+/// Configuration methods specific to `Client`, such as
+/// `with_request_timeout`, should be called before the client is boxed.
+pub struct BoxClient<T: Block + HasHeader> {
+    inner: Box<dyn ErasedClient<T> + Send>,
+}
+
+impl<T> BoxClient<T>
+where
+    T: Block + HasHeader + 'static,
+{
+    /// Erases the type parameters of the client.
+    pub fn new<S, E>(client: Client<S, E>) -> Self
+    where
+        Client<S, E>: ErasedClient<T> + Send + 'static,
+    {
+        BoxClient {
+            inner: Box::new(client),
+        }
+    }
+}
+
+impl<T> Clone for BoxClient<T>
+where
+    T: Block + HasHeader,
+{
+    fn clone(&self) -> Self {
+        BoxClient {
+            inner: self.inner.box_clone(),
+        }
+    }
+}
+
+type BlockStreamFuture<T> = ResponseStreamFuture<T, gen::node::Block>;
+type HeaderStreamFuture<T> = ResponseStreamFuture<<T as HasHeader>::Header, gen::node::Header>;
+type BoxBlockStream<T> = Box<dyn Stream<Item = T, Error = core_client::Error> + Send>;
+type BoxIdStream<T> = Box<dyn Stream<Item = <T as Block>::Id, Error = core_client::Error> + Send>;
+
+// Object-safe counterpart of the service traits implemented by `Client`.
+// This needs to be public because it appears in the bounds of
+// `BoxClient::new`, but the module is private and the trait is not
+// re-exported.
+pub trait ErasedClient<T: Block + HasHeader> {
+    fn box_clone(&self) -> Box<dyn ErasedClient<T> + Send>;
+    fn tip(&mut self) -> ResponseFuture<(T::Id, T::Date), gen::node::TipResponse>;
+    fn chain_length(&mut self) -> ResponseFuture<u64, gen::node::ChainLengthResponse>;
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> BlockStreamFuture<T>;
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> BlockStreamFuture<T>;
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> BlockStreamFuture<T>;
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> BlockStreamFuture<T>;
+    fn get_blocks(&mut self, ids: &[T::Id]) -> BlockStreamFuture<T>;
+    fn get_block(&mut self, id: T::Id) -> ResponseFuture<T, gen::node::Block>;
+    fn get_blocks_pipelined(&mut self, ids: BoxIdStream<T>) -> BlockStreamFuture<T>;
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> BlockStreamFuture<T>;
+    fn block_id_at_height(
+        &mut self,
+        height: u64,
+    ) -> ResponseFuture<T::Id, gen::node::BlockIdResponse>;
+    fn has_blocks(
+        &mut self,
+        ids: &[T::Id],
+    ) -> ResponseFuture<Vec<bool>, gen::node::HasBlocksResponse>;
+    fn block_subscription(&mut self) -> BlockStreamFuture<T>;
+    fn upload_blocks(
+        &mut self,
+        blocks: BoxBlockStream<T>,
+    ) -> ClientStreamingFuture<u64, gen::node::UploadBlocksResponse>;
+    fn get_headers(&mut self, ids: &[T::Id]) -> HeaderStreamFuture<T>;
+    fn tip_header(&mut self) -> ResponseFuture<T::Header, gen::node::Header>;
+    fn tip_headers(&mut self, count: u32) -> HeaderStreamFuture<T>;
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> HeaderStreamFuture<T>;
+    fn push_headers(
+        &mut self,
+        headers: &[T::Header],
+    ) -> ClientStreamingFuture<(), gen::node::PushHeadersResponse>;
+}
+
+impl<T, S, E> ErasedClient<T> for Client<S, E>
+where
+    T: Block + HasHeader + 'static,
+    S: AsyncRead + AsyncWrite + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + 'static,
+    NodeClient<S, E>: Clone + Send + 'static,
+    T::Date: FromStr,
+    <T as Serialize>::Error: Send + Sync + 'static,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+    <T::Date as FromStr>::Err: error::Error + Send + Sync + 'static,
+    <T::Header as Serialize>::Error: Send + Sync + 'static,
+    <T::Header as Deserialize>::Error: Send + Sync + 'static,
+{
+    fn box_clone(&self) -> Box<dyn ErasedClient<T> + Send> {
+        Box::new(self.clone())
+    }
+
+    fn tip(&mut self) -> ResponseFuture<(T::Id, T::Date), gen::node::TipResponse> {
+        BlockService::<T>::tip(self)
+    }
+
+    fn chain_length(&mut self) -> ResponseFuture<u64, gen::node::ChainLengthResponse> {
+        BlockService::<T>::chain_length(self)
+    }
+
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> BlockStreamFuture<T> {
+        BlockService::<T>::pull_blocks_to_tip(self, from)
+    }
+
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> BlockStreamFuture<T> {
+        BlockService::<T>::pull_blocks(self, from, to)
+    }
+
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> BlockStreamFuture<T> {
+        BlockService::<T>::pull_blocks_from_tip(self, to)
+    }
+
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> BlockStreamFuture<T> {
+        BlockService::<T>::pull_blocks_to_tip_filtered(self, from, known)
+    }
+
+    fn get_blocks(&mut self, ids: &[T::Id]) -> BlockStreamFuture<T> {
+        BlockService::<T>::get_blocks(self, ids)
+    }
+
+    fn get_block(&mut self, id: T::Id) -> ResponseFuture<T, gen::node::Block> {
+        BlockService::<T>::get_block(self, id)
+    }
+
+    fn get_blocks_pipelined(&mut self, ids: BoxIdStream<T>) -> BlockStreamFuture<T> {
+        BlockService::<T>::get_blocks_pipelined(self, ids)
+    }
+
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> BlockStreamFuture<T> {
+        BlockService::<T>::get_blocks_by_height(self, from_height, count)
+    }
+
+    fn block_id_at_height(
+        &mut self,
+        height: u64,
+    ) -> ResponseFuture<T::Id, gen::node::BlockIdResponse> {
+        BlockService::<T>::block_id_at_height(self, height)
+    }
+
+    fn has_blocks(
+        &mut self,
+        ids: &[T::Id],
+    ) -> ResponseFuture<Vec<bool>, gen::node::HasBlocksResponse> {
+        BlockService::<T>::has_blocks(self, ids)
+    }
+
+    fn block_subscription(&mut self) -> BlockStreamFuture<T> {
+        BlockService::<T>::block_subscription(self)
+    }
+
+    fn upload_blocks(
+        &mut self,
+        blocks: BoxBlockStream<T>,
+    ) -> ClientStreamingFuture<u64, gen::node::UploadBlocksResponse> {
+        BlockService::<T>::upload_blocks(self, blocks)
+    }
+
+    fn get_headers(&mut self, ids: &[T::Id]) -> HeaderStreamFuture<T> {
+        HeaderService::<T>::get_headers(self, ids)
+    }
+
+    fn tip_header(&mut self) -> ResponseFuture<T::Header, gen::node::Header> {
+        HeaderService::<T>::tip_header(self)
+    }
+
+    fn tip_headers(&mut self, count: u32) -> HeaderStreamFuture<T> {
+        HeaderService::<T>::tip_headers(self, count)
+    }
+
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> HeaderStreamFuture<T> {
+        HeaderService::<T>::pull_headers_to_tip(self, from)
+    }
+
+    fn push_headers(
+        &mut self,
+        headers: &[T::Header],
+    ) -> ClientStreamingFuture<(), gen::node::PushHeadersResponse> {
+        HeaderService::<T>::push_headers(self, headers)
+    }
+}
+
+impl<T> BlockService<T> for BoxClient<T>
+where
+    T: Block + HasHeader + 'static,
+    T::Date: FromStr,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+    <T::Date as FromStr>::Err: error::Error + Send + Sync + 'static,
+{
+    type TipFuture = ResponseFuture<(T::Id, T::Date), gen::node::TipResponse>;
+
+    type ChainLengthFuture = ResponseFuture<u64, gen::node::ChainLengthResponse>;
+
+    type PullBlocksToTipStream = ResponseStream<T, gen::node::Block>;
+    type PullBlocksToTipFuture = BlockStreamFuture<T>;
+
+    type PullBlocksStream = ResponseStream<T, gen::node::Block>;
+    type PullBlocksFuture = BlockStreamFuture<T>;
+
+    type GetBlocksStream = ResponseStream<T, gen::node::Block>;
+    type GetBlocksFuture = BlockStreamFuture<T>;
+
+    type GetBlockFuture = ResponseFuture<T, gen::node::Block>;
+
+    type BlockIdAtHeightFuture = ResponseFuture<T::Id, gen::node::BlockIdResponse>;
+
+    type GetBlocksPipelinedStream = ResponseStream<T, gen::node::Block>;
+    type GetBlocksPipelinedFuture = BlockStreamFuture<T>;
+
+    type HasBlocksFuture = ResponseFuture<Vec<bool>, gen::node::HasBlocksResponse>;
+
+    type BlockSubscriptionStream = ResponseStream<T, gen::node::Block>;
+    type BlockSubscriptionFuture = BlockStreamFuture<T>;
+
+    fn tip(&mut self) -> Self::TipFuture {
+        self.inner.tip()
+    }
+
+    fn chain_length(&mut self) -> Self::ChainLengthFuture {
+        self.inner.chain_length()
+    }
+
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture {
+        self.inner.pull_blocks_to_tip(from)
+    }
+
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> Self::PullBlocksFuture {
+        self.inner.pull_blocks(from, to)
+    }
+
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> Self::PullBlocksFuture {
+        self.inner.pull_blocks_from_tip(to)
+    }
+
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Self::PullBlocksFuture {
+        self.inner.pull_blocks_to_tip_filtered(from, known)
+    }
+
+    fn get_blocks(&mut self, ids: &[T::Id]) -> Self::GetBlocksFuture {
+        self.inner.get_blocks(ids)
+    }
+
+    fn get_block(&mut self, id: T::Id) -> Self::GetBlockFuture {
+        self.inner.get_block(id)
+    }
+
+    fn get_blocks_pipelined<St>(&mut self, ids: St) -> Self::GetBlocksPipelinedFuture
+    where
+        St: Stream<Item = T::Id, Error = core_client::Error> + Send + 'static,
+    {
+        self.inner.get_blocks_pipelined(Box::new(ids))
+    }
+
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture {
+        self.inner.get_blocks_by_height(from_height, count)
+    }
+
+    fn block_id_at_height(&mut self, height: u64) -> Self::BlockIdAtHeightFuture {
+        self.inner.block_id_at_height(height)
+    }
+
+    fn has_blocks(&mut self, ids: &[T::Id]) -> Self::HasBlocksFuture {
+        self.inner.has_blocks(ids)
+    }
+
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture {
+        self.inner.block_subscription()
+    }
+
+    type UploadBlocksFuture = ClientStreamingFuture<u64, gen::node::UploadBlocksResponse>;
+
+    fn upload_blocks<St>(&mut self, blocks: St) -> Self::UploadBlocksFuture
+    where
+        St: Stream<Item = T, Error = core_client::Error> + Send + 'static,
+    {
+        self.inner.upload_blocks(Box::new(blocks))
+    }
+}
+
+impl<T> HeaderService<T> for BoxClient<T>
+where
+    T: Block + HasHeader + 'static,
+    <T::Header as Deserialize>::Error: Send + Sync + 'static,
+{
+    type GetHeadersStream = ResponseStream<T::Header, gen::node::Header>;
+    type GetHeadersFuture = HeaderStreamFuture<T>;
+
+    fn get_headers(&mut self, ids: &[T::Id]) -> Self::GetHeadersFuture {
+        self.inner.get_headers(ids)
+    }
+
+    type TipHeaderFuture = ResponseFuture<T::Header, gen::node::Header>;
+
+    fn tip_header(&mut self) -> Self::TipHeaderFuture {
+        self.inner.tip_header()
+    }
+
+    type TipHeadersStream = ResponseStream<T::Header, gen::node::Header>;
+    type TipHeadersFuture = HeaderStreamFuture<T>;
+
+    fn tip_headers(&mut self, count: u32) -> Self::TipHeadersFuture {
+        self.inner.tip_headers(count)
+    }
+
+    type PullHeadersStream = ResponseStream<T::Header, gen::node::Header>;
+    type PullHeadersFuture = HeaderStreamFuture<T>;
+
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> Self::PullHeadersFuture {
+        self.inner.pull_headers_to_tip(from)
+    }
+
+    type PushHeadersFuture = ClientStreamingFuture<(), gen::node::PushHeadersResponse>;
+
+    fn push_headers(&mut self, headers: &[T::Header]) -> Self::PushHeadersFuture {
+        self.inner.push_headers(headers)
+    }
+}
@@ -0,0 +1,509 @@
+use super::{
+    activity::Activity,
+    auth::AuthProvider,
+    cancel::CancelToken,
+    connection::ConnectionStatus,
+    host::DEFAULT_ATTEMPT_DELAY,
+    intercept::Interceptor,
+    metrics::{Metrics, Recorder},
+    service_path::{self, ServicePath},
+    span::RequestSpan,
+    Client, Error, RetryPolicy, StreamLimitBehavior,
+};
+use crate::{gen::node::client as gen_client, peer::Origin};
+
+use chain_core::property::Block;
+use network_core::client::peer::PeerService;
+
+use futures::future::{self, Executor};
+use tokio::io;
+use tokio::prelude::*;
+use tokio::timer::Timeout;
+use tower_grpc::BoxBody;
+use tower_h2::client::{Background, Connect, Connection};
+use tower_http::add_origin::{self, AddOrigin};
+use tower_util::MakeService;
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Builder for gRPC clients with customized connection settings.
+///
+/// A `ClientBuilder` can be reused to establish any number of
+/// connections with the same settings.
+#[derive(Clone)]
+pub struct ClientBuilder {
+    h2: h2::client::Builder,
+    keepalive: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    auth: Option<Arc<dyn AuthProvider>>,
+    interceptor: Option<Arc<dyn Interceptor>>,
+    pub(super) metrics: Recorder,
+    cancel_token: Option<CancelToken>,
+    service_path: Option<Arc<str>>,
+    verify_checksums: bool,
+    pub(super) attempt_delay: Duration,
+}
+
+impl ClientBuilder {
+    /// Creates a builder with the default settings.
+    pub fn new() -> Self {
+        ClientBuilder {
+            h2: h2::client::Builder::new(),
+            keepalive: None,
+            connect_timeout: None,
+            request_timeout: None,
+            retry_policy: None,
+            auth: None,
+            interceptor: None,
+            metrics: Recorder::default(),
+            cancel_token: None,
+            service_path: None,
+            verify_checksums: false,
+            attempt_delay: DEFAULT_ATTEMPT_DELAY,
+        }
+    }
+
+    /// Sets the initial HTTP/2 flow control window size for each stream,
+    /// in octets.
+    ///
+    /// This bounds the amount of response data the peer can send ahead
+    /// of a consumer of a `ResponseStream` that is slower than the
+    /// transport.
+    pub fn initial_stream_window_size(&mut self, size: u32) -> &mut Self {
+        self.h2.initial_window_size(size);
+        self
+    }
+
+    /// Sets the initial HTTP/2 flow control window size for the
+    /// connection as a whole, in octets.
+    pub fn initial_connection_window_size(&mut self, size: u32) -> &mut Self {
+        self.h2.initial_connection_window_size(size);
+        self
+    }
+
+    /// Sets the maximum number of concurrent streams advertised to the peer
+    /// in the HTTP/2 settings.
+    ///
+    /// Note that this limits the streams initiated by the peer; the number
+    /// of concurrent requests the client can make is limited by the
+    /// settings advertised by the peer.
+    pub fn max_concurrent_streams(&mut self, max: u32) -> &mut Self {
+        self.h2.max_concurrent_streams(max);
+        self
+    }
+
+    /// Sets the TCP keepalive interval of the connections established
+    /// over TCP, including those made by `build_connect_host`,
+    /// `build_connect_proxy`, `ClientPool` and `ReconnectingClient`.
+    ///
+    /// The h2 library does not provide HTTP/2 PING frames to the client,
+    /// so TCP keepalive is the means to detect dead peers on long-lived
+    /// connections. A keepalive interval set on the `TcpPeer` itself
+    /// takes precedence. Connections to other kinds of peers are not
+    /// affected. Keepalive is disabled by default.
+    pub fn tcp_keepalive(&mut self, interval: Duration) -> &mut Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    /// Sets the timeout for establishing a connection, including
+    /// the HTTP/2 handshake.
+    ///
+    /// A connection attempt that does not complete in time fails with
+    /// `Error::Timeout`. There is no timeout by default.
+    pub fn connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for unary requests made with the built clients.
+    ///
+    /// See `Client::with_request_timeout` for details.
+    pub fn request_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the policy for retrying idempotent unary requests
+    /// made with the built clients.
+    ///
+    /// See `Client::with_retry_policy` for details.
+    pub fn retry_policy(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Installs a provider of bearer tokens to authenticate all requests
+    /// made with the built clients.
+    ///
+    /// See the documentation of `AuthProvider` for details.
+    pub fn auth_provider<A>(&mut self, provider: A) -> &mut Self
+    where
+        A: AuthProvider + 'static,
+    {
+        self.auth = Some(Arc::new(provider));
+        self
+    }
+
+    /// Installs a hook invoked on every request made with the built
+    /// clients before the request is sent.
+    ///
+    /// See the documentation of `Interceptor` for details.
+    pub fn interceptor<I>(&mut self, interceptor: I) -> &mut Self
+    where
+        I: Interceptor + 'static,
+    {
+        self.interceptor = Some(Arc::new(interceptor));
+        self
+    }
+
+    /// Sets the token to cancel all requests made with the built
+    /// clients at once.
+    ///
+    /// See `Client::with_cancel_token` for details.
+    pub fn cancel_token(&mut self, token: CancelToken) -> &mut Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Sets the name of the Node service used in the paths of requests
+    /// made with the built clients, for peers that serve it under
+    /// a different name than `iohk.chain.node.Node` from node.proto.
+    ///
+    /// This is meant for deployments behind gateways that route requests
+    /// by versioned service names: with the name `Node.v2`, the `Tip`
+    /// request is sent to path `/Node.v2/Tip` instead of
+    /// `/iohk.chain.node.Node/Tip`. The messages are not changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is empty or contains characters that
+    /// are not valid in the path of a request, including `/`.
+    pub fn node_service_name(&mut self, name: &str) -> &mut Self {
+        match service_path::path_prefix(name) {
+            Some(prefix) => self.service_path = Some(prefix),
+            None => panic!("invalid service name {:?}", name),
+        }
+        self
+    }
+
+    /// Enables verification of the checksums that the peer may send
+    /// with the blocks it streams or responds with, to detect corrupted
+    /// block content before it gets deserialized.
+    ///
+    /// A block whose content does not match its checksum fails
+    /// the request, or the response stream at the position of
+    /// the block, with an error of kind `ErrorKind::Format`.
+    /// Blocks sent without a checksum are not verified.
+    /// Verification is disabled by default.
+    pub fn verify_block_checksums(&mut self, verify: bool) -> &mut Self {
+        self.verify_checksums = verify;
+        self
+    }
+
+    /// Installs an implementation of `Metrics` to receive
+    /// instrumentation events from connection attempts made by
+    /// this builder and requests made with the built clients.
+    pub fn metrics(&mut self, metrics: Arc<dyn Metrics>) -> &mut Self {
+        self.metrics = Recorder::new(metrics);
+        self
+    }
+
+    /// Connects to the peer with the settings of this builder,
+    /// using the origin derived from the peer's connection details.
+    pub fn build_connect<P, S, E>(
+        &self,
+        mut peer: P,
+        executor: E,
+    ) -> impl Future<Item = Client<S, E>, Error = Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+        S: AsyncRead + AsyncWrite,
+        E: Executor<Background<S, BoxBody>> + Clone,
+    {
+        if let Some(interval) = self.keepalive {
+            peer.set_default_keepalive(interval);
+        }
+        let origin = peer.origin();
+        self.build_connect_with_origin(peer, origin, executor)
+    }
+
+    // Returns the TCP keepalive interval for the connections made by
+    // this builder.
+    pub(super) fn keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
+
+    /// Connects to the peer with the settings of this builder and
+    /// verifies that the peer serves the blockchain with the expected
+    /// genesis block.
+    ///
+    /// Once connected, the client pings the peer, with the request
+    /// timeout and retry policy of this builder, and compares
+    /// the genesis block identifier reported by the peer with
+    /// `genesis_hash`. The future fails with `Error::NetworkMismatch`
+    /// if they differ, or with `Error::Ping` if the ping fails.
+    pub fn build_connect_verified<T, P, S, E>(
+        &self,
+        peer: P,
+        executor: E,
+        genesis_hash: T::Id,
+    ) -> impl Future<Item = Client<S, E>, Error = Error>
+    where
+        T: Block,
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+        S: AsyncRead + AsyncWrite,
+        E: Executor<Background<S, BoxBody>> + Clone,
+        Client<S, E>: PeerService<T>,
+    {
+        self.build_connect(peer, executor)
+            .and_then(move |mut client| {
+                PeerService::<T>::ping(&mut client)
+                    .map_err(Error::Ping)
+                    .and_then(move |info| {
+                        if info.genesis_hash == genesis_hash {
+                            Ok(client)
+                        } else {
+                            Err(Error::NetworkMismatch {
+                                expected: format!("{:?}", genesis_hash),
+                                got: format!("{:?}", info.genesis_hash),
+                            })
+                        }
+                    })
+            })
+    }
+
+    /// Connects to the peer with the settings of this builder,
+    /// setting the scheme and authority of the specified origin URI
+    /// on all requests.
+    pub fn build_connect_with_origin<P, S, E>(
+        &self,
+        peer: P,
+        origin: http::Uri,
+        executor: E,
+    ) -> impl Future<Item = Client<S, E>, Error = Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + 'static,
+        S: AsyncRead + AsyncWrite,
+        E: Executor<Background<S, BoxBody>> + Clone,
+    {
+        let request_timeout = self.request_timeout;
+        let retry_policy = self.retry_policy.clone();
+        let auth = self.auth.clone();
+        let interceptor = self.interceptor.clone();
+        let cancel_token = self.cancel_token.clone();
+        let service_path = self.service_path.clone();
+        let verify_checksums = self.verify_checksums;
+        let metrics = self.metrics.clone();
+        let client_metrics = self.metrics.clone();
+        let started = Instant::now();
+        let span = RequestSpan::new(&origin, "connect");
+        let status_span = span.clone();
+        let mut connect = self
+            .connect_service(peer, origin.clone(), executor)
+            .map(move |conn| Client {
+                node: gen_client::Node::new(ServicePath::new(conn, service_path)),
+                origin,
+                connection: ConnectionStatus::new(),
+                request_timeout,
+                retry_policy,
+                auth,
+                interceptor,
+                metrics: client_metrics,
+                stream_limit: None,
+                stream_limit_behavior: StreamLimitBehavior::default(),
+                cancel_token,
+                verify_checksums,
+                activity: Activity::default(),
+            })
+            .then(move |res| {
+                metrics.connect_completed(started, &res);
+                status_span.record_status(&res);
+                res
+            });
+        future::poll_fn(move || span.in_scope(|| connect.poll()))
+    }
+
+    // Establishes the HTTP/2 connection, setting the origin on
+    // all requests made through it.
+    pub(super) fn connect_service<P, S, E>(
+        &self,
+        peer: P,
+        origin: http::Uri,
+        executor: E,
+    ) -> impl Future<Item = AddOrigin<Connection<S, E, BoxBody>>, Error = Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + 'static,
+        S: AsyncRead + AsyncWrite,
+        E: Executor<Background<S, BoxBody>> + Clone,
+    {
+        let mut make_client = Connect::new(peer, self.h2.clone(), executor);
+        let connect = make_client
+            .make_service(())
+            .map_err(|e| Error::Connect(e))
+            .and_then(move |conn| {
+                add_origin::Builder::new()
+                    .uri(origin.clone())
+                    .build(conn)
+                    .map_err(|_| Error::InvalidOrigin(origin))
+            });
+        match self.connect_timeout {
+            None => future::Either::A(connect),
+            // A failure of the timer is reported as a timeout as well.
+            Some(timeout) => future::Either::B(
+                Timeout::new(connect, timeout)
+                    .map_err(|e| e.into_inner().unwrap_or(Error::Timeout)),
+            ),
+        }
+    }
+}
+
+impl fmt::Debug for ClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClientBuilder")
+            .field("h2", &self.h2)
+            .field("keepalive", &self.keepalive)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("retry_policy", &self.retry_policy)
+            .field("auth", &self.auth.is_some())
+            .field("interceptor", &self.interceptor.is_some())
+            .field("metrics", &self.metrics)
+            .field("service_path", &self.service_path)
+            .field("verify_checksums", &self.verify_checksums)
+            .field("attempt_delay", &self.attempt_delay)
+            .finish()
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClientBuilder;
+    use crate::client::Error;
+    use crate::peer::{Origin, TcpPeer};
+
+    use futures::future;
+    use tokio::executor::DefaultExecutor;
+    use tokio::net::TcpStream;
+    use tokio::runtime::current_thread::Runtime;
+
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    // A peer that never gets connected to.
+    struct Unreachable;
+
+    impl tokio_connect::Connect for Unreachable {
+        type Connected = TcpStream;
+        type Error = io::Error;
+        type Future = future::Empty<TcpStream, io::Error>;
+
+        fn connect(&self) -> Self::Future {
+            future::empty()
+        }
+    }
+
+    impl Origin for Unreachable {
+        fn origin(&self) -> http::Uri {
+            "http://unreachable".parse().unwrap()
+        }
+    }
+
+    #[test]
+    fn connection_attempt_times_out() {
+        let mut rt = Runtime::new().unwrap();
+        let connect = ClientBuilder::new()
+            .connect_timeout(Duration::from_millis(50))
+            .build_connect(Unreachable, DefaultExecutor::current());
+        match rt.block_on(connect) {
+            Err(Error::Timeout) => {}
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("the connection attempt should have timed out"),
+        }
+    }
+
+    // A TCP peer that records the keepalive interval it would connect
+    // with, instead of connecting.
+    struct KeepaliveProbe {
+        inner: TcpPeer,
+        keepalive: Arc<Mutex<Option<Duration>>>,
+    }
+
+    impl tokio_connect::Connect for KeepaliveProbe {
+        type Connected = TcpStream;
+        type Error = io::Error;
+        type Future = future::Empty<TcpStream, io::Error>;
+
+        fn connect(&self) -> Self::Future {
+            *self.keepalive.lock().unwrap() = self.inner.keepalive();
+            future::empty()
+        }
+    }
+
+    impl Origin for KeepaliveProbe {
+        fn origin(&self) -> http::Uri {
+            self.inner.origin()
+        }
+
+        fn set_default_keepalive(&mut self, interval: Duration) {
+            self.inner.set_default_keepalive(interval)
+        }
+    }
+
+    fn connect_keepalive(
+        builder: &ClientBuilder,
+        peer_keepalive: Option<Duration>,
+    ) -> Option<Duration> {
+        let mut rt = Runtime::new().unwrap();
+        let mut inner = TcpPeer::new("127.0.0.1:1".parse().unwrap());
+        inner.set_keepalive(peer_keepalive);
+        let keepalive = Arc::new(Mutex::new(None));
+        let peer = KeepaliveProbe {
+            inner,
+            keepalive: keepalive.clone(),
+        };
+        let connect = builder
+            .clone()
+            .connect_timeout(Duration::from_millis(10))
+            .build_connect(peer, DefaultExecutor::current());
+        let _ = rt.block_on(connect);
+        let keepalive = *keepalive.lock().unwrap();
+        keepalive
+    }
+
+    #[test]
+    fn tcp_keepalive_is_applied_to_tcp_peers() {
+        let mut builder = ClientBuilder::new();
+        assert_eq!(connect_keepalive(&builder, None), None);
+        builder.tcp_keepalive(Duration::from_secs(30));
+        assert_eq!(
+            connect_keepalive(&builder, None),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn keepalive_of_the_peer_takes_precedence() {
+        let mut builder = ClientBuilder::new();
+        builder.tcp_keepalive(Duration::from_secs(30));
+        assert_eq!(
+            connect_keepalive(&builder, Some(Duration::from_secs(5))),
+            Some(Duration::from_secs(5))
+        );
+    }
+}
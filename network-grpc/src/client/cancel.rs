@@ -0,0 +1,168 @@
+use futures::{future::Shared, prelude::*, sync::oneshot, task::AtomicTask};
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+struct Shared {
+    canceled: AtomicBool,
+    task: AtomicTask,
+}
+
+/// A handle to cancel a streamed response.
+///
+/// A handle can be obtained from a `ResponseStreamFuture` or
+/// a `ResponseStream`, and cloned to be passed to other tasks.
+#[derive(Clone)]
+pub struct StreamHandle {
+    shared: Arc<Shared>,
+}
+
+impl StreamHandle {
+    pub(super) fn new() -> Self {
+        StreamHandle {
+            shared: Arc::new(Shared {
+                canceled: AtomicBool::new(false),
+                task: AtomicTask::new(),
+            }),
+        }
+    }
+
+    /// Cancels the response stream.
+    ///
+    /// The HTTP/2 stream of the request is reset with the `CANCEL` code,
+    /// which the server sees as cancellation of the gRPC call, and the
+    /// stream slot on the connection is released. The response stream
+    /// terminates on its next poll; if the task polling it is waiting
+    /// for a message, it is woken up.
+    pub fn cancel(&self) {
+        self.shared.canceled.store(true, Ordering::SeqCst);
+        self.shared.task.notify();
+    }
+
+    /// Returns true if the response stream has been canceled.
+    pub fn is_canceled(&self) -> bool {
+        self.shared.canceled.load(Ordering::SeqCst)
+    }
+
+    // Checks for cancellation, registering the current task to be
+    // notified if the stream is canceled later.
+    pub(super) fn poll_canceled(&self) -> bool {
+        if self.is_canceled() {
+            return true;
+        }
+        self.shared.task.register();
+        // Check again to not miss a cancellation that happened before
+        // the task was registered.
+        self.is_canceled()
+    }
+}
+
+/// A token to cancel all requests made with a client at once.
+///
+/// The token is set on a client with `Client::with_cancel_token` or
+/// `ClientBuilder::cancel_token`, and can be cloned to be kept by the task
+/// that decides when to cancel, e.g. on shutdown. Unlike `StreamHandle`,
+/// which ends a single response stream, the token fails all response
+/// futures and streams of the client with an error of kind
+/// `ErrorKind::Canceled`. A token is triggered once and for all.
+#[derive(Clone)]
+pub struct CancelToken {
+    trigger: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    signal: Shared<oneshot::Receiver<()>>,
+}
+
+impl CancelToken {
+    /// Creates a token that has not been triggered.
+    pub fn new() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        CancelToken {
+            trigger: Arc::new(Mutex::new(Some(sender))),
+            signal: receiver.shared(),
+        }
+    }
+
+    /// Cancels the requests made with the clients having this token.
+    ///
+    /// The tasks polling the requests are woken up. Requests made
+    /// afterwards fail on their first poll.
+    pub fn cancel(&self) {
+        if let Some(sender) = self.trigger.lock().unwrap().take() {
+            let _ = sender.send(());
+        }
+    }
+
+    /// Returns true if the token has been triggered.
+    pub fn is_canceled(&self) -> bool {
+        self.trigger.lock().unwrap().is_none()
+    }
+
+    // Checks for cancellation, registering the current task to be
+    // notified if the token is triggered later.
+    pub(super) fn poll_canceled(&mut self) -> bool {
+        match self.signal.poll() {
+            Ok(Async::NotReady) => false,
+            Ok(Async::Ready(_)) => true,
+            // The sender is kept alive by every clone of the token,
+            // so it is only dropped by triggering.
+            Err(_) => true,
+        }
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        CancelToken::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_types::{TestBlock, TestId};
+    use crate::{client::ClientBuilder, peer::TcpPeer};
+
+    use network_core::client::{block::BlockService, ErrorKind};
+
+    use tokio::executor::DefaultExecutor;
+    use tokio::net::TcpListener;
+    use tokio::runtime::current_thread::Runtime;
+    use tokio::timer::Delay;
+
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn triggering_the_token_aborts_pull_blocks_to_tip() {
+        let mut rt = Runtime::new().unwrap();
+        // The peer accepts the connection in the listen backlog,
+        // but never responds, so the request stays in progress.
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer = TcpPeer::new(listener.local_addr().unwrap());
+        let token = CancelToken::new();
+        let mut client = rt
+            .block_on(
+                ClientBuilder::new()
+                    .cancel_token(token.clone())
+                    .build_connect(peer, DefaultExecutor::current()),
+            )
+            .unwrap();
+
+        let pull = BlockService::<TestBlock>::pull_blocks_to_tip(&mut client, &[TestId(0)]);
+        let trigger = token.clone();
+        rt.spawn(
+            Delay::new(Instant::now() + Duration::from_millis(50))
+                .map(move |()| trigger.cancel())
+                .map_err(|e| panic!("timer error: {}", e)),
+        );
+        match rt.block_on(pull) {
+            Err(e) => match e.kind() {
+                ErrorKind::Canceled => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            Ok(_) => panic!("the request was not canceled"),
+        }
+        assert!(token.is_canceled());
+        drop(listener);
+    }
+}
@@ -0,0 +1,180 @@
+use network_core::client as core_client;
+
+use futures::{prelude::*, stream::Fuse};
+use tokio::timer::Delay;
+
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+/// Stream adapter that collects the items of the stream into chunks.
+///
+/// A chunk is yielded when it holds `max_len` items, or when
+/// `flush_after` has elapsed since the first item of the chunk has been
+/// received, so items trickling in slowly are not held back for long.
+/// When the stream ends, the remaining items are yielded as a final,
+/// shorter chunk. If the stream fails, the items received before the
+/// error are yielded first, and the error on the next poll. This is
+/// meant for batching e.g. the storage of downloaded blocks.
+///
+/// An adapter for a `ResponseStream` is created with its `chunks`
+/// method; any stream can be wrapped with `Chunks::new`.
+pub struct Chunks<S: Stream> {
+    inner: Fuse<S>,
+    max_len: usize,
+    flush_after: Duration,
+    items: Vec<S::Item>,
+    // The deadline for flushing the current chunk, once it is not empty.
+    flush: Option<Delay>,
+    err: Option<core_client::Error>,
+}
+
+impl<S> Chunks<S>
+where
+    S: Stream<Error = core_client::Error>,
+{
+    /// Wraps the stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_len` is 0.
+    pub fn new(stream: S, max_len: usize, flush_after: Duration) -> Self {
+        assert!(max_len > 0, "the chunk length should not be 0");
+        Chunks {
+            inner: stream.fuse(),
+            max_len,
+            flush_after,
+            items: Vec::with_capacity(max_len),
+            flush: None,
+            err: None,
+        }
+    }
+
+    /// Consumes the adapter, returning the wrapped stream.
+    ///
+    /// The items collected into a chunk not yet yielded are dropped.
+    pub fn into_inner(self) -> S {
+        self.inner.into_inner()
+    }
+
+    fn take_chunk(&mut self) -> Vec<S::Item> {
+        self.flush = None;
+        mem::replace(&mut self.items, Vec::with_capacity(self.max_len))
+    }
+}
+
+impl<S> Stream for Chunks<S>
+where
+    S: Stream<Error = core_client::Error>,
+{
+    type Item = Vec<S::Item>;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<Vec<S::Item>>, core_client::Error> {
+        if let Some(e) = self.err.take() {
+            return Err(e);
+        }
+        loop {
+            match self.inner.poll() {
+                Ok(Async::Ready(Some(item))) => {
+                    if self.items.is_empty() {
+                        self.flush = Some(Delay::new(Instant::now() + self.flush_after));
+                    }
+                    self.items.push(item);
+                    if self.items.len() >= self.max_len {
+                        return Ok(Async::Ready(Some(self.take_chunk())));
+                    }
+                }
+                Ok(Async::Ready(None)) => {
+                    if self.items.is_empty() {
+                        return Ok(Async::Ready(None));
+                    }
+                    return Ok(Async::Ready(Some(self.take_chunk())));
+                }
+                Ok(Async::NotReady) => {
+                    let flush = match self.flush {
+                        None => return Ok(Async::NotReady),
+                        Some(ref mut flush) => flush,
+                    };
+                    return match flush.poll() {
+                        Ok(Async::NotReady) => Ok(Async::NotReady),
+                        Ok(Async::Ready(())) => Ok(Async::Ready(Some(self.take_chunk()))),
+                        Err(e) => Err(core_client::Error::new(core_client::ErrorKind::Rpc, e)),
+                    };
+                }
+                Err(e) => {
+                    if self.items.is_empty() {
+                        return Err(e);
+                    }
+                    self.err = Some(e);
+                    return Ok(Async::Ready(Some(self.take_chunk())));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream;
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn yields_full_chunks_and_the_remainder() {
+        let mut rt = Runtime::new().unwrap();
+        let stream = Chunks::new(
+            stream::iter_ok::<_, core_client::Error>(1..=5),
+            2,
+            Duration::from_secs(10),
+        );
+        let chunks = rt.block_on(stream.collect()).unwrap();
+        assert_eq!(chunks, vec![vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn flushes_a_partial_chunk_after_the_timeout() {
+        let mut rt = Runtime::new().unwrap();
+        let second = Delay::new(Instant::now() + Duration::from_millis(500))
+            .map(|()| 2)
+            .map_err(|e| core_client::Error::new(core_client::ErrorKind::Rpc, e))
+            .into_stream();
+        let stream = Chunks::new(
+            stream::once(Ok(1)).chain(second),
+            10,
+            Duration::from_millis(20),
+        );
+        let (chunk, stream) = rt
+            .block_on(stream.into_future())
+            .map_err(|(e, _)| e)
+            .unwrap();
+        assert_eq!(chunk, Some(vec![1]));
+        let chunks = rt.block_on(stream.collect()).unwrap();
+        assert_eq!(chunks, vec![vec![2]]);
+    }
+
+    #[test]
+    fn yields_the_received_items_before_an_error() {
+        let mut rt = Runtime::new().unwrap();
+        let err = core_client::Error::new(core_client::ErrorKind::Rpc, "stream failed");
+        let stream = Chunks::new(
+            stream::iter_result(vec![Ok(1), Ok(2), Err(err)]),
+            10,
+            Duration::from_secs(10),
+        );
+        let (chunk, stream) = rt
+            .block_on(stream.into_future())
+            .map_err(|(e, _)| e)
+            .unwrap();
+        assert_eq!(chunk, Some(vec![1, 2]));
+        match rt.block_on(stream.into_future()) {
+            Err((ref e, _)) => match e.kind() {
+                core_client::ErrorKind::Rpc => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            Ok((chunk, _)) => panic!("unexpected chunk {:?}", chunk),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use chain_core::property::Block;
+use network_core::{
+    bloom::BloomFilter,
+    client::{self as core_client, block::BlockService},
+};
+
+use futures::future::Shared;
+use futures::prelude::*;
+
+use std::{
+    error,
+    sync::{Arc, Mutex},
+};
+
+/// A wrapper over a client that coalesces concurrent requests
+/// which take no arguments.
+///
+/// When `tip` or `chain_length` is called on the wrapper or any of its
+/// clones while an earlier call of the same method is still awaiting
+/// its response, no new request is made: the returned future waits for
+/// the response to the earlier request, and all of the waiting futures
+/// resolve to the same result. This saves round trips to the peer when
+/// many tasks sharing a client poll the tip of its chain at the same
+/// time. Once the shared request has completed, the next call makes
+/// a new request, so callers do not receive a result retained from an
+/// earlier round.
+///
+/// The other methods of `BlockService` take arguments and are passed
+/// to the wrapped client as they are, without coalescing.
+///
+/// If all futures waiting for a request are dropped before it completes,
+/// the request is dropped as well, and the next call makes a new one.
+pub struct CoalescingClient<T, C>
+where
+    T: Block,
+    C: BlockService<T>,
+{
+    inner: C,
+    tip: Coalescer<C::TipFuture>,
+    chain_length: Coalescer<C::ChainLengthFuture>,
+}
+
+impl<T, C> CoalescingClient<T, C>
+where
+    T: Block,
+    C: BlockService<T>,
+{
+    /// Wraps the client to coalesce its argument-free requests.
+    ///
+    /// Requests are only coalesced between clones of the returned
+    /// wrapper, so the wrapper should be cloned rather than the client.
+    pub fn new(client: C) -> Self {
+        CoalescingClient {
+            inner: client,
+            tip: Coalescer::new(),
+            chain_length: Coalescer::new(),
+        }
+    }
+
+    /// Returns a reference to the underlying client.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying client.
+    ///
+    /// Requests made directly on the client are not coalesced.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Consumes the wrapper, returning the underlying client.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<T, C> Clone for CoalescingClient<T, C>
+where
+    T: Block,
+    C: BlockService<T> + Clone,
+{
+    fn clone(&self) -> Self {
+        CoalescingClient {
+            inner: self.inner.clone(),
+            tip: self.tip.clone(),
+            chain_length: self.chain_length.clone(),
+        }
+    }
+}
+
+impl<T, C> BlockService<T> for CoalescingClient<T, C>
+where
+    T: Block,
+    C: BlockService<T>,
+{
+    type TipFuture = CoalescedFuture<C::TipFuture>;
+    type ChainLengthFuture = CoalescedFuture<C::ChainLengthFuture>;
+    type PullBlocksToTipStream = C::PullBlocksToTipStream;
+    type PullBlocksToTipFuture = C::PullBlocksToTipFuture;
+    type PullBlocksStream = C::PullBlocksStream;
+    type PullBlocksFuture = C::PullBlocksFuture;
+    type GetBlocksStream = C::GetBlocksStream;
+    type GetBlocksFuture = C::GetBlocksFuture;
+    type GetBlockFuture = C::GetBlockFuture;
+    type GetBlocksPipelinedStream = C::GetBlocksPipelinedStream;
+    type GetBlocksPipelinedFuture = C::GetBlocksPipelinedFuture;
+    type BlockIdAtHeightFuture = C::BlockIdAtHeightFuture;
+    type HasBlocksFuture = C::HasBlocksFuture;
+    type BlockSubscriptionStream = C::BlockSubscriptionStream;
+    type BlockSubscriptionFuture = C::BlockSubscriptionFuture;
+    type UploadBlocksFuture = C::UploadBlocksFuture;
+
+    fn tip(&mut self) -> Self::TipFuture {
+        let inner = &mut self.inner;
+        self.tip.attach(|| inner.tip())
+    }
+
+    fn chain_length(&mut self) -> Self::ChainLengthFuture {
+        let inner = &mut self.inner;
+        self.chain_length.attach(|| inner.chain_length())
+    }
+
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture {
+        self.inner.pull_blocks_to_tip(from)
+    }
+
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> Self::PullBlocksFuture {
+        self.inner.pull_blocks(from, to)
+    }
+
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> Self::PullBlocksFuture {
+        self.inner.pull_blocks_from_tip(to)
+    }
+
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Self::PullBlocksFuture {
+        self.inner.pull_blocks_to_tip_filtered(from, known)
+    }
+
+    fn get_blocks(&mut self, ids: &[T::Id]) -> Self::GetBlocksFuture {
+        self.inner.get_blocks(ids)
+    }
+
+    fn get_block(&mut self, id: T::Id) -> Self::GetBlockFuture {
+        self.inner.get_block(id)
+    }
+
+    fn get_blocks_pipelined<S>(&mut self, ids: S) -> Self::GetBlocksPipelinedFuture
+    where
+        S: Stream<Item = T::Id, Error = core_client::Error> + Send + 'static,
+    {
+        self.inner.get_blocks_pipelined(ids)
+    }
+
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture {
+        self.inner.get_blocks_by_height(from_height, count)
+    }
+
+    fn block_id_at_height(&mut self, height: u64) -> Self::BlockIdAtHeightFuture {
+        self.inner.block_id_at_height(height)
+    }
+
+    fn has_blocks(&mut self, ids: &[T::Id]) -> Self::HasBlocksFuture {
+        self.inner.has_blocks(ids)
+    }
+
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture {
+        self.inner.block_subscription()
+    }
+
+    fn upload_blocks<S>(&mut self, blocks: S) -> Self::UploadBlocksFuture
+    where
+        S: Stream<Item = T, Error = core_client::Error> + Send + 'static,
+    {
+        self.inner.upload_blocks(blocks)
+    }
+}
+
+// The request in flight for a method, shared by the clones of
+// the wrapper. The generation tells the waiters of a replaced request
+// from those of the current one.
+struct Slot<F: Future> {
+    in_flight: Option<InFlight<F>>,
+    generation: u64,
+}
+
+struct InFlight<F: Future> {
+    future: Shared<F>,
+    waiters: usize,
+}
+
+struct Coalescer<F: Future> {
+    slot: Arc<Mutex<Slot<F>>>,
+}
+
+impl<F: Future> Clone for Coalescer<F> {
+    fn clone(&self) -> Self {
+        Coalescer {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<F: Future> Coalescer<F> {
+    fn new() -> Self {
+        let slot = Slot {
+            in_flight: None,
+            generation: 0,
+        };
+        Coalescer {
+            slot: Arc::new(Mutex::new(slot)),
+        }
+    }
+
+    // Returns a future waiting for the request in flight, or for
+    // a request made with `start` if there is none.
+    fn attach<S>(&self, start: S) -> CoalescedFuture<F>
+    where
+        S: FnOnce() -> F,
+    {
+        let mut slot = self.slot.lock().unwrap();
+        let generation = slot.generation;
+        if let Some(ref mut in_flight) = slot.in_flight {
+            if in_flight.future.peek().is_none() {
+                in_flight.waiters += 1;
+                return CoalescedFuture {
+                    shared: in_flight.future.clone(),
+                    slot: self.slot.clone(),
+                    generation,
+                };
+            }
+        }
+        let generation = generation.wrapping_add(1);
+        let future = start().shared();
+        slot.generation = generation;
+        slot.in_flight = Some(InFlight {
+            future: future.clone(),
+            waiters: 1,
+        });
+        CoalescedFuture {
+            shared: future,
+            slot: self.slot.clone(),
+            generation,
+        }
+    }
+}
+
+/// Future returned by the coalesced methods of `CoalescingClient`,
+/// resolving to the result of a request shared with other callers.
+pub struct CoalescedFuture<F: Future> {
+    shared: Shared<F>,
+    slot: Arc<Mutex<Slot<F>>>,
+    generation: u64,
+}
+
+impl<F> Future for CoalescedFuture<F>
+where
+    F: Future<Error = core_client::Error>,
+    F::Item: Clone,
+{
+    type Item = F::Item;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, core_client::Error> {
+        match self.shared.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(item)) => Ok(Async::Ready((*item).clone())),
+            // Each of the waiters gets a copy of the error.
+            Err(e) => Err(copy_error(&e)),
+        }
+    }
+}
+
+// Copies the error with the message of its source, which is not
+// part of the displayed error, and the retry hint if there is one.
+fn copy_error(e: &core_client::Error) -> core_client::Error {
+    let msg = error::Error::source(e).map_or_else(String::new, ToString::to_string);
+    let copy = core_client::Error::new(e.kind(), msg);
+    match e.retry_after() {
+        Some(delay) => copy.with_retry_after(delay),
+        None => copy,
+    }
+}
+
+impl<F: Future> Drop for CoalescedFuture<F> {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock().unwrap();
+        if slot.generation != self.generation {
+            return;
+        }
+        let last = match slot.in_flight {
+            Some(ref mut in_flight) => {
+                in_flight.waiters -= 1;
+                in_flight.waiters == 0
+            }
+            None => false,
+        };
+        if last {
+            slot.in_flight = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Coalescer;
+
+    use network_core::client as core_client;
+
+    use futures::{future, prelude::*, sync::oneshot};
+
+    use std::{cell::Cell, error, time::Duration};
+
+    fn response(
+        rx: oneshot::Receiver<u64>,
+    ) -> Box<dyn Future<Item = u64, Error = core_client::Error>> {
+        Box::new(rx.map_err(|e| core_client::Error::new(core_client::ErrorKind::Canceled, e)))
+    }
+
+    #[test]
+    fn concurrent_calls_share_a_request() {
+        let coalescer = Coalescer::new();
+        let started = Cell::new(0);
+        let (tx, rx) = oneshot::channel();
+        let mut rx = Some(rx);
+        let mut start = || {
+            started.set(started.get() + 1);
+            response(rx.take().unwrap())
+        };
+        let first = coalescer.attach(&mut start);
+        let second = coalescer.attach(&mut start);
+        assert_eq!(started.get(), 1);
+        tx.send(42).unwrap();
+        assert_eq!(first.wait().unwrap(), 42);
+        assert_eq!(second.wait().unwrap(), 42);
+
+        // A call after the response has been received is not coalesced.
+        let third = coalescer.attach(|| {
+            started.set(started.get() + 1);
+            Box::new(future::ok(43)) as Box<dyn Future<Item = _, Error = _>>
+        });
+        assert_eq!(started.get(), 2);
+        assert_eq!(third.wait().unwrap(), 43);
+    }
+
+    #[test]
+    fn dropping_all_waiters_drops_the_request() {
+        let coalescer = Coalescer::new();
+        let (tx, rx) = oneshot::channel::<u64>();
+        let first = coalescer.attach(|| response(rx));
+        let second = coalescer.attach(|| unreachable!());
+        drop(first);
+        assert!(!tx.is_canceled());
+        drop(second);
+        assert!(tx.is_canceled());
+    }
+
+    #[test]
+    fn waiters_get_copies_of_the_error_with_the_retry_hint() {
+        let coalescer = Coalescer::new();
+        let first = coalescer.attach(|| {
+            let err =
+                core_client::Error::new(core_client::ErrorKind::ResourceExhausted, "slow down")
+                    .with_retry_after(Duration::from_secs(2));
+            Box::new(future::err(err)) as Box<dyn Future<Item = u64, Error = _>>
+        });
+        let second = coalescer.attach(|| unreachable!());
+        for waiter in vec![first, second] {
+            let e = waiter.wait().unwrap_err();
+            match e.kind() {
+                core_client::ErrorKind::ResourceExhausted => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            }
+            assert_eq!(error::Error::source(&e).unwrap().to_string(), "slow down");
+            assert_eq!(e.retry_after(), Some(Duration::from_secs(2)));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use futures::{Async, Poll};
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+// Liveness of the HTTP/2 connection as last observed by the requests
+// made over it. The status is shared by all clones of a client.
+#[derive(Clone, Debug)]
+pub(super) struct ConnectionStatus {
+    open: Arc<AtomicBool>,
+}
+
+impl ConnectionStatus {
+    pub fn new() -> Self {
+        ConnectionStatus {
+            open: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Relaxed)
+    }
+
+    // Records the outcome of polling a request or a response stream.
+    // A transport or HTTP/2 error is taken as a sign that the connection
+    // has been closed; a gRPC status or a protocol error received from
+    // the peer proves the opposite.
+    pub fn observe<T, E>(&self, result: &Poll<T, tower_grpc::Error<E>>) {
+        let open = match result {
+            Ok(Async::NotReady) => return,
+            Err(tower_grpc::Error::Inner(_)) => false,
+            _ => true,
+        };
+        self.open.store(open, Ordering::Relaxed);
+    }
+}
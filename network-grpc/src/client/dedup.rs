@@ -0,0 +1,200 @@
+use chain_core::property::Block;
+use network_core::client as core_client;
+
+use futures::{prelude::*, try_ready};
+
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+
+/// Stream adapter that filters out blocks already yielded by the stream.
+///
+/// The identifiers of the yielded blocks are remembered in a cache of
+/// bounded capacity, evicting the least recently seen identifier when
+/// the cache is full. A duplicate of an evicted block is yielded again,
+/// so the capacity should cover the expected overlap between the sources
+/// of the stream. Blocks are yielded in the order they are first seen.
+///
+/// An adapter for a `ResponseStream` is created with its `dedup_by_id`
+/// method; any stream of blocks can be wrapped with `DedupById::new`.
+pub struct DedupById<S>
+where
+    S: Stream,
+    S::Item: Block,
+{
+    inner: S,
+    seen: SeenIds<<S::Item as Block>::Id>,
+}
+
+impl<S> DedupById<S>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Block,
+{
+    /// Wraps the stream, remembering up to `capacity` block identifiers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(stream: S, capacity: usize) -> Self {
+        assert!(capacity > 0, "the capacity should not be 0");
+        DedupById {
+            inner: stream,
+            seen: SeenIds::new(capacity),
+        }
+    }
+
+    /// Consumes the adapter, returning the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> Stream for DedupById<S>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Block,
+{
+    type Item = S::Item;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, core_client::Error> {
+        loop {
+            match try_ready!(self.inner.poll()) {
+                None => return Ok(Async::Ready(None)),
+                Some(block) => {
+                    if self.seen.insert(block.id()) {
+                        return Ok(Async::Ready(Some(block)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Cache of recently seen identifiers with least-recently-seen eviction.
+//
+// Each identifier maps to the sequence number of its latest sighting.
+// The queue holds sightings in order; entries superseded by a later
+// sighting of the same identifier are stale and skipped on eviction.
+// The queue is compacted when stale entries make up half of it,
+// so its size stays within twice the capacity.
+struct SeenIds<I> {
+    capacity: usize,
+    latest: HashMap<I, u64>,
+    queue: VecDeque<(I, u64)>,
+    seq: u64,
+}
+
+impl<I> SeenIds<I>
+where
+    I: Clone + Eq + std::hash::Hash,
+{
+    fn new(capacity: usize) -> Self {
+        SeenIds {
+            capacity,
+            latest: HashMap::new(),
+            queue: VecDeque::new(),
+            seq: 0,
+        }
+    }
+
+    // Records a sighting of the identifier, returning true if it was
+    // not in the cache.
+    fn insert(&mut self, id: I) -> bool {
+        self.seq += 1;
+        let seq = self.seq;
+        let is_new = match self.latest.entry(id.clone()) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(seq);
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(seq);
+                true
+            }
+        };
+        self.queue.push_back((id, seq));
+        if is_new && self.latest.len() > self.capacity {
+            self.evict_one();
+        }
+        if self.queue.len() > 2 * self.capacity {
+            self.compact();
+        }
+        is_new
+    }
+
+    fn evict_one(&mut self) {
+        while let Some((id, seq)) = self.queue.pop_front() {
+            if self.latest.get(&id) == Some(&seq) {
+                self.latest.remove(&id);
+                return;
+            }
+        }
+    }
+
+    fn compact(&mut self) {
+        let latest = &self.latest;
+        self.queue.retain(|(id, seq)| latest.get(id) == Some(seq));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DedupById, SeenIds};
+    use crate::test_types::TestBlock;
+
+    use network_core::client as core_client;
+
+    use futures::{prelude::*, stream};
+
+    fn insert_all(seen: &mut SeenIds<u64>, ids: &[u64]) -> Vec<bool> {
+        ids.iter().map(|&id| seen.insert(id)).collect()
+    }
+
+    #[test]
+    fn least_recently_seen_id_is_evicted_first() {
+        let mut seen = SeenIds::new(3);
+        assert_eq!(insert_all(&mut seen, &[1, 2, 3]), [true, true, true]);
+        // 1 is evicted to make room for 4, then 2 to make room for 1.
+        assert_eq!(insert_all(&mut seen, &[4, 1]), [true, true]);
+        assert_eq!(insert_all(&mut seen, &[3, 4, 1]), [false, false, false]);
+        assert_eq!(insert_all(&mut seen, &[2]), [true]);
+    }
+
+    #[test]
+    fn sighting_an_id_again_makes_it_most_recent() {
+        let mut seen = SeenIds::new(2);
+        assert_eq!(insert_all(&mut seen, &[1, 2, 1]), [true, true, false]);
+        // 2 is now the least recently seen and is evicted for 3.
+        assert_eq!(insert_all(&mut seen, &[3, 1]), [true, false]);
+        assert_eq!(insert_all(&mut seen, &[2]), [true]);
+    }
+
+    #[test]
+    fn queue_stays_within_twice_the_capacity() {
+        let capacity = 4;
+        let mut seen = SeenIds::new(capacity);
+        for i in 0..1000 {
+            // Mostly repeated sightings, which leave stale entries
+            // in the queue, with a new identifier now and then.
+            let id = if i % 10 == 0 { i } else { i % 3 };
+            seen.insert(id);
+            assert!(seen.latest.len() <= capacity);
+            assert!(seen.queue.len() <= 2 * capacity);
+        }
+    }
+
+    #[test]
+    fn duplicate_blocks_are_filtered_out() {
+        let blocks = vec![1, 2, 1, 3, 2, 4, 1]
+            .into_iter()
+            .map(TestBlock)
+            .collect::<Vec<_>>();
+        let stream = stream::iter_ok::<_, core_client::Error>(blocks);
+        let heights: Vec<_> = DedupById::new(stream, 8)
+            .map(|block| block.0)
+            .collect()
+            .wait()
+            .unwrap();
+        assert_eq!(heights, vec![1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,261 @@
+use super::{Client, ClientBuilder, Error};
+use crate::peer::TcpPeer;
+
+use futures::{
+    future::{self, Executor},
+    sync::oneshot,
+};
+use tokio::io;
+use tokio::net::TcpStream;
+use tokio::prelude::*;
+use tokio::timer::Delay;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::{
+    collections::VecDeque,
+    mem,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    thread,
+    time::{Duration, Instant},
+};
+
+// The delay between starting connection attempts to successive
+// addresses, as recommended in RFC 8305.
+pub(super) const DEFAULT_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+impl<E> Client<TcpStream, E>
+where
+    E: Executor<Background<TcpStream, BoxBody>> + Clone + Send + 'static,
+{
+    /// Resolves the host name and connects to the first of its
+    /// addresses that accepts a connection.
+    ///
+    /// See `ClientBuilder::build_connect_host` for details.
+    pub fn connect_host(
+        host: &str,
+        port: u16,
+        executor: E,
+    ) -> impl Future<Item = Self, Error = Error> {
+        ClientBuilder::new().build_connect_host(host, port, executor)
+    }
+}
+
+impl ClientBuilder {
+    /// Resolves the host name and connects to the first of its
+    /// addresses that accepts a connection, with the settings of this
+    /// builder.
+    ///
+    /// The name is resolved with the system resolver on a separate
+    /// thread, so the resolution does not block the executor. The
+    /// connection attempts are made in the manner of the Happy Eyeballs
+    /// algorithm: the addresses are ordered alternating between IPv6
+    /// and IPv4, starting with the family of the first resolved address,
+    /// and an attempt is started every time the previous one fails or
+    /// has not completed within the connection attempt delay. The first
+    /// connection to be established is returned and the other attempts
+    /// are abandoned, so on a dual-stack host the client uses whichever
+    /// family connects first. An attempt is only considered complete
+    /// when the HTTP/2 connection is ready.
+    ///
+    /// If the name cannot be resolved, the future fails with
+    /// `Error::Resolve`. If all attempts fail, the future fails with
+    /// `Error::AllAddressesFailed`, listing the error for each address.
+    ///
+    /// The requests are made with an origin formed by the host name and
+    /// the port, rather than the address the client is connected to.
+    pub fn build_connect_host<E>(
+        &self,
+        host: &str,
+        port: u16,
+        executor: E,
+    ) -> impl Future<Item = Client<TcpStream, E>, Error = Error>
+    where
+        E: Executor<Background<TcpStream, BoxBody>> + Clone + Send + 'static,
+    {
+        let origin = match host_origin(host, port) {
+            Ok(origin) => origin,
+            Err(e) => return future::Either::A(future::err(e)),
+        };
+        let builder = self.clone();
+        let delay = self.attempt_delay;
+        let connect = resolve(host, port).and_then(move |addrs| ConnectHost {
+            builder,
+            executor,
+            origin,
+            delay,
+            addrs: interleave_families(addrs),
+            attempts: Vec::new(),
+            errors: Vec::new(),
+            next_attempt: None,
+        });
+        future::Either::B(connect)
+    }
+
+    /// Sets the delay after which a connection attempt to the next
+    /// address of a host is started while the previous attempt is
+    /// still in progress. The default is 250 milliseconds.
+    ///
+    /// See `build_connect_host` for details.
+    pub fn connection_attempt_delay(&mut self, delay: Duration) -> &mut Self {
+        self.attempt_delay = delay;
+        self
+    }
+}
+
+pub(super) fn host_origin(host: &str, port: u16) -> Result<http::Uri, Error> {
+    // An IPv6 address needs brackets in the authority.
+    let authority = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(addr)) => format!("[{}]:{}", addr, port),
+        _ => format!("{}:{}", host, port),
+    };
+    format!("http://{}", authority).parse().map_err(|_| {
+        let msg = format!("invalid host name {:?}", host);
+        Error::Resolve(io::Error::new(io::ErrorKind::InvalidInput, msg))
+    })
+}
+
+fn resolve(host: &str, port: u16) -> impl Future<Item = Vec<SocketAddr>, Error = Error> {
+    let (tx, rx) = oneshot::channel();
+    let host = host.to_owned();
+    let spawned = thread::Builder::new()
+        .name("resolve".into())
+        .spawn(move || {
+            let res = (host.as_str(), port)
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            let _ = tx.send(res);
+        });
+    let rx = match spawned {
+        Ok(_) => rx,
+        Err(e) => return future::Either::A(future::err(Error::Resolve(e))),
+    };
+    let resolved = rx
+        .map_err(|_| {
+            let msg = "the resolver thread terminated";
+            Error::Resolve(io::Error::new(io::ErrorKind::Other, msg))
+        })
+        .and_then(|res| match res {
+            Ok(ref addrs) if addrs.is_empty() => {
+                let msg = "the host name resolved to no addresses";
+                Err(Error::Resolve(io::Error::new(io::ErrorKind::NotFound, msg)))
+            }
+            Ok(addrs) => Ok(addrs),
+            Err(e) => Err(Error::Resolve(e)),
+        });
+    future::Either::B(resolved)
+}
+
+// Orders the addresses alternating between the address families,
+// starting with the family of the first address and otherwise keeping
+// the order of the resolver.
+fn interleave_families(addrs: Vec<SocketAddr>) -> VecDeque<SocketAddr> {
+    let first_is_v6 = addrs.first().map_or(false, SocketAddr::is_ipv6);
+    let (mut first, mut second): (VecDeque<_>, VecDeque<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_v6);
+    let mut ordered = VecDeque::with_capacity(first.len() + second.len());
+    loop {
+        match (first.pop_front(), second.pop_front()) {
+            (None, None) => return ordered,
+            (a, b) => {
+                ordered.extend(a);
+                ordered.extend(b);
+            }
+        }
+    }
+}
+
+type Attempt<E> = Box<dyn Future<Item = Client<TcpStream, E>, Error = Error> + Send>;
+
+// Connection attempts to the resolved addresses of a host.
+struct ConnectHost<E> {
+    builder: ClientBuilder,
+    executor: E,
+    origin: http::Uri,
+    delay: Duration,
+    // The addresses yet to be attempted.
+    addrs: VecDeque<SocketAddr>,
+    attempts: Vec<(SocketAddr, Attempt<E>)>,
+    errors: Vec<(SocketAddr, Error)>,
+    // When the attempt to the next address is due while others are
+    // in progress.
+    next_attempt: Option<Delay>,
+}
+
+impl<E> ConnectHost<E>
+where
+    E: Executor<Background<TcpStream, BoxBody>> + Clone + Send + 'static,
+{
+    fn start_attempt(&mut self) {
+        if let Some(addr) = self.addrs.pop_front() {
+            let mut peer = TcpPeer::new(addr);
+            peer.set_keepalive(self.builder.keepalive());
+            let connect = self.builder.build_connect_with_origin(
+                peer,
+                self.origin.clone(),
+                self.executor.clone(),
+            );
+            self.attempts.push((addr, Box::new(connect)));
+            self.next_attempt = if self.addrs.is_empty() {
+                None
+            } else {
+                Some(Delay::new(Instant::now() + self.delay))
+            };
+        }
+    }
+
+    fn attempt_due(&mut self) -> bool {
+        match self.next_attempt {
+            None => false,
+            // A timer error only makes the attempt start early.
+            Some(ref mut delay) => match delay.poll() {
+                Ok(Async::NotReady) => false,
+                Ok(Async::Ready(())) | Err(_) => true,
+            },
+        }
+    }
+}
+
+impl<E> Future for ConnectHost<E>
+where
+    E: Executor<Background<TcpStream, BoxBody>> + Clone + Send + 'static,
+{
+    type Item = Client<TcpStream, E>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Client<TcpStream, E>, Error> {
+        loop {
+            let due = self.attempts.is_empty() || self.attempt_due();
+            if due && !self.addrs.is_empty() {
+                // Loop to poll the new attempt and the timer for the next.
+                self.start_attempt();
+                continue;
+            }
+            let mut failed = false;
+            let mut i = 0;
+            while i < self.attempts.len() {
+                match self.attempts[i].1.poll() {
+                    Ok(Async::NotReady) => i += 1,
+                    // Dropping the other attempts closes their connections.
+                    Ok(Async::Ready(client)) => return Ok(Async::Ready(client)),
+                    Err(e) => {
+                        let (addr, _) = self.attempts.remove(i);
+                        self.errors.push((addr, e));
+                        failed = true;
+                    }
+                }
+            }
+            if self.attempts.is_empty() && self.addrs.is_empty() {
+                let errors = mem::replace(&mut self.errors, Vec::new());
+                return Err(Error::AllAddressesFailed(errors));
+            }
+            if failed && !self.addrs.is_empty() {
+                // A failed attempt is followed by the next one right away.
+                self.start_attempt();
+                continue;
+            }
+            return Ok(Async::NotReady);
+        }
+    }
+}
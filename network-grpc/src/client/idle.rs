@@ -0,0 +1,121 @@
+use network_core::client as core_client;
+
+use futures::prelude::*;
+use tokio::timer::Delay;
+
+use std::time::{Duration, Instant};
+
+/// Stream adapter that fails the stream when no item arrives within
+/// a timeout since the previous item.
+///
+/// The timer is reset every time the underlying stream yields an item
+/// or ends. If the timer elapses first, the adapter fails with an error
+/// of kind `ErrorKind::Timeout`. This detects peers that keep a stream
+/// open but have stopped sending data, which an overall deadline for
+/// a long download cannot catch without being very generous. Dropping
+/// the adapter after the error drops the underlying stream, which for
+/// a `ResponseStream` resets the HTTP/2 stream.
+///
+/// An adapter for a `ResponseStream` is created with its `idle_timeout`
+/// method; any stream can be wrapped with `IdleTimeout::new`.
+pub struct IdleTimeout<S> {
+    inner: S,
+    timeout: Duration,
+    delay: Delay,
+}
+
+impl<S> IdleTimeout<S>
+where
+    S: Stream<Error = core_client::Error>,
+{
+    /// Wraps the stream, starting the timer for the first item.
+    pub fn new(stream: S, timeout: Duration) -> Self {
+        IdleTimeout {
+            inner: stream,
+            timeout,
+            delay: Delay::new(Instant::now() + timeout),
+        }
+    }
+
+    /// Consumes the adapter, returning the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> Stream for IdleTimeout<S>
+where
+    S: Stream<Error = core_client::Error>,
+{
+    type Item = S::Item;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, core_client::Error> {
+        match self.inner.poll()? {
+            Async::Ready(item) => {
+                self.delay.reset(Instant::now() + self.timeout);
+                Ok(Async::Ready(item))
+            }
+            Async::NotReady => match self.delay.poll() {
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                Ok(Async::Ready(())) => {
+                    let msg = format!("no stream item received within {:?}", self.timeout);
+                    Err(core_client::Error::new(
+                        core_client::ErrorKind::Timeout,
+                        msg,
+                    ))
+                }
+                Err(e) => Err(core_client::Error::new(core_client::ErrorKind::Rpc, e)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream;
+    use tokio::runtime::current_thread::Runtime;
+
+    // A stub stream yielding one item, then another one after a pause.
+    fn paused_stream(pause: Duration) -> impl Stream<Item = u32, Error = core_client::Error> {
+        let second = Delay::new(Instant::now() + pause)
+            .map(|()| 2)
+            .map_err(|e| core_client::Error::new(core_client::ErrorKind::Rpc, e))
+            .into_stream();
+        stream::once(Ok(1)).chain(second)
+    }
+
+    #[test]
+    fn fails_when_the_pause_exceeds_the_timeout() {
+        let mut rt = Runtime::new().unwrap();
+        let stream = IdleTimeout::new(
+            paused_stream(Duration::from_millis(500)),
+            Duration::from_millis(20),
+        );
+        let (item, stream) = rt
+            .block_on(stream.into_future())
+            .map_err(|(e, _)| e)
+            .unwrap();
+        assert_eq!(item, Some(1));
+        match rt.block_on(stream.into_future()) {
+            Err((ref e, _)) => match e.kind() {
+                core_client::ErrorKind::Timeout => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            Ok((item, _)) => panic!("unexpected item {:?}", item),
+        }
+    }
+
+    #[test]
+    fn yields_all_items_arriving_within_the_timeout() {
+        let mut rt = Runtime::new().unwrap();
+        let stream = IdleTimeout::new(
+            paused_stream(Duration::from_millis(10)),
+            Duration::from_millis(500),
+        );
+        let items = rt.block_on(stream.collect()).unwrap();
+        assert_eq!(items, vec![1, 2]);
+    }
+}
@@ -0,0 +1,48 @@
+use tower_grpc::{Request, Status};
+
+/// Hook invoked on every request made by a client before it is sent.
+///
+/// An implementation can be installed with `ClientBuilder::interceptor`
+/// or `Client::with_interceptor`. It is given the head of the request,
+/// with an empty body standing in for the message, so it can add
+/// metadata headers, such as tracing identifiers or a `grpc-timeout`
+/// deadline, in one place for all methods. The path of the request is
+/// only set after the hook returns.
+///
+/// Returning an error status rejects the request: it is not sent to
+/// the peer, and the response future or stream fails as if the peer had
+/// responded with the status. This can be used e.g. to rate limit
+/// requests by failing with `Code::ResourceExhausted`. Requests retried
+/// under a retry policy are intercepted again on every attempt.
+///
+/// The trait is implemented for closures, so an interceptor can be
+/// installed with a closure like
+/// `|req: &mut http::Request<()>| { req.headers_mut().insert(...); Ok(()) }`.
+pub trait Interceptor: Send + Sync {
+    /// Inspects or modifies the request before it is sent.
+    fn intercept(&self, request: &mut http::Request<()>) -> Result<(), Status>;
+}
+
+impl<F> Interceptor for F
+where
+    F: Fn(&mut http::Request<()>) -> Result<(), Status> + Send + Sync,
+{
+    fn intercept(&self, request: &mut http::Request<()>) -> Result<(), Status> {
+        self(request)
+    }
+}
+
+// Applies the interceptor to the head of the request, leaving
+// the message as is.
+pub(super) fn apply<M>(
+    interceptor: &dyn Interceptor,
+    req: Request<M>,
+) -> Result<Request<M>, Status> {
+    let (parts, message) = req.into_http().into_parts();
+    let mut head = http::Request::from_parts(parts, ());
+    interceptor.intercept(&mut head)?;
+    let (parts, ()) = head.into_parts();
+    Ok(Request::from_http(http::Request::from_parts(
+        parts, message,
+    )))
+}
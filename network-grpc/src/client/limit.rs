@@ -0,0 +1,94 @@
+use futures::{
+    task::{self, Task},
+    Async,
+};
+
+use std::sync::{Arc, Mutex};
+
+/// The behavior of requests with streamed responses made when
+/// the limit set with `Client::with_stream_limit` has been reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamLimitBehavior {
+    /// The request waits until another response stream is finished,
+    /// and is sent after that. This is the default.
+    Wait,
+    /// The request fails with an error of kind
+    /// `ErrorKind::ResourceExhausted` without being sent.
+    Fail,
+}
+
+impl Default for StreamLimitBehavior {
+    fn default() -> Self {
+        StreamLimitBehavior::Wait
+    }
+}
+
+// Counting semaphore for the response streams of a client and its clones.
+#[derive(Clone)]
+pub(super) struct StreamLimit {
+    shared: Arc<Mutex<Shared>>,
+}
+
+struct Shared {
+    available: usize,
+    // Tasks waiting for a permit. All of them are woken up when a permit
+    // is released, because some may have given up waiting in the meantime.
+    waiters: Vec<Task>,
+}
+
+impl StreamLimit {
+    pub fn new(limit: usize) -> Self {
+        StreamLimit {
+            shared: Arc::new(Mutex::new(Shared {
+                available: limit,
+                waiters: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn try_acquire(&self) -> Option<StreamPermit> {
+        let mut shared = self.shared.lock().unwrap();
+        self.take_permit(&mut shared)
+    }
+
+    // Acquires a permit, or registers the current task to be notified
+    // when a permit is released.
+    pub fn poll_acquire(&self) -> Async<StreamPermit> {
+        let mut shared = self.shared.lock().unwrap();
+        match self.take_permit(&mut shared) {
+            Some(permit) => Async::Ready(permit),
+            None => {
+                if !shared.waiters.iter().any(Task::will_notify_current) {
+                    shared.waiters.push(task::current());
+                }
+                Async::NotReady
+            }
+        }
+    }
+
+    fn take_permit(&self, shared: &mut Shared) -> Option<StreamPermit> {
+        if shared.available == 0 {
+            return None;
+        }
+        shared.available -= 1;
+        Some(StreamPermit {
+            shared: self.shared.clone(),
+        })
+    }
+}
+
+// A stream slot taken from the limit; it is returned when dropped.
+pub(super) struct StreamPermit {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.available += 1;
+            for task in shared.waiters.drain(..) {
+                task.notify();
+            }
+        }
+    }
+}
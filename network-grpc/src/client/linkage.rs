@@ -0,0 +1,76 @@
+use chain_core::property::Block;
+use network_core::client as core_client;
+
+use futures::{prelude::*, try_ready};
+
+/// Stream adapter that checks that the blocks of the stream form
+/// a chain.
+///
+/// Every block after the first must have the identifier of the block
+/// preceding it in the stream as its parent. At the first block that
+/// does not, the adapter fails with an error of kind `ErrorKind::Format`
+/// that gives both identifiers. Only the identifier of the last block
+/// is kept between polls, so the check does not buffer the stream.
+/// The parent of the first block is not checked, so a segment can be
+/// pulled from any starting point.
+///
+/// An adapter for a `ResponseStream` is created with its
+/// `check_chain_linkage` method; any stream of blocks can be wrapped
+/// with `CheckChainLinkage::new`.
+pub struct CheckChainLinkage<S>
+where
+    S: Stream,
+    S::Item: Block,
+{
+    inner: S,
+    last_id: Option<<S::Item as Block>::Id>,
+}
+
+impl<S> CheckChainLinkage<S>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Block,
+{
+    /// Wraps the stream.
+    pub fn new(stream: S) -> Self {
+        CheckChainLinkage {
+            inner: stream,
+            last_id: None,
+        }
+    }
+
+    /// Consumes the adapter, returning the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> Stream for CheckChainLinkage<S>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Block,
+{
+    type Item = S::Item;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, core_client::Error> {
+        let block = match try_ready!(self.inner.poll()) {
+            None => return Ok(Async::Ready(None)),
+            Some(block) => block,
+        };
+        if let Some(ref last_id) = self.last_id {
+            let parent_id = block.parent_id();
+            if parent_id != *last_id {
+                let msg = format!(
+                    "block {:?} has parent {:?}, not the preceding block {:?}",
+                    block.id(),
+                    parent_id,
+                    last_id
+                );
+                return Err(core_client::Error::new(core_client::ErrorKind::Format, msg));
+            }
+        }
+        self.last_id = Some(block.id());
+        Ok(Async::Ready(Some(block)))
+    }
+}
@@ -0,0 +1,120 @@
+use super::DedupById;
+
+use chain_core::property::Block;
+use network_core::client as core_client;
+
+use futures::prelude::*;
+
+/// Stream combinator that merges block streams from multiple sources.
+///
+/// The blocks are yielded as they arrive from any of the source streams,
+/// typically `ResponseStream`s of the same request made to different
+/// peers. A block with an identifier already seen in the merged stream
+/// is filtered out, so the first copy of each block to arrive wins.
+/// As with `DedupById`, identifiers are remembered in a cache of bounded
+/// capacity, which should cover the expected difference in progress
+/// between the sources.
+///
+/// A source stream that fails is dropped, and the merged stream goes on
+/// with the remaining sources. The merged stream ends only when every
+/// source has ended or failed. If all of the sources have failed,
+/// the merged stream yields the error of the last one to fail before
+/// ending; if any source has ended normally, the merged stream ends
+/// without an error.
+pub struct MergedBlockStream<S>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Block,
+{
+    inner: DedupById<Merge<S>>,
+}
+
+impl<S> MergedBlockStream<S>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Block,
+{
+    /// Merges the streams, remembering up to `capacity` block identifiers
+    /// for deduplication.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new<I>(streams: I, capacity: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let merge = Merge {
+            streams: streams.into_iter().collect(),
+            next: 0,
+            any_ended: false,
+            last_error: None,
+        };
+        MergedBlockStream {
+            inner: DedupById::new(merge, capacity),
+        }
+    }
+}
+
+impl<S> Stream for MergedBlockStream<S>
+where
+    S: Stream<Error = core_client::Error>,
+    S::Item: Block,
+{
+    type Item = S::Item;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, core_client::Error> {
+        self.inner.poll()
+    }
+}
+
+// Polls the streams in turn, starting after the one that yielded
+// the previous item, so that a busy stream does not starve the others.
+struct Merge<S: Stream> {
+    streams: Vec<S>,
+    next: usize,
+    any_ended: bool,
+    last_error: Option<S::Error>,
+}
+
+impl<S> Stream for Merge<S>
+where
+    S: Stream<Error = core_client::Error>,
+{
+    type Item = S::Item;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, core_client::Error> {
+        let mut index = self.next;
+        // Every stream is polled once, unless one yields an item.
+        for _ in 0..self.streams.len() {
+            if index >= self.streams.len() {
+                index = 0;
+            }
+            match self.streams[index].poll() {
+                Ok(Async::NotReady) => index += 1,
+                Ok(Async::Ready(Some(item))) => {
+                    self.next = index + 1;
+                    return Ok(Async::Ready(Some(item)));
+                }
+                Ok(Async::Ready(None)) => {
+                    self.streams.remove(index);
+                    self.any_ended = true;
+                }
+                Err(e) => {
+                    self.streams.remove(index);
+                    self.last_error = Some(e);
+                }
+            }
+        }
+        self.next = index;
+        if !self.streams.is_empty() {
+            return Ok(Async::NotReady);
+        }
+        match self.last_error.take() {
+            Some(e) if !self.any_ended => Err(e),
+            _ => Ok(Async::Ready(None)),
+        }
+    }
+}
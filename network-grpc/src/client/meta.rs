@@ -0,0 +1,165 @@
+use super::{
+    serialize_to_bytes, serialize_to_vec, Client, ConvertResponse, NodeClient, ResponseFuture,
+    ResponseStream, ResponseStreamFuture,
+};
+use crate::gen;
+
+use chain_core::property::{Block, Deserialize, Serialize};
+use network_core::client as core_client;
+
+use bytes::Bytes;
+use futures::{
+    future::{self, Executor},
+    stream,
+};
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::time::Duration;
+
+/// Diagnostic information on how the peer has served a block.
+///
+/// This is received alongside the blocks by the methods of `Client`
+/// with the `_with_meta` suffix, such as `get_block_with_meta`,
+/// which yield pairs of a block and its `BlockMeta`.
+/// The fields are `None` if the peer does not supply the information.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockMeta {
+    /// Whether the peer has served the block from an in-memory cache
+    /// rather than read it from storage.
+    pub cache_hit: Option<bool>,
+    /// The time the peer has reported taking to retrieve the block.
+    pub serve_latency: Option<Duration>,
+}
+
+impl From<Option<gen::node::BlockServeInfo>> for BlockMeta {
+    fn from(info: Option<gen::node::BlockServeInfo>) -> Self {
+        match info {
+            None => BlockMeta::default(),
+            Some(info) => BlockMeta {
+                cache_hit: Some(info.cache_hit),
+                serve_latency: Some(Duration::from_micros(info.latency_micros)),
+            },
+        }
+    }
+}
+
+/// Future returned by `Client::get_block_with_meta`.
+pub type BlockWithMetaFuture<T> =
+    future::Map<ResponseFuture<WithMeta<T>, gen::node::Block>, fn(WithMeta<T>) -> (T, BlockMeta)>;
+
+/// Stream returned by `Client::pull_blocks_to_tip_with_meta`.
+pub type BlockWithMetaStream<T> =
+    stream::Map<ResponseStream<WithMeta<T>, gen::node::Block>, fn(WithMeta<T>) -> (T, BlockMeta)>;
+
+/// Future that resolves to a `BlockWithMetaStream`.
+pub type BlockWithMetaStreamFuture<T> = future::Map<
+    ResponseStreamFuture<WithMeta<T>, gen::node::Block>,
+    fn(ResponseStream<WithMeta<T>, gen::node::Block>) -> BlockWithMetaStream<T>,
+>;
+
+// A block decoded along with the diagnostic information on it.
+// A tuple cannot be the target of a conversion of block messages,
+// because it would conflict with the conversion into any `Block`.
+pub struct WithMeta<T>(T, BlockMeta);
+
+impl<T> WithMeta<T> {
+    fn into_tuple(self) -> (T, BlockMeta) {
+        (self.0, self.1)
+    }
+}
+
+fn into_tuples<T>(stream: ResponseStream<WithMeta<T>, gen::node::Block>) -> BlockWithMetaStream<T> {
+    stream.map(WithMeta::into_tuple as fn(_) -> _)
+}
+
+impl<T> ConvertResponse<WithMeta<T>> for gen::node::Block
+where
+    T: Block,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+{
+    fn convert_response(mut self) -> Result<WithMeta<T>, core_client::Error> {
+        let meta = BlockMeta::from(self.serve_info.take());
+        let block = ConvertResponse::<T>::convert_response(self)?;
+        Ok(WithMeta(block, meta))
+    }
+
+    fn verify_checksum(&self) -> Result<(), core_client::Error> {
+        ConvertResponse::<T>::verify_checksum(self)
+    }
+
+    fn take_item_id(&mut self) -> Option<Vec<u8>> {
+        ConvertResponse::<T>::take_item_id(self)
+    }
+
+    fn take_continuation_token(&mut self) -> Option<Bytes> {
+        ConvertResponse::<T>::take_continuation_token(self)
+    }
+}
+
+impl<S, E> Client<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Retrieves the block identified by `id`, along with the information
+    /// the peer supplies on how it has served the block.
+    ///
+    /// This is the same request as `BlockService::get_block`.
+    pub fn get_block_with_meta<T>(&mut self, id: T::Id) -> BlockWithMetaFuture<T>
+    where
+        T: Block,
+        <T as Deserialize>::Error: Send + Sync + 'static,
+        <T::Id as Serialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let future = match serialize_to_bytes(&id) {
+            Ok(id) => {
+                let expected = id.clone();
+                let req = gen::node::GetBlockRequest { id };
+                self.idempotent_call("GetBlock", req, |node, req| node.get_block(req))
+                    .with_check(Box::new(move |WithMeta(block, _): &WithMeta<T>| {
+                        if serialize_to_bytes(&block.id())? == expected {
+                            Ok(())
+                        } else {
+                            let msg = format!(
+                                "received block {:?} instead of the requested one",
+                                block.id()
+                            );
+                            Err(core_client::Error::new(core_client::ErrorKind::Format, msg))
+                        }
+                    }))
+            }
+            Err(e) => ResponseFuture::error(e),
+        };
+        future.map(WithMeta::into_tuple as fn(_) -> _)
+    }
+
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the peer up to the tip of its chain, each along with
+    /// the information the peer supplies on how it has served the block.
+    ///
+    /// This is the same request as `BlockService::pull_blocks_to_tip`.
+    pub fn pull_blocks_to_tip_with_meta<T>(
+        &mut self,
+        from: &[T::Id],
+    ) -> BlockWithMetaStreamFuture<T>
+    where
+        T: Block,
+        <T as Deserialize>::Error: Send + Sync + 'static,
+        <T::Id as Serialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let future = match serialize_to_vec(from) {
+            Ok(from) => {
+                let req = gen::node::PullBlocksToTipRequest { from, known: None };
+                self.streaming_call("PullBlocksToTip", req, |node, req| {
+                    node.pull_blocks_to_tip(req)
+                })
+            }
+            Err(e) => ResponseStreamFuture::error(e),
+        };
+        future.map(into_tuples as fn(_) -> _)
+    }
+}
@@ -0,0 +1,133 @@
+use super::Error;
+
+use network_core::client as core_client;
+use tower_h2::client::ConnectError;
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Receiver of instrumentation events from gRPC clients.
+///
+/// An implementation can be installed with `ClientBuilder::metrics`
+/// to forward the events to a metrics collection system.
+/// The methods are called from the tasks polling the connection and
+/// request futures, so they should not block.
+pub trait Metrics: Send + Sync {
+    /// Called when an attempt to connect to a peer has completed,
+    /// successfully or not.
+    fn connect_completed(&self, latency: Duration, result: Result<(), ConnectErrorKind>);
+
+    /// Called when a request has completed, successfully or not.
+    ///
+    /// `method` is the name of the gRPC method, e.g. `"Tip"`.
+    /// For methods with streamed responses, the latency is measured
+    /// until the response stream is established.
+    fn request_completed(
+        &self,
+        method: &'static str,
+        latency: Duration,
+        result: Result<(), core_client::ErrorKind>,
+    );
+}
+
+/// The stage of connection establishment at which an error occurred.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConnectErrorKind {
+    /// Failed to establish the transport connection, e.g. over TCP.
+    Transport,
+    /// Failed to perform the HTTP/2 handshake,
+    /// including the exchange of settings.
+    Handshake,
+    /// The origin URI could not be applied to requests.
+    InvalidOrigin,
+    /// Failed to establish the tunnel through an HTTP proxy.
+    Proxy,
+}
+
+impl ConnectErrorKind {
+    /// Returns a short name of the error kind, suitable for
+    /// use as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectErrorKind::Transport => "transport",
+            ConnectErrorKind::Handshake => "handshake",
+            ConnectErrorKind::InvalidOrigin => "invalid_origin",
+            ConnectErrorKind::Proxy => "proxy",
+        }
+    }
+
+    fn of(err: &Error) -> Self {
+        match err {
+            Error::Connect(ConnectError::Handshake(_)) => ConnectErrorKind::Handshake,
+            Error::InvalidOrigin(_) => ConnectErrorKind::InvalidOrigin,
+            Error::Proxy(_) | Error::Protocol(_) => ConnectErrorKind::Proxy,
+            _ => ConnectErrorKind::Transport,
+        }
+    }
+}
+
+impl fmt::Display for ConnectErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Dispatches events to the optionally installed metrics implementation.
+#[derive(Clone, Default)]
+pub(super) struct Recorder {
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+impl Recorder {
+    pub fn new(metrics: Arc<dyn Metrics>) -> Self {
+        Recorder {
+            metrics: Some(metrics),
+        }
+    }
+
+    pub fn connect_completed<T>(&self, started: Instant, result: &Result<T, Error>) {
+        if let Some(ref metrics) = self.metrics {
+            let result = match result {
+                Ok(_) => Ok(()),
+                Err(e) => Err(ConnectErrorKind::of(e)),
+            };
+            metrics.connect_completed(started.elapsed(), result);
+        }
+    }
+
+    pub fn start_request(&self, method: &'static str) -> Option<RequestTimer> {
+        self.metrics.as_ref().map(|metrics| RequestTimer {
+            metrics: metrics.clone(),
+            method,
+            started: Instant::now(),
+        })
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("enabled", &self.metrics.is_some())
+            .finish()
+    }
+}
+
+pub(super) struct RequestTimer {
+    metrics: Arc<dyn Metrics>,
+    method: &'static str,
+    started: Instant,
+}
+
+impl RequestTimer {
+    pub fn finish<T>(self, result: &Result<T, core_client::Error>) {
+        let result = match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.kind()),
+        };
+        self.metrics
+            .request_completed(self.method, self.started.elapsed(), result);
+    }
+}
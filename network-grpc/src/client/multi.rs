@@ -0,0 +1,427 @@
+use super::{Backoff, ClientBuilder, ReconnectingClient};
+use crate::peer::Origin;
+
+use chain_core::property::{Block, Deserialize, HasHeader, Serialize};
+use network_core::{
+    bloom::BloomFilter,
+    client::{
+        self as core_client,
+        block::{BlockService, HeaderService},
+    },
+};
+
+use futures::{future::Executor, try_ready};
+use tokio::io;
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::{
+    error,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+// One of the connections, with the number of streaming requests
+// in progress on it.
+struct Connection<P, S, E> {
+    client: ReconnectingClient<P, S, E>,
+    load: Arc<AtomicUsize>,
+}
+
+/// A gRPC client that stripes requests across several connections
+/// to the same peer.
+///
+/// With a single HTTP/2 connection, all responses share the flow control
+/// window and the congestion window of one TCP connection, which can
+/// limit the throughput of bulk block transfers from a fast peer.
+/// This client opens the configured number of connections to the peer
+/// and spreads the requests over them: unary requests are sent over
+/// the connections in turn, and a streaming request is sent over
+/// the connection with the fewest streams in progress.
+///
+/// Every connection is a `ReconnectingClient` of its own, established
+/// lazily and re-established independently of the others. Requests are
+/// steered away from a connection that has been observed to be closed
+/// for as long as another one is open, so one connection dying does not
+/// fail the requests made after it.
+pub struct MultiConnectClient<P, S, E> {
+    connections: Vec<Connection<P, S, E>>,
+    // The index of the connection to try first for the next request.
+    next: usize,
+}
+
+impl<P, S, E> MultiConnectClient<P, S, E>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+{
+    /// Creates a client making `connections` connections to the peer
+    /// with the default connection settings.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `connections` is 0.
+    pub fn new(peer: P, connections: usize, executor: E) -> Self {
+        Self::with_builder(peer, connections, ClientBuilder::new(), executor)
+    }
+
+    /// Creates a client making `connections` connections to the peer
+    /// with the settings of the builder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `connections` is 0.
+    pub fn with_builder(peer: P, connections: usize, builder: ClientBuilder, executor: E) -> Self {
+        assert!(connections > 0, "the number of connections should not be 0");
+        let connections = (0..connections)
+            .map(|_| Connection {
+                client: ReconnectingClient::with_builder(
+                    peer.clone(),
+                    builder.clone(),
+                    executor.clone(),
+                ),
+                load: Arc::new(AtomicUsize::new(0)),
+            })
+            .collect();
+        MultiConnectClient {
+            connections,
+            next: 0,
+        }
+    }
+
+    /// Sets the backoff settings for reconnection attempts
+    /// on all connections.
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        for conn in &mut self.connections {
+            conn.client.set_backoff(backoff.clone());
+        }
+    }
+
+    /// Returns the number of connections the requests are striped across.
+    pub fn connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    // Returns the indices of the connections in the order of preference
+    // for the next request, advancing the rotation.
+    fn rotation(&mut self) -> impl Iterator<Item = usize> {
+        let len = self.connections.len();
+        let start = self.next;
+        self.next = (start + 1) % len;
+        (0..len).map(move |i| (start + i) % len)
+    }
+
+    // Picks the next connection in turn that is not known to be closed,
+    // or the next connection if all are, so that it gets reconnected.
+    fn pick(&mut self) -> usize {
+        let order = self.rotation().collect::<Vec<_>>();
+        order
+            .iter()
+            .cloned()
+            .find(|&i| !self.connections[i].client.is_closed())
+            .unwrap_or(order[0])
+    }
+
+    // Picks the connection with the fewest streams in progress
+    // among those not known to be closed,
+    // preferring the earliest in turn among equally loaded ones.
+    fn pick_least_loaded(&mut self) -> usize {
+        let order = self.rotation().collect::<Vec<_>>();
+        let connections = &self.connections;
+        order
+            .iter()
+            .cloned()
+            .filter(|&i| !connections[i].client.is_closed())
+            .min_by_key(|&i| connections[i].load.load(Ordering::Relaxed))
+            .unwrap_or(order[0])
+    }
+
+    fn unary(&mut self) -> &mut ReconnectingClient<P, S, E> {
+        let i = self.pick();
+        &mut self.connections[i].client
+    }
+
+    fn streaming(&mut self) -> (&mut ReconnectingClient<P, S, E>, LoadGuard) {
+        let i = self.pick_least_loaded();
+        let conn = &mut self.connections[i];
+        (&mut conn.client, LoadGuard::new(&conn.load))
+    }
+}
+
+// Counts a streaming request in the load of its connection
+// for as long as it is alive.
+struct LoadGuard {
+    load: Arc<AtomicUsize>,
+}
+
+impl LoadGuard {
+    fn new(load: &Arc<AtomicUsize>) -> Self {
+        load.fetch_add(1, Ordering::Relaxed);
+        LoadGuard { load: load.clone() }
+    }
+}
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        self.load.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A future or stream for a streaming request made with
+/// `MultiConnectClient`, counted in the load of its connection until
+/// it is completed or dropped.
+pub struct LoadTracked<T> {
+    inner: T,
+    _guard: LoadGuard,
+}
+
+impl<T: Future> Future for LoadTracked<T> {
+    type Item = T::Item;
+    type Error = T::Error;
+
+    fn poll(&mut self) -> Poll<T::Item, T::Error> {
+        self.inner.poll()
+    }
+}
+
+impl<T: Stream> Stream for LoadTracked<T> {
+    type Item = T::Item;
+    type Error = T::Error;
+
+    fn poll(&mut self) -> Poll<Option<T::Item>, T::Error> {
+        self.inner.poll()
+    }
+}
+
+/// Future for a streaming request made with `MultiConnectClient`,
+/// resolving to a `LoadTracked` stream.
+pub struct LoadTrackedFuture<F> {
+    inner: F,
+    guard: Option<LoadGuard>,
+}
+
+impl<F> LoadTrackedFuture<F> {
+    fn new(inner: F, guard: LoadGuard) -> Self {
+        LoadTrackedFuture {
+            inner,
+            guard: Some(guard),
+        }
+    }
+}
+
+impl<F> Future for LoadTrackedFuture<F>
+where
+    F: Future,
+    F::Item: Stream,
+{
+    type Item = LoadTracked<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<LoadTracked<F::Item>, F::Error> {
+        let stream = try_ready!(self.inner.poll());
+        let guard = self
+            .guard
+            .take()
+            .expect("polled LoadTrackedFuture after completion");
+        Ok(Async::Ready(LoadTracked {
+            inner: stream,
+            _guard: guard,
+        }))
+    }
+}
+
+impl<T, P, S, E> BlockService<T> for MultiConnectClient<P, S, E>
+where
+    T: Block + 'static,
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    T::Id: Send,
+    T::Date: FromStr,
+    <T as Serialize>::Error: Send + Sync + 'static,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+    <T::Date as FromStr>::Err: error::Error + Send + Sync + 'static,
+{
+    type TipFuture = <ReconnectingClient<P, S, E> as BlockService<T>>::TipFuture;
+
+    type ChainLengthFuture = <ReconnectingClient<P, S, E> as BlockService<T>>::ChainLengthFuture;
+
+    type PullBlocksToTipStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as BlockService<T>>::PullBlocksToTipStream>;
+    type PullBlocksToTipFuture =
+        LoadTrackedFuture<<ReconnectingClient<P, S, E> as BlockService<T>>::PullBlocksToTipFuture>;
+
+    type PullBlocksStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as BlockService<T>>::PullBlocksStream>;
+    type PullBlocksFuture =
+        LoadTrackedFuture<<ReconnectingClient<P, S, E> as BlockService<T>>::PullBlocksFuture>;
+
+    type GetBlocksStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as BlockService<T>>::GetBlocksStream>;
+    type GetBlocksFuture =
+        LoadTrackedFuture<<ReconnectingClient<P, S, E> as BlockService<T>>::GetBlocksFuture>;
+
+    type GetBlockFuture = <ReconnectingClient<P, S, E> as BlockService<T>>::GetBlockFuture;
+
+    type BlockIdAtHeightFuture =
+        <ReconnectingClient<P, S, E> as BlockService<T>>::BlockIdAtHeightFuture;
+
+    type GetBlocksPipelinedStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as BlockService<T>>::GetBlocksPipelinedStream>;
+    type GetBlocksPipelinedFuture = LoadTrackedFuture<
+        <ReconnectingClient<P, S, E> as BlockService<T>>::GetBlocksPipelinedFuture,
+    >;
+
+    type UploadBlocksFuture =
+        LoadTracked<<ReconnectingClient<P, S, E> as BlockService<T>>::UploadBlocksFuture>;
+
+    type HasBlocksFuture = <ReconnectingClient<P, S, E> as BlockService<T>>::HasBlocksFuture;
+
+    type BlockSubscriptionStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as BlockService<T>>::BlockSubscriptionStream>;
+    type BlockSubscriptionFuture = LoadTrackedFuture<
+        <ReconnectingClient<P, S, E> as BlockService<T>>::BlockSubscriptionFuture,
+    >;
+
+    fn tip(&mut self) -> Self::TipFuture {
+        BlockService::<T>::tip(self.unary())
+    }
+
+    fn chain_length(&mut self) -> Self::ChainLengthFuture {
+        BlockService::<T>::chain_length(self.unary())
+    }
+
+    fn get_blocks(&mut self, ids: &[T::Id]) -> Self::GetBlocksFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(BlockService::<T>::get_blocks(client, ids), guard)
+    }
+
+    fn get_block(&mut self, id: T::Id) -> Self::GetBlockFuture {
+        BlockService::<T>::get_block(self.unary(), id)
+    }
+
+    fn get_blocks_pipelined<St>(&mut self, ids: St) -> Self::GetBlocksPipelinedFuture
+    where
+        St: Stream<Item = T::Id, Error = core_client::Error> + Send + 'static,
+    {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(BlockService::<T>::get_blocks_pipelined(client, ids), guard)
+    }
+
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture {
+        let (client, guard) = self.streaming();
+        let future = BlockService::<T>::get_blocks_by_height(client, from_height, count);
+        LoadTrackedFuture::new(future, guard)
+    }
+
+    fn block_id_at_height(&mut self, height: u64) -> Self::BlockIdAtHeightFuture {
+        BlockService::<T>::block_id_at_height(self.unary(), height)
+    }
+
+    fn has_blocks(&mut self, ids: &[T::Id]) -> Self::HasBlocksFuture {
+        BlockService::<T>::has_blocks(self.unary(), ids)
+    }
+
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(BlockService::<T>::block_subscription(client), guard)
+    }
+
+    fn upload_blocks<St>(&mut self, blocks: St) -> Self::UploadBlocksFuture
+    where
+        St: Stream<Item = T, Error = core_client::Error> + Send + 'static,
+    {
+        let (client, guard) = self.streaming();
+        LoadTracked {
+            inner: BlockService::<T>::upload_blocks(client, blocks),
+            _guard: guard,
+        }
+    }
+
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(BlockService::<T>::pull_blocks_to_tip(client, from), guard)
+    }
+
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> Self::PullBlocksFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(BlockService::<T>::pull_blocks(client, from, to), guard)
+    }
+
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> Self::PullBlocksFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(BlockService::<T>::pull_blocks_from_tip(client, to), guard)
+    }
+
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Self::PullBlocksFuture {
+        let (client, guard) = self.streaming();
+        let future = BlockService::<T>::pull_blocks_to_tip_filtered(client, from, known);
+        LoadTrackedFuture::new(future, guard)
+    }
+}
+
+impl<T, P, S, E> HeaderService<T> for MultiConnectClient<P, S, E>
+where
+    T: Block + HasHeader + 'static,
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    T::Id: Send,
+    T::Header: Clone + Send,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Header as Serialize>::Error: Send + Sync + 'static,
+    <T::Header as Deserialize>::Error: Send + Sync + 'static,
+{
+    type GetHeadersStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as HeaderService<T>>::GetHeadersStream>;
+    type GetHeadersFuture =
+        LoadTrackedFuture<<ReconnectingClient<P, S, E> as HeaderService<T>>::GetHeadersFuture>;
+
+    fn get_headers(&mut self, ids: &[T::Id]) -> Self::GetHeadersFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(HeaderService::<T>::get_headers(client, ids), guard)
+    }
+
+    type TipHeaderFuture = <ReconnectingClient<P, S, E> as HeaderService<T>>::TipHeaderFuture;
+
+    fn tip_header(&mut self) -> Self::TipHeaderFuture {
+        HeaderService::<T>::tip_header(self.unary())
+    }
+
+    type TipHeadersStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as HeaderService<T>>::TipHeadersStream>;
+    type TipHeadersFuture =
+        LoadTrackedFuture<<ReconnectingClient<P, S, E> as HeaderService<T>>::TipHeadersFuture>;
+
+    fn tip_headers(&mut self, count: u32) -> Self::TipHeadersFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(HeaderService::<T>::tip_headers(client, count), guard)
+    }
+
+    type PullHeadersStream =
+        LoadTracked<<ReconnectingClient<P, S, E> as HeaderService<T>>::PullHeadersStream>;
+    type PullHeadersFuture =
+        LoadTrackedFuture<<ReconnectingClient<P, S, E> as HeaderService<T>>::PullHeadersFuture>;
+
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> Self::PullHeadersFuture {
+        let (client, guard) = self.streaming();
+        LoadTrackedFuture::new(HeaderService::<T>::pull_headers_to_tip(client, from), guard)
+    }
+
+    type PushHeadersFuture = <ReconnectingClient<P, S, E> as HeaderService<T>>::PushHeadersFuture;
+
+    fn push_headers(&mut self, headers: &[T::Header]) -> Self::PushHeadersFuture {
+        HeaderService::<T>::push_headers(self.unary(), headers)
+    }
+}
@@ -0,0 +1,393 @@
+use super::{Client, ClientBuilder, Error, NodeClient};
+use crate::peer::Origin;
+
+use network_core::client as core_client;
+
+use futures::future::{Executor, Shared};
+use tokio::io;
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::{Background, ConnectError};
+
+use std::{
+    collections::HashMap,
+    error,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A pool of gRPC clients caching a connection for each peer.
+///
+/// Method `get` returns a clone of the cached client for the peer,
+/// connecting to the peer first if there is no usable connection.
+/// A connection is evicted when it has not been handed out for longer
+/// than the idle TTL, or when the client reports it as closed with
+/// `Client::is_connected`. Requests for a peer that is being connected
+/// to wait for the same connection attempt, so a burst of requests
+/// for a new peer makes only one connection; if the attempt fails,
+/// all of them fail with the error.
+///
+/// Cloning a pool is cheap: the clones share the cached connections.
+pub struct ClientPool<P, S, E> {
+    inner: Arc<Mutex<Inner<P, S, E>>>,
+}
+
+// The connection attempt shared by the requests waiting for it.
+// When the attempt succeeds, the client is stored in the pool.
+type PendingConnect = Shared<Box<dyn Future<Item = (), Error = Error> + Send>>;
+
+enum Entry<S, E> {
+    Connected {
+        client: Client<S, E>,
+        last_used: Instant,
+    },
+    Connecting(PendingConnect),
+}
+
+struct Inner<P, S, E> {
+    builder: ClientBuilder,
+    executor: E,
+    idle_ttl: Duration,
+    entries: HashMap<P, Entry<S, E>>,
+}
+
+const DEFAULT_IDLE_TTL: Duration = Duration::from_secs(60);
+
+impl<P, S, E> Clone for ClientPool<P, S, E> {
+    fn clone(&self) -> Self {
+        ClientPool {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<P, S, E> ClientPool<P, S, E>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error>
+        + Origin
+        + Clone
+        + Eq
+        + Hash
+        + Send
+        + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+{
+    /// Creates a pool connecting with the default connection settings.
+    pub fn new(executor: E) -> Self {
+        Self::with_builder(ClientBuilder::new(), executor)
+    }
+
+    /// Creates a pool that uses the settings of the builder
+    /// to establish connections.
+    pub fn with_builder(builder: ClientBuilder, executor: E) -> Self {
+        let inner = Inner {
+            builder,
+            executor,
+            idle_ttl: DEFAULT_IDLE_TTL,
+            entries: HashMap::new(),
+        };
+        ClientPool {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Sets the time after which a connection that has not been
+    /// handed out is evicted. The default is 60 seconds.
+    pub fn set_idle_ttl(&mut self, ttl: Duration) {
+        self.inner.lock().unwrap().idle_ttl = ttl;
+    }
+
+    /// Returns a client connected to the peer, reusing the cached
+    /// connection if it is still usable.
+    pub fn get(&self, peer: P) -> impl Future<Item = Client<S, E>, Error = Error>
+    where
+        NodeClient<S, E>: Clone,
+    {
+        GetClient {
+            pool: self.inner.clone(),
+            peer,
+            pending: None,
+        }
+    }
+
+    /// Drops the cached connections that have been idle for longer
+    /// than the TTL or have been closed.
+    ///
+    /// Such connections are also replaced when requested with `get`,
+    /// so calling this method periodically only serves to release
+    /// the connections to peers that are no longer requested.
+    pub fn evict_idle(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let ttl = inner.idle_ttl;
+        inner.entries.retain(|_, entry| match entry {
+            Entry::Connected { client, last_used } => is_usable(client, *last_used, ttl),
+            Entry::Connecting(_) => true,
+        });
+    }
+}
+
+fn is_usable<S, E>(client: &Client<S, E>, last_used: Instant, ttl: Duration) -> bool {
+    client.is_connected() && last_used.elapsed() <= ttl
+}
+
+// Returns a clone of the cached client for the peer, or else
+// the pending connection attempt, starting one if there is none.
+// A client that has just been connected is returned even if
+// the TTL is shorter than the time it took to connect.
+fn checkout<P, S, E>(
+    pool: &Arc<Mutex<Inner<P, S, E>>>,
+    peer: &P,
+    just_connected: bool,
+) -> Result<Client<S, E>, PendingConnect>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error>
+        + Origin
+        + Clone
+        + Eq
+        + Hash
+        + Send
+        + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    NodeClient<S, E>: Clone,
+{
+    let mut inner = pool.lock().unwrap();
+    let ttl = inner.idle_ttl;
+    match inner.entries.get_mut(peer) {
+        Some(Entry::Connected { client, last_used })
+            if client.is_connected() && (just_connected || last_used.elapsed() <= ttl) =>
+        {
+            *last_used = Instant::now();
+            return Ok(client.clone());
+        }
+        Some(Entry::Connecting(pending)) => return Err(pending.clone()),
+        _ => {}
+    }
+    let weak = Arc::downgrade(pool);
+    let key = peer.clone();
+    let connect = inner
+        .builder
+        .build_connect(peer.clone(), inner.executor.clone())
+        .then(move |res| {
+            let pool = match weak.upgrade() {
+                Some(pool) => pool,
+                None => return res.map(|_| ()),
+            };
+            let mut inner = pool.lock().unwrap();
+            match res {
+                Ok(client) => {
+                    let entry = Entry::Connected {
+                        client,
+                        last_used: Instant::now(),
+                    };
+                    inner.entries.insert(key, entry);
+                    Ok(())
+                }
+                Err(e) => {
+                    inner.entries.remove(&key);
+                    Err(e)
+                }
+            }
+        });
+    let connect: Box<dyn Future<Item = (), Error = Error> + Send> = Box::new(connect);
+    let pending = connect.shared();
+    inner
+        .entries
+        .insert(peer.clone(), Entry::Connecting(pending.clone()));
+    Err(pending)
+}
+
+// Makes a copy of the error of a connection attempt for each of
+// the requests that have waited for it.
+fn copy_error(e: &Error) -> Error {
+    match e {
+        Error::Connect(ConnectError::Connect(e)) => Error::Connect(ConnectError::Connect(
+            io::Error::new(e.kind(), e.to_string()),
+        )),
+        Error::Connect(e) => Error::Connect(ConnectError::Connect(io::Error::new(
+            io::ErrorKind::Other,
+            e.to_string(),
+        ))),
+        Error::InvalidOrigin(uri) => Error::InvalidOrigin(uri.clone()),
+        Error::Timeout => Error::Timeout,
+        Error::Canceled => Error::Canceled,
+        Error::Ping(e) => Error::Ping(copy_client_error(e)),
+        Error::NetworkMismatch { expected, got } => Error::NetworkMismatch {
+            expected: expected.clone(),
+            got: got.clone(),
+        },
+        Error::Resolve(e) => Error::Resolve(io::Error::new(e.kind(), e.to_string())),
+        Error::AllAddressesFailed(errors) => Error::AllAddressesFailed(
+            errors
+                .iter()
+                .map(|(addr, e)| (*addr, copy_error(e)))
+                .collect(),
+        ),
+        Error::Proxy(e) => Error::Proxy(io::Error::new(e.kind(), e.to_string())),
+        Error::Protocol(msg) => Error::Protocol(msg.clone()),
+    }
+}
+
+// Copies a client error along with the message of its source,
+// which is not part of the displayed error.
+fn copy_client_error(e: &core_client::Error) -> core_client::Error {
+    let msg = error::Error::source(e).map_or_else(String::new, ToString::to_string);
+    core_client::Error::new(e.kind(), msg)
+}
+
+struct GetClient<P, S, E> {
+    pool: Arc<Mutex<Inner<P, S, E>>>,
+    peer: P,
+    pending: Option<PendingConnect>,
+}
+
+impl<P, S, E> Future for GetClient<P, S, E>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error>
+        + Origin
+        + Clone
+        + Eq
+        + Hash
+        + Send
+        + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    NodeClient<S, E>: Clone,
+{
+    type Item = Client<S, E>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Client<S, E>, Error> {
+        loop {
+            let just_connected = match self.pending {
+                None => false,
+                Some(ref mut pending) => match pending.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(_)) => true,
+                    Err(e) => return Err(copy_error(&e)),
+                },
+            };
+            match checkout(&self.pool, &self.peer, just_connected) {
+                Ok(client) => return Ok(Async::Ready(client)),
+                Err(pending) => self.pending = Some(pending),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClientPool;
+    use crate::peer::{Origin, TcpPeer};
+    use crate::test_types::{serve, StubNode};
+
+    use futures::future;
+    use tokio::executor::DefaultExecutor;
+    use tokio::net::TcpStream;
+    use tokio::runtime::current_thread::Runtime;
+    use tokio::timer::Delay;
+
+    use std::{
+        hash::{Hash, Hasher},
+        io,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    // A peer that counts the connections made to it.
+    #[derive(Clone)]
+    struct CountingPeer {
+        inner: TcpPeer,
+        connects: Arc<AtomicUsize>,
+    }
+
+    impl CountingPeer {
+        fn new(inner: TcpPeer) -> Self {
+            CountingPeer {
+                inner,
+                connects: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn connects(&self) -> usize {
+            self.connects.load(Ordering::SeqCst)
+        }
+    }
+
+    impl PartialEq for CountingPeer {
+        fn eq(&self, other: &Self) -> bool {
+            self.inner == other.inner
+        }
+    }
+
+    impl Eq for CountingPeer {}
+
+    impl Hash for CountingPeer {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.inner.hash(state)
+        }
+    }
+
+    impl Origin for CountingPeer {
+        fn origin(&self) -> http::Uri {
+            self.inner.origin()
+        }
+    }
+
+    impl tokio_connect::Connect for CountingPeer {
+        type Connected = TcpStream;
+        type Error = io::Error;
+        type Future = <TcpPeer as tokio_connect::Connect>::Future;
+
+        fn connect(&self) -> Self::Future {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            tokio_connect::Connect::connect(&self.inner)
+        }
+    }
+
+    fn start(
+        rt: &mut Runtime,
+    ) -> (
+        ClientPool<CountingPeer, TcpStream, DefaultExecutor>,
+        CountingPeer,
+    ) {
+        let peer = CountingPeer::new(serve(rt, StubNode::new(vec![0, 1, 2])));
+        let pool = ClientPool::new(DefaultExecutor::current());
+        (pool, peer)
+    }
+
+    #[test]
+    fn concurrent_requests_for_a_new_peer_make_one_connection() {
+        let mut rt = Runtime::new().unwrap();
+        let (pool, peer) = start(&mut rt);
+        let gets: Vec<_> = (0..5).map(|_| pool.get(peer.clone())).collect();
+        let clients = rt.block_on(future::join_all(gets)).unwrap();
+        assert_eq!(clients.len(), 5);
+        assert_eq!(peer.connects(), 1);
+        rt.block_on(pool.get(peer.clone())).unwrap();
+        assert_eq!(peer.connects(), 1);
+    }
+
+    #[test]
+    fn connection_idle_past_the_ttl_is_evicted() {
+        let mut rt = Runtime::new().unwrap();
+        let (mut pool, peer) = start(&mut rt);
+        let ttl = Duration::from_millis(50);
+        pool.set_idle_ttl(ttl);
+        let wait_past_ttl = |rt: &mut Runtime| {
+            rt.block_on(Delay::new(Instant::now() + ttl * 2)).unwrap();
+        };
+        rt.block_on(pool.get(peer.clone())).unwrap();
+        wait_past_ttl(&mut rt);
+        rt.block_on(pool.get(peer.clone())).unwrap();
+        assert_eq!(peer.connects(), 2);
+        wait_past_ttl(&mut rt);
+        pool.evict_idle();
+        assert!(pool.inner.lock().unwrap().entries.is_empty());
+    }
+}
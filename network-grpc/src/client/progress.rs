@@ -0,0 +1,152 @@
+use super::{ConvertResponse, ResponseStream, StreamHandle};
+
+use chain_core::property::Block;
+use network_core::client as core_client;
+
+use futures::{prelude::*, sync::mpsc};
+
+use std::time::{Duration, Instant};
+
+/// A snapshot of the progress of a block download.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncProgress<D> {
+    /// The number of blocks received so far.
+    pub blocks_received: u64,
+    /// The date of the last block received, or `None` if no blocks
+    /// have been received yet.
+    pub last_block_date: Option<D>,
+    /// The total size in bytes of the block messages received so far.
+    /// This is the serialized size of the blocks plus a few bytes of
+    /// protobuf framing per block.
+    pub bytes_received: u64,
+}
+
+/// How often `Progress` sends a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressInterval {
+    /// A snapshot is sent every time the given number of blocks
+    /// has been received since the last snapshot.
+    Blocks(u64),
+    /// A snapshot is sent with the first block received after
+    /// the given time has passed since the last snapshot.
+    Duration(Duration),
+}
+
+/// Stream adapter that reports the progress of a block download
+/// through a channel.
+///
+/// The blocks are yielded unchanged. Snapshots of the progress are sent
+/// to the channel at the configured `ProgressInterval`, and a final
+/// snapshot is sent when the stream ends or fails, unless it would repeat
+/// the previous one. A snapshot is dropped rather than waited on
+/// if the channel is full, so a slow receiver never holds back
+/// the download; when the receiver is dropped, reporting stops.
+///
+/// An adapter is created with the `progress` method of `ResponseStream`.
+pub struct Progress<T: Block, R> {
+    inner: ResponseStream<T, R>,
+    sender: Option<mpsc::Sender<SyncProgress<T::Date>>>,
+    interval: ProgressInterval,
+    blocks_received: u64,
+    last_block_date: Option<T::Date>,
+    // The number of blocks and the time at the last snapshot.
+    reported_blocks: u64,
+    reported_at: Instant,
+}
+
+impl<T, R> Progress<T, R>
+where
+    T: Block,
+    R: prost::Message + Default + ConvertResponse<T>,
+{
+    pub(super) fn new(
+        inner: ResponseStream<T, R>,
+        sender: mpsc::Sender<SyncProgress<T::Date>>,
+        interval: ProgressInterval,
+    ) -> Self {
+        Progress {
+            inner,
+            sender: Some(sender),
+            interval,
+            blocks_received: 0,
+            last_block_date: None,
+            reported_blocks: 0,
+            reported_at: Instant::now(),
+        }
+    }
+
+    /// Returns a handle that can be used to cancel the stream.
+    pub fn handle(&self) -> StreamHandle {
+        self.inner.handle()
+    }
+
+    /// Consumes the adapter, returning the wrapped stream.
+    pub fn into_inner(self) -> ResponseStream<T, R> {
+        self.inner
+    }
+
+    fn is_due(&self) -> bool {
+        match self.interval {
+            ProgressInterval::Blocks(n) => self.blocks_received - self.reported_blocks >= n,
+            ProgressInterval::Duration(d) => self.reported_at.elapsed() >= d,
+        }
+    }
+
+    fn report(&mut self) {
+        self.reported_blocks = self.blocks_received;
+        self.reported_at = Instant::now();
+        let progress = SyncProgress {
+            blocks_received: self.blocks_received,
+            last_block_date: self.last_block_date.clone(),
+            bytes_received: self.inner.received_bytes(),
+        };
+        let disconnected = match self.sender {
+            None => return,
+            Some(ref mut sender) => match sender.try_send(progress) {
+                Ok(()) => false,
+                Err(e) => e.is_disconnected(),
+            },
+        };
+        if disconnected {
+            self.sender = None;
+        }
+    }
+
+    fn report_final(&mut self) {
+        if self.blocks_received != self.reported_blocks {
+            self.report();
+        }
+        self.sender = None;
+    }
+}
+
+impl<T, R> Stream for Progress<T, R>
+where
+    T: Block,
+    R: prost::Message + Default + ConvertResponse<T>,
+{
+    type Item = T;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, core_client::Error> {
+        match self.inner.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(Some(block))) => {
+                self.blocks_received += 1;
+                self.last_block_date = Some(block.date());
+                if self.is_due() {
+                    self.report();
+                }
+                Ok(Async::Ready(Some(block)))
+            }
+            Ok(Async::Ready(None)) => {
+                self.report_final();
+                Ok(Async::Ready(None))
+            }
+            Err(e) => {
+                self.report_final();
+                Err(e)
+            }
+        }
+    }
+}
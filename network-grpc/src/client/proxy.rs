@@ -0,0 +1,273 @@
+use super::{host::host_origin, Client, ClientBuilder, Error};
+
+use futures::future::{self, Executor, Loop};
+use tokio::io;
+use tokio::net::TcpStream;
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::{fmt, net::SocketAddr, sync::Mutex, time::Instant};
+
+// The limit on the size of the response head sent by the proxy
+// to a CONNECT request.
+const MAX_RESPONSE_HEAD_LEN: usize = 8 * 1024;
+
+/// Specifies the connection details of an HTTP proxy that relays
+/// connections to peers through tunnels established with
+/// the `CONNECT` method.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct HttpProxy {
+    addr: SocketAddr,
+    authorization: Option<String>,
+}
+
+impl HttpProxy {
+    pub fn new(addr: SocketAddr) -> Self {
+        HttpProxy {
+            addr,
+            authorization: None,
+        }
+    }
+
+    pub fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+
+    /// Sets the credentials to authenticate with the proxy using
+    /// the basic scheme, sent in the `Proxy-Authorization` header
+    /// of the `CONNECT` request.
+    pub fn set_basic_auth(&mut self, user: &str, password: &str) {
+        let credentials = base64::encode(format!("{}:{}", user, password).as_bytes());
+        self.authorization = Some(format!("Basic {}", credentials));
+    }
+}
+
+impl fmt::Debug for HttpProxy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpProxy")
+            .field("addr", &self.addr)
+            .field("auth", &self.authorization.is_some())
+            .finish()
+    }
+}
+
+impl<E> Client<TcpStream, E>
+where
+    E: Executor<Background<TcpStream, BoxBody>> + Clone,
+{
+    /// Connects to the peer listening on the host and port through
+    /// a tunnel established by the HTTP proxy.
+    ///
+    /// See `ClientBuilder::build_connect_proxy` for details.
+    pub fn connect_proxy(
+        proxy: &HttpProxy,
+        host: &str,
+        port: u16,
+        executor: E,
+    ) -> impl Future<Item = Self, Error = Error> {
+        ClientBuilder::new().build_connect_proxy(proxy, host, port, executor)
+    }
+}
+
+impl ClientBuilder {
+    /// Connects to the peer listening on the host and port through
+    /// a tunnel established by the HTTP proxy, with the settings of
+    /// this builder.
+    ///
+    /// The client sends a `CONNECT` request for the authority formed
+    /// by the host and the port to the proxy, which is left to resolve
+    /// the host name. Once the proxy has responded with a success
+    /// status, the HTTP/2 connection is established over the tunnel
+    /// as it would be over a direct TCP connection.
+    ///
+    /// If the connection to the proxy fails, or the proxy does not
+    /// establish the tunnel, the future fails with `Error::Proxy`.
+    /// A reply of the proxy that is not an HTTP response fails
+    /// the future with `Error::Protocol`.
+    /// Failures of the HTTP/2 connection established through
+    /// the tunnel are reported as `Error::Connect`.
+    ///
+    /// The requests are made with an origin formed by the host name and
+    /// the port, as with `build_connect_host`.
+    pub fn build_connect_proxy<E>(
+        &self,
+        proxy: &HttpProxy,
+        host: &str,
+        port: u16,
+        executor: E,
+    ) -> impl Future<Item = Client<TcpStream, E>, Error = Error>
+    where
+        E: Executor<Background<TcpStream, BoxBody>> + Clone,
+    {
+        let origin = match host_origin(host, port) {
+            Ok(origin) => origin,
+            Err(e) => return future::Either::A(future::err(e)),
+        };
+        let request = connect_request(&origin, proxy.authorization.as_ref());
+        let builder = self.clone();
+        let metrics = self.metrics.clone();
+        let started = Instant::now();
+        let keepalive = self.keepalive();
+        let connect = TcpStream::connect(&proxy.addr)
+            .and_then(move |stream| stream.set_keepalive(keepalive).map(|()| stream))
+            .and_then(move |stream| io::write_all(stream, request))
+            .map_err(Error::Proxy)
+            .and_then(|(stream, _)| read_response_head(stream))
+            .then(move |res| {
+                // A successful connection is recorded once the HTTP/2
+                // connection is established through the tunnel.
+                if res.is_err() {
+                    metrics.connect_completed(started, &res);
+                }
+                res
+            })
+            .and_then(move |stream| {
+                builder.build_connect_with_origin(Tunneled::new(stream), origin, executor)
+            });
+        future::Either::B(connect)
+    }
+}
+
+fn connect_request(origin: &http::Uri, authorization: Option<&String>) -> Vec<u8> {
+    let authority = origin
+        .authority_part()
+        .expect("host origin should have an authority");
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority.as_str());
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    request.push_str("\r\n");
+    request.into_bytes()
+}
+
+// Reads the response of the proxy to the CONNECT request.
+// The response head is read one byte at a time, so that none of
+// the data the peer may send through the tunnel right after it
+// is consumed.
+fn read_response_head(stream: TcpStream) -> impl Future<Item = TcpStream, Error = Error> {
+    future::loop_fn((stream, Vec::new()), |(stream, mut head)| {
+        io::read_exact(stream, [0; 1])
+            .map_err(Error::Proxy)
+            .and_then(move |(stream, byte)| {
+                head.push(byte[0]);
+                if head.ends_with(b"\r\n\r\n") {
+                    check_response_status(&head)?;
+                    Ok(Loop::Break(stream))
+                } else if head.len() >= MAX_RESPONSE_HEAD_LEN {
+                    Err(Error::Protocol(
+                        "the response head of the proxy is too long".into(),
+                    ))
+                } else {
+                    Ok(Loop::Continue((stream, head)))
+                }
+            })
+    })
+}
+
+// Checks that the status line of the response head has a success
+// status, which means the tunnel has been established.
+fn check_response_status(head: &[u8]) -> Result<(), Error> {
+    let line = head
+        .split(|&b| b == b'\r')
+        .next()
+        .and_then(|line| std::str::from_utf8(line).ok())
+        .ok_or_else(|| {
+            Error::Protocol("invalid status line in the response of the proxy".into())
+        })?;
+    let mut parts = line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    let status = parts.next().and_then(|s| s.parse::<u16>().ok());
+    match status {
+        Some(status) if version.starts_with("HTTP/1.") => {
+            if status >= 200 && status < 300 {
+                Ok(())
+            } else {
+                let reason = parts.next().unwrap_or("");
+                Err(Error::Proxy(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "the proxy refused to establish the tunnel: {} {}",
+                        status, reason
+                    ),
+                )))
+            }
+        }
+        _ => Err(Error::Protocol(format!(
+            "invalid status line in the response of the proxy: {:?}",
+            line
+        ))),
+    }
+}
+
+// Connector handing the stream of an established tunnel over to
+// the HTTP/2 connection.
+struct Tunneled {
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl Tunneled {
+    fn new(stream: TcpStream) -> Self {
+        Tunneled {
+            stream: Mutex::new(Some(stream)),
+        }
+    }
+}
+
+impl tokio_connect::Connect for Tunneled {
+    type Connected = TcpStream;
+    type Error = io::Error;
+    type Future = future::FutureResult<TcpStream, io::Error>;
+
+    fn connect(&self) -> Self::Future {
+        let stream = self.stream.lock().unwrap().take();
+        future::result(stream.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "the tunnel has already been used")
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn success_status_establishes_the_tunnel() {
+        let head = b"HTTP/1.1 200 Connection established\r\n\r\n";
+        assert!(check_response_status(head).is_ok());
+        let head = b"HTTP/1.0 407 Proxy Authentication Required\r\n\r\n";
+        match check_response_status(head) {
+            Err(Error::Proxy(_)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn reply_that_is_not_http_is_a_protocol_error() {
+        let head = b"SSH-2.0-OpenSSH\r\n\r\n";
+        match check_response_status(head) {
+            Err(Error::Protocol(_)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        let head = b"\xff\xfe\r\n\r\n";
+        match check_response_status(head) {
+            Err(Error::Protocol(_)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn connect_request_carries_credentials() {
+        let mut proxy = HttpProxy::new("127.0.0.1:3128".parse().unwrap());
+        proxy.set_basic_auth("user", "secret");
+        let origin = host_origin("node.example", 3000).unwrap();
+        let request = connect_request(&origin, proxy.authorization.as_ref());
+        assert_eq!(
+            String::from_utf8(request).unwrap(),
+            "CONNECT node.example:3000 HTTP/1.1\r\n\
+             Host: node.example:3000\r\n\
+             Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n\
+             \r\n"
+        );
+    }
+}
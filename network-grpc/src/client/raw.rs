@@ -0,0 +1,143 @@
+use super::{
+    empty_content, serialize_to_vec, take_block_continuation_token, verify_block_checksum, Client,
+    ConvertResponse, NodeClient, ResponseStream, ResponseStreamFuture, StreamHandle,
+};
+use crate::gen;
+
+use chain_core::property::{BlockId, Serialize};
+use network_core::client as core_client;
+
+use bytes::Bytes;
+use futures::{future::Executor, try_ready};
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+/// Stream of blocks in serialized form, as received from the peer.
+///
+/// This stream is returned by the methods of `Client` with the `_raw`
+/// suffix, such as `pull_blocks_to_tip_raw`. The blocks are not
+/// deserialized, so a relay node can forward them to other peers
+/// without the cost of decoding and re-encoding them. The bytes can
+/// be deserialized with `chain_core::property::Deserialize` if needed.
+///
+/// A message with empty content fails the stream with an error
+/// of kind `ErrorKind::Format`, as for the typed streams.
+pub struct RawResponseStream {
+    inner: ResponseStream<RawBlock, gen::node::Block>,
+}
+
+impl RawResponseStream {
+    /// Returns a handle that can be used to cancel the stream.
+    pub fn handle(&self) -> StreamHandle {
+        self.inner.handle()
+    }
+
+    /// Returns the number of blocks the server has announced it will
+    /// send in this stream, or `None` if the server did not provide a hint.
+    pub fn size_hint(&self) -> Option<u64> {
+        self.inner.size_hint()
+    }
+}
+
+impl Stream for RawResponseStream {
+    type Item = Vec<u8>;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, core_client::Error> {
+        let item = try_ready!(self.inner.poll());
+        Ok(Async::Ready(item.map(|RawBlock(bytes)| bytes)))
+    }
+}
+
+/// Future that resolves to a `RawResponseStream`.
+pub struct RawResponseStreamFuture {
+    inner: ResponseStreamFuture<RawBlock, gen::node::Block>,
+}
+
+impl RawResponseStreamFuture {
+    /// Returns a handle that can be used to cancel the request.
+    ///
+    /// If the request is canceled before the response arrives, the future
+    /// resolves to an empty stream.
+    pub fn handle(&self) -> StreamHandle {
+        self.inner.handle()
+    }
+}
+
+impl Future for RawResponseStreamFuture {
+    type Item = RawResponseStream;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<RawResponseStream, core_client::Error> {
+        let inner = try_ready!(self.inner.poll());
+        Ok(Async::Ready(RawResponseStream { inner }))
+    }
+}
+
+// The serialized content of a block message, passed through as is.
+pub struct RawBlock(Vec<u8>);
+
+impl ConvertResponse<RawBlock> for gen::node::Block {
+    fn convert_response(self) -> Result<RawBlock, core_client::Error> {
+        if self.content.is_empty() {
+            return Err(empty_content("block"));
+        }
+        Ok(RawBlock(self.content))
+    }
+
+    fn verify_checksum(&self) -> Result<(), core_client::Error> {
+        verify_block_checksum(self)
+    }
+
+    fn take_continuation_token(&mut self) -> Option<Bytes> {
+        take_block_continuation_token(self)
+    }
+}
+
+impl<S, E> Client<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Retrieves the blocks identified by `ids` in serialized form.
+    ///
+    /// This is the same request as `BlockService::get_blocks`.
+    pub fn get_blocks_raw<I>(&mut self, ids: &[I]) -> RawResponseStreamFuture
+    where
+        I: BlockId + Serialize,
+        <I as Serialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let inner = match serialize_to_vec(ids) {
+            Ok(ids) => {
+                let req = gen::node::GetBlocksRequest { ids };
+                self.streaming_call("GetBlocks", req, |node, req| node.get_blocks(req))
+            }
+            Err(e) => ResponseStreamFuture::error(e),
+        };
+        RawResponseStreamFuture { inner }
+    }
+
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the peer up to the tip of its chain, in serialized form.
+    ///
+    /// This is the same request as `BlockService::pull_blocks_to_tip`.
+    pub fn pull_blocks_to_tip_raw<I>(&mut self, from: &[I]) -> RawResponseStreamFuture
+    where
+        I: BlockId + Serialize,
+        <I as Serialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let inner = match serialize_to_vec(from) {
+            Ok(from) => {
+                let req = gen::node::PullBlocksToTipRequest { from, known: None };
+                self.streaming_call("PullBlocksToTip", req, |node, req| {
+                    node.pull_blocks_to_tip(req)
+                })
+            }
+            Err(e) => ResponseStreamFuture::error(e),
+        };
+        RawResponseStreamFuture { inner }
+    }
+}
@@ -0,0 +1,774 @@
+use super::{Client, ClientBuilder, Error};
+use crate::peer::Origin;
+
+use chain_core::property::{Block, Deserialize, HasHeader, Serialize};
+use network_core::{
+    bloom::BloomFilter,
+    client::{
+        self as core_client,
+        block::{BlockService, HeaderService},
+        gossip::GossipService,
+        peer::PeerService,
+    },
+};
+
+use futures::future::Executor;
+use tokio::io;
+use tokio::prelude::*;
+use tokio::timer::Delay;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::{
+    cmp, error,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Exponential backoff settings for reconnection attempts.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_retries: u32,
+}
+
+impl Backoff {
+    /// Creates backoff settings where the delay before the first retry
+    /// is `initial_delay`, each subsequent delay is doubled up to
+    /// `max_delay`, and the request fails after `max_retries`
+    /// unsuccessful retries.
+    pub fn new(initial_delay: Duration, max_delay: Duration, max_retries: u32) -> Self {
+        Backoff {
+            initial_delay,
+            max_delay,
+            max_retries,
+        }
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::max_value());
+        let delay = self
+            .initial_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay);
+        cmp::min(delay, self.max_delay)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_millis(100), Duration::from_secs(10), 5)
+    }
+}
+
+type ConnectFuture<S, E> = Box<dyn Future<Item = Client<S, E>, Error = Error> + Send>;
+
+struct Inner<P, S, E> {
+    peer: P,
+    executor: E,
+    builder: ClientBuilder,
+    backoff: Backoff,
+    client: Option<Client<S, E>>,
+    // Incremented on every established connection, so that a failing
+    // request does not tear down a connection made after it was issued.
+    generation: u64,
+}
+
+impl<P, S, E> Inner<P, S, E>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+{
+    fn connect(&self) -> ConnectFuture<S, E> {
+        let future = self
+            .builder
+            .build_connect(self.peer.clone(), self.executor.clone());
+        Box::new(future)
+    }
+}
+
+/// A gRPC client that transparently reconnects to the peer.
+///
+/// The connection is established lazily on the first request, and
+/// re-established when a request fails because the connection has
+/// been observed to be closed, as reported by `Client::is_connected`,
+/// or by the next request after that. A request that fails with
+/// a status returned by the peer, such as an error for an unknown
+/// block, leaves the connection in place and is not retried.
+/// Idempotent unary requests such as `tip` are retried after reconnecting,
+/// with delays determined by the `Backoff` settings.
+/// Streaming requests are never retried: an error on a stream is
+/// returned to the caller, who should decide where to resume the stream.
+pub struct ReconnectingClient<P, S, E> {
+    inner: Arc<Mutex<Inner<P, S, E>>>,
+}
+
+impl<P, S, E> ReconnectingClient<P, S, E>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+{
+    /// Creates a client for the peer with the default connection settings.
+    pub fn new(peer: P, executor: E) -> Self {
+        Self::with_builder(peer, ClientBuilder::new(), executor)
+    }
+
+    /// Creates a client for the peer that uses the settings of the
+    /// builder to establish connections.
+    pub fn with_builder(peer: P, builder: ClientBuilder, executor: E) -> Self {
+        let inner = Inner {
+            peer,
+            executor,
+            builder,
+            backoff: Backoff::default(),
+            client: None,
+            generation: 0,
+        };
+        ReconnectingClient {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Sets the backoff settings for reconnection attempts.
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.inner.lock().unwrap().backoff = backoff;
+    }
+
+    // Whether the connection has been established and then observed
+    // to be closed, so that the next request is going to reconnect.
+    pub(super) fn is_closed(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .client
+            .as_ref()
+            .map_or(false, |client| !client.is_connected())
+    }
+
+    fn call<F>(
+        &self,
+        retry: bool,
+        call: Box<dyn FnMut(&mut Client<S, E>) -> F + Send>,
+    ) -> ReconnectingFuture<P, S, E, F> {
+        ReconnectingFuture {
+            inner: self.inner.clone(),
+            call,
+            retry,
+            attempt: 0,
+            generation: 0,
+            state: State::Idle,
+        }
+    }
+}
+
+enum State<S, E, F> {
+    Idle,
+    Connecting(ConnectFuture<S, E>),
+    Waiting(Delay),
+    Calling(F),
+}
+
+/// Future for requests made with `ReconnectingClient`.
+pub struct ReconnectingFuture<P, S, E, F> {
+    inner: Arc<Mutex<Inner<P, S, E>>>,
+    call: Box<dyn FnMut(&mut Client<S, E>) -> F + Send>,
+    retry: bool,
+    attempt: u32,
+    generation: u64,
+    state: State<S, E, F>,
+}
+
+impl<P, S, E, F> ReconnectingFuture<P, S, E, F>
+where
+    F: Future<Error = core_client::Error>,
+{
+    // Whether the connection the request has been made on has failed.
+    // The client tells a transport failure from a status returned by
+    // the peer, which the error kind does not.
+    fn connection_failed(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        // Another request has reconnected after observing
+        // the connection closed.
+        if inner.generation != self.generation {
+            return true;
+        }
+        inner
+            .client
+            .as_ref()
+            .map_or(true, |client| !client.is_connected())
+    }
+
+    fn disconnect(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.generation == self.generation {
+            inner.client = None;
+        }
+    }
+
+    fn backoff(&mut self, err: core_client::Error) -> Result<State<S, E, F>, core_client::Error> {
+        let backoff = self.inner.lock().unwrap().backoff.clone();
+        if self.attempt >= backoff.max_retries {
+            return Err(err);
+        }
+        // Honor the peer's hint on when to retry, if it is longer.
+        let delay = cmp::max(
+            backoff.delay(self.attempt),
+            err.retry_after().unwrap_or_default(),
+        );
+        self.attempt += 1;
+        Ok(State::Waiting(Delay::new(Instant::now() + delay)))
+    }
+}
+
+impl<P, S, E, F> Future for ReconnectingFuture<P, S, E, F>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    F: Future<Error = core_client::Error>,
+{
+    type Item = F::Item;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, core_client::Error> {
+        loop {
+            let next = match self.state {
+                State::Idle => {
+                    let mut guard = self.inner.lock().unwrap();
+                    let inner = &mut *guard;
+                    match inner.client {
+                        Some(ref mut client) if client.is_connected() => {
+                            self.generation = inner.generation;
+                            State::Calling((self.call)(client))
+                        }
+                        _ => State::Connecting(inner.connect()),
+                    }
+                }
+                State::Connecting(ref mut future) => match future.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(client)) => {
+                        let mut inner = self.inner.lock().unwrap();
+                        // Another request may have reconnected in the meantime.
+                        if inner.client.as_ref().map_or(true, |c| !c.is_connected()) {
+                            inner.client = Some(client);
+                            inner.generation += 1;
+                        }
+                        State::Idle
+                    }
+                    Err(e) => {
+                        let err = core_client::Error::new(core_client::ErrorKind::Rpc, e);
+                        self.backoff(err)?
+                    }
+                },
+                State::Waiting(ref mut delay) => match delay.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(())) => State::Idle,
+                    Err(e) => {
+                        return Err(core_client::Error::new(core_client::ErrorKind::Rpc, e));
+                    }
+                },
+                State::Calling(ref mut future) => match future.poll() {
+                    Err(e) => {
+                        if !self.connection_failed() {
+                            return Err(e);
+                        }
+                        self.disconnect();
+                        if !self.retry {
+                            return Err(e);
+                        }
+                        self.backoff(e)?
+                    }
+                    res => return res,
+                },
+            };
+            self.state = next;
+        }
+    }
+}
+
+impl<T, P, S, E> BlockService<T> for ReconnectingClient<P, S, E>
+where
+    T: Block + 'static,
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    T::Id: Send,
+    T::Date: FromStr,
+    <T as Serialize>::Error: Send + Sync + 'static,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+    <T::Date as FromStr>::Err: error::Error + Send + Sync + 'static,
+{
+    type TipFuture = ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::TipFuture>;
+
+    type ChainLengthFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::ChainLengthFuture>;
+
+    type PullBlocksToTipStream = <Client<S, E> as BlockService<T>>::PullBlocksToTipStream;
+    type PullBlocksToTipFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::PullBlocksToTipFuture>;
+
+    type PullBlocksStream = <Client<S, E> as BlockService<T>>::PullBlocksStream;
+    type PullBlocksFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::PullBlocksFuture>;
+
+    type GetBlocksStream = <Client<S, E> as BlockService<T>>::GetBlocksStream;
+    type GetBlocksFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::GetBlocksFuture>;
+
+    type GetBlockFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::GetBlockFuture>;
+
+    type BlockIdAtHeightFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::BlockIdAtHeightFuture>;
+
+    type GetBlocksPipelinedStream = <Client<S, E> as BlockService<T>>::GetBlocksPipelinedStream;
+    type GetBlocksPipelinedFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::GetBlocksPipelinedFuture>;
+
+    type UploadBlocksFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::UploadBlocksFuture>;
+
+    type HasBlocksFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::HasBlocksFuture>;
+
+    type BlockSubscriptionStream = <Client<S, E> as BlockService<T>>::BlockSubscriptionStream;
+    type BlockSubscriptionFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as BlockService<T>>::BlockSubscriptionFuture>;
+
+    fn tip(&mut self) -> Self::TipFuture {
+        self.call(true, Box::new(|client| BlockService::<T>::tip(client)))
+    }
+
+    fn chain_length(&mut self) -> Self::ChainLengthFuture {
+        self.call(
+            true,
+            Box::new(|client| BlockService::<T>::chain_length(client)),
+        )
+    }
+
+    fn get_blocks(&mut self, ids: &[T::Id]) -> Self::GetBlocksFuture {
+        let ids = ids.to_vec();
+        self.call(
+            false,
+            Box::new(move |client| BlockService::<T>::get_blocks(client, &ids)),
+        )
+    }
+
+    fn get_block(&mut self, id: T::Id) -> Self::GetBlockFuture {
+        self.call(
+            true,
+            Box::new(move |client| BlockService::<T>::get_block(client, id.clone())),
+        )
+    }
+
+    fn get_blocks_pipelined<St>(&mut self, ids: St) -> Self::GetBlocksPipelinedFuture
+    where
+        St: Stream<Item = T::Id, Error = core_client::Error> + Send + 'static,
+    {
+        // The stream can only be consumed once, but the call
+        // is not retried, so the closure is invoked at most once.
+        let mut ids = Some(ids);
+        self.call(
+            false,
+            Box::new(move |client| {
+                let ids = ids.take().expect("get_blocks_pipelined request made twice");
+                BlockService::<T>::get_blocks_pipelined(client, ids)
+            }),
+        )
+    }
+
+    fn get_blocks_by_height(&mut self, from_height: u64, count: u32) -> Self::GetBlocksFuture {
+        self.call(
+            false,
+            Box::new(move |client| {
+                BlockService::<T>::get_blocks_by_height(client, from_height, count)
+            }),
+        )
+    }
+
+    fn block_id_at_height(&mut self, height: u64) -> Self::BlockIdAtHeightFuture {
+        self.call(
+            true,
+            Box::new(move |client| BlockService::<T>::block_id_at_height(client, height)),
+        )
+    }
+
+    fn has_blocks(&mut self, ids: &[T::Id]) -> Self::HasBlocksFuture {
+        let ids = ids.to_vec();
+        self.call(
+            true,
+            Box::new(move |client| BlockService::<T>::has_blocks(client, &ids)),
+        )
+    }
+
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture {
+        self.call(
+            false,
+            Box::new(|client| BlockService::<T>::block_subscription(client)),
+        )
+    }
+
+    fn upload_blocks<St>(&mut self, blocks: St) -> Self::UploadBlocksFuture
+    where
+        St: Stream<Item = T, Error = core_client::Error> + Send + 'static,
+    {
+        // The stream can only be consumed once, but the call
+        // is not retried, so the closure is invoked at most once.
+        let mut blocks = Some(blocks);
+        self.call(
+            false,
+            Box::new(move |client| {
+                let blocks = blocks.take().expect("upload_blocks request made twice");
+                BlockService::<T>::upload_blocks(client, blocks)
+            }),
+        )
+    }
+
+    fn pull_blocks_to_tip(&mut self, from: &[T::Id]) -> Self::PullBlocksToTipFuture {
+        let from = from.to_vec();
+        self.call(
+            false,
+            Box::new(move |client| BlockService::<T>::pull_blocks_to_tip(client, &from)),
+        )
+    }
+
+    fn pull_blocks(&mut self, from: &[T::Id], to: &T::Id) -> Self::PullBlocksFuture {
+        let from = from.to_vec();
+        let to = to.clone();
+        self.call(
+            false,
+            Box::new(move |client| BlockService::<T>::pull_blocks(client, &from, &to)),
+        )
+    }
+
+    fn pull_blocks_from_tip(&mut self, to: &[T::Id]) -> Self::PullBlocksFuture {
+        let to = to.to_vec();
+        self.call(
+            false,
+            Box::new(move |client| BlockService::<T>::pull_blocks_from_tip(client, &to)),
+        )
+    }
+
+    fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> Self::PullBlocksFuture {
+        let from = from.to_vec();
+        let known = known.clone();
+        self.call(
+            false,
+            Box::new(move |client| {
+                BlockService::<T>::pull_blocks_to_tip_filtered(client, &from, &known)
+            }),
+        )
+    }
+}
+
+impl<T, P, S, E> HeaderService<T> for ReconnectingClient<P, S, E>
+where
+    T: Block + HasHeader + 'static,
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    T::Id: Send,
+    T::Header: Clone + Send,
+    <T::Id as Serialize>::Error: Send + Sync + 'static,
+    <T::Header as Serialize>::Error: Send + Sync + 'static,
+    <T::Header as Deserialize>::Error: Send + Sync + 'static,
+{
+    type GetHeadersStream = <Client<S, E> as HeaderService<T>>::GetHeadersStream;
+    type GetHeadersFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as HeaderService<T>>::GetHeadersFuture>;
+
+    fn get_headers(&mut self, ids: &[T::Id]) -> Self::GetHeadersFuture {
+        let ids = ids.to_vec();
+        self.call(
+            false,
+            Box::new(move |client| HeaderService::<T>::get_headers(client, &ids)),
+        )
+    }
+
+    type TipHeaderFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as HeaderService<T>>::TipHeaderFuture>;
+
+    fn tip_header(&mut self) -> Self::TipHeaderFuture {
+        self.call(
+            true,
+            Box::new(|client| HeaderService::<T>::tip_header(client)),
+        )
+    }
+
+    type TipHeadersStream = <Client<S, E> as HeaderService<T>>::TipHeadersStream;
+    type TipHeadersFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as HeaderService<T>>::TipHeadersFuture>;
+
+    fn tip_headers(&mut self, count: u32) -> Self::TipHeadersFuture {
+        self.call(
+            false,
+            Box::new(move |client| HeaderService::<T>::tip_headers(client, count)),
+        )
+    }
+
+    type PullHeadersStream = <Client<S, E> as HeaderService<T>>::PullHeadersStream;
+    type PullHeadersFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as HeaderService<T>>::PullHeadersFuture>;
+
+    fn pull_headers_to_tip(&mut self, from: &[T::Id]) -> Self::PullHeadersFuture {
+        let from = from.to_vec();
+        self.call(
+            false,
+            Box::new(move |client| HeaderService::<T>::pull_headers_to_tip(client, &from)),
+        )
+    }
+
+    type PushHeadersFuture =
+        ReconnectingFuture<P, S, E, <Client<S, E> as HeaderService<T>>::PushHeadersFuture>;
+
+    fn push_headers(&mut self, headers: &[T::Header]) -> Self::PushHeadersFuture {
+        let headers = headers.to_vec();
+        self.call(
+            false,
+            Box::new(move |client| HeaderService::<T>::push_headers(client, &headers)),
+        )
+    }
+}
+
+impl<T, P, S, E> PeerService<T> for ReconnectingClient<P, S, E>
+where
+    T: Block + 'static,
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+{
+    type PingFuture = ReconnectingFuture<P, S, E, <Client<S, E> as PeerService<T>>::PingFuture>;
+
+    fn ping(&mut self) -> Self::PingFuture {
+        self.call(true, Box::new(|client| PeerService::<T>::ping(client)))
+    }
+}
+
+impl<P, S, E> GossipService for ReconnectingClient<P, S, E>
+where
+    P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + Clone + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    E: Executor<Background<S, BoxBody>> + Clone + Send + 'static,
+{
+    type PeersStream = <Client<S, E> as GossipService>::PeersStream;
+    type PeersFuture = ReconnectingFuture<P, S, E, <Client<S, E> as GossipService>::PeersFuture>;
+
+    fn peers(&mut self) -> Self::PeersFuture {
+        self.call(false, Box::new(|client| GossipService::peers(client)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backoff, ReconnectingClient};
+    use crate::peer::{Origin, TcpPeer};
+    use crate::test_types::{serve, StubNode, TestBlock, TestDate, TestId};
+
+    use network_core::client::{
+        self as core_client,
+        block::{BlockService, HeaderService},
+    };
+
+    use futures::try_ready;
+    use tokio::executor::DefaultExecutor;
+    use tokio::net::TcpStream;
+    use tokio::prelude::*;
+    use tokio::runtime::current_thread::Runtime;
+
+    use std::{
+        io::{self, Read, Write},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    // A peer that counts the connections made to it, and can make
+    // the connections established so far fail on any further I/O.
+    #[derive(Clone)]
+    struct FlakyPeer {
+        inner: TcpPeer,
+        connects: Arc<AtomicUsize>,
+        broken_below: Arc<AtomicUsize>,
+    }
+
+    impl FlakyPeer {
+        fn new(inner: TcpPeer) -> Self {
+            FlakyPeer {
+                inner,
+                connects: Arc::new(AtomicUsize::new(0)),
+                broken_below: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn connects(&self) -> usize {
+            self.connects.load(Ordering::SeqCst)
+        }
+
+        fn break_connections(&self) {
+            self.broken_below.store(self.connects(), Ordering::SeqCst);
+        }
+    }
+
+    impl Origin for FlakyPeer {
+        fn origin(&self) -> http::Uri {
+            self.inner.origin()
+        }
+    }
+
+    impl tokio_connect::Connect for FlakyPeer {
+        type Connected = FlakyStream;
+        type Error = io::Error;
+        type Future = FlakyConnect;
+
+        fn connect(&self) -> FlakyConnect {
+            FlakyConnect {
+                inner: tokio_connect::Connect::connect(&self.inner),
+                seq: self.connects.fetch_add(1, Ordering::SeqCst),
+                broken_below: self.broken_below.clone(),
+            }
+        }
+    }
+
+    struct FlakyConnect {
+        inner: <TcpPeer as tokio_connect::Connect>::Future,
+        seq: usize,
+        broken_below: Arc<AtomicUsize>,
+    }
+
+    impl Future for FlakyConnect {
+        type Item = FlakyStream;
+        type Error = io::Error;
+
+        fn poll(&mut self) -> Poll<FlakyStream, io::Error> {
+            let sock = try_ready!(self.inner.poll());
+            Ok(Async::Ready(FlakyStream {
+                sock,
+                seq: self.seq,
+                broken_below: self.broken_below.clone(),
+            }))
+        }
+    }
+
+    struct FlakyStream {
+        sock: TcpStream,
+        seq: usize,
+        broken_below: Arc<AtomicUsize>,
+    }
+
+    impl FlakyStream {
+        fn check(&self) -> io::Result<()> {
+            if self.seq < self.broken_below.load(Ordering::SeqCst) {
+                Err(io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "connection broken by the test",
+                ))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Read for FlakyStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.check()?;
+            self.sock.read(buf)
+        }
+    }
+
+    impl Write for FlakyStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.check()?;
+            self.sock.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.check()?;
+            self.sock.flush()
+        }
+    }
+
+    impl AsyncRead for FlakyStream {}
+
+    impl AsyncWrite for FlakyStream {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            AsyncWrite::shutdown(&mut self.sock)
+        }
+    }
+
+    fn start(
+        rt: &mut Runtime,
+    ) -> (
+        FlakyPeer,
+        ReconnectingClient<FlakyPeer, FlakyStream, DefaultExecutor>,
+    ) {
+        let peer = FlakyPeer::new(serve(rt, StubNode::new(vec![1, 2, 3])));
+        let mut client = ReconnectingClient::new(peer.clone(), DefaultExecutor::current());
+        let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(50), 3);
+        client.set_backoff(backoff);
+        (peer, client)
+    }
+
+    const TIP: (TestId, TestDate) = (TestId(3), TestDate { epoch: 0, slot: 3 });
+
+    #[test]
+    fn unary_request_is_retried_on_a_new_connection() {
+        let mut rt = Runtime::new().unwrap();
+        let (peer, mut client) = start(&mut rt);
+        let tip = rt.block_on(BlockService::<TestBlock>::tip(&mut client));
+        assert_eq!(tip.unwrap(), TIP);
+        assert_eq!(peer.connects(), 1);
+        peer.break_connections();
+        let tip = rt.block_on(BlockService::<TestBlock>::tip(&mut client));
+        assert_eq!(tip.unwrap(), TIP);
+        assert_eq!(peer.connects(), 2);
+    }
+
+    #[test]
+    fn streaming_request_is_not_retried() {
+        let mut rt = Runtime::new().unwrap();
+        let (peer, mut client) = start(&mut rt);
+        let tip = rt.block_on(BlockService::<TestBlock>::tip(&mut client));
+        assert_eq!(tip.unwrap(), TIP);
+        peer.break_connections();
+        let pull = BlockService::<TestBlock>::pull_blocks_to_tip(&mut client, &[TestId(1)]);
+        assert!(rt.block_on(pull).is_err());
+        assert_eq!(peer.connects(), 1);
+        // The next request reconnects.
+        let pull = BlockService::<TestBlock>::pull_blocks_to_tip(&mut client, &[TestId(1)]);
+        let blocks = rt.block_on(pull.and_then(Stream::collect)).unwrap();
+        assert_eq!(blocks, vec![TestBlock(2), TestBlock(3)]);
+        assert_eq!(peer.connects(), 2);
+    }
+
+    #[test]
+    fn error_status_keeps_the_connection() {
+        let mut rt = Runtime::new().unwrap();
+        let (peer, mut client) = start(&mut rt);
+        // The stub header service fails the request, which is
+        // reported with gRPC status code Unknown.
+        match rt.block_on(HeaderService::<TestBlock>::tip_header(&mut client)) {
+            Err(ref e) => match e.kind() {
+                core_client::ErrorKind::Rpc => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            Ok(header) => panic!("unexpected header {:?}", header),
+        }
+        assert_eq!(peer.connects(), 1);
+        assert!(!client.is_closed());
+        let tip = rt.block_on(BlockService::<TestBlock>::tip(&mut client));
+        assert_eq!(tip.unwrap(), TIP);
+        assert_eq!(peer.connects(), 1);
+    }
+}
@@ -0,0 +1,237 @@
+use super::{convert_error, ClientBuilder, Error};
+use crate::{
+    gen::reflection::{
+        client::ServerReflection, server_reflection_request::MessageRequest,
+        server_reflection_response::MessageResponse, ErrorResponse, ServerReflectionRequest,
+        ServerReflectionResponse,
+    },
+    peer::Origin,
+};
+
+use network_core::client as core_client;
+
+use futures::{future::Executor, sync::mpsc};
+use prost::Message;
+use tokio::io;
+use tokio::prelude::*;
+use tower_grpc::{BoxBody, Request};
+use tower_h2::client::{Background, Connection};
+use tower_http::add_origin::AddOrigin;
+
+use std::collections::VecDeque;
+
+/// Client for the gRPC server reflection service, for diagnostics.
+///
+/// Server reflection lets a client discover the services and methods
+/// served by a peer it knows nothing about. Most production nodes do
+/// not serve reflection, in which case the requests fail with an error
+/// of kind `ErrorKind::Unimplemented`.
+///
+/// A `ReflectionClient` makes its own connection to the peer, which
+/// can be established with the settings of a `ClientBuilder`. Its
+/// requests are not authenticated and have no timeout.
+pub struct ReflectionClient<S, E> {
+    inner: ServerReflection<AddOrigin<Connection<S, E, BoxBody>>>,
+}
+
+/// A service served by a peer, as reported by server reflection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// The fully qualified name of the service,
+    /// e.g. `iohk.chain.node.Node`.
+    pub name: String,
+    /// The names of the methods of the service. The list is empty
+    /// if the peer has not provided the descriptor of the service.
+    pub methods: Vec<String>,
+}
+
+impl ClientBuilder {
+    /// Connects a reflection client to the peer with the settings of
+    /// this builder, using the origin derived from the peer's
+    /// connection details.
+    pub fn build_reflection_connect<P, S, E>(
+        &self,
+        peer: P,
+        executor: E,
+    ) -> impl Future<Item = ReflectionClient<S, E>, Error = Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+        S: AsyncRead + AsyncWrite,
+        E: Executor<Background<S, BoxBody>> + Clone,
+    {
+        let origin = peer.origin();
+        self.connect_service(peer, origin, executor)
+            .map(|conn| ReflectionClient {
+                inner: ServerReflection::new(conn),
+            })
+    }
+}
+
+impl<S, E> ReflectionClient<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Connects to the peer, using the origin derived from the peer's
+    /// connection details for requests.
+    pub fn connect<P>(peer: P, executor: E) -> impl Future<Item = Self, Error = Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+    {
+        ClientBuilder::new().build_reflection_connect(peer, executor)
+    }
+
+    /// Lists the services served by the peer with the names of
+    /// their methods.
+    ///
+    /// The list of services and the descriptor of each service are
+    /// requested in a single reflection stream.
+    pub fn list_services(
+        &mut self,
+    ) -> impl Future<Item = Vec<ServiceInfo>, Error = core_client::Error> {
+        let (requests, body) = mpsc::unbounded();
+        let mut lookup = ServiceLookup {
+            requests: Some(requests),
+            listed: false,
+            pending: VecDeque::new(),
+            services: Vec::new(),
+        };
+        lookup.send(MessageRequest::ListServices(String::new()));
+        // The receiver of an unbounded channel never fails.
+        let body = body.map_err(|()| tower_grpc::Error::Inner(()));
+        self.inner
+            .server_reflection_info(Request::new(body))
+            .map_err(convert_error)
+            .and_then(move |res| {
+                res.into_inner()
+                    .map_err(convert_error)
+                    .fold(lookup, ServiceLookup::process)
+            })
+            .and_then(ServiceLookup::finish)
+    }
+}
+
+// State of a reflection stream that lists the services, then requests
+// the descriptor of each service in turn.
+struct ServiceLookup {
+    requests: Option<mpsc::UnboundedSender<ServerReflectionRequest>>,
+    listed: bool,
+    // Names of the services with descriptor requests awaiting responses,
+    // which are sent by the server in the order of the requests.
+    pending: VecDeque<String>,
+    services: Vec<ServiceInfo>,
+}
+
+impl ServiceLookup {
+    fn send(&mut self, request: MessageRequest) {
+        if let Some(ref requests) = self.requests {
+            let request = ServerReflectionRequest {
+                host: String::new(),
+                message_request: Some(request),
+            };
+            let _ = requests.unbounded_send(request);
+        }
+    }
+
+    fn process(mut self, response: ServerReflectionResponse) -> Result<Self, core_client::Error> {
+        match response.message_response {
+            Some(MessageResponse::ListServicesResponse(list)) if !self.listed => {
+                self.listed = true;
+                for service in list.service {
+                    self.send(MessageRequest::FileContainingSymbol(service.name.clone()));
+                    self.pending.push_back(service.name);
+                }
+            }
+            Some(MessageResponse::FileDescriptorResponse(res)) if !self.pending.is_empty() => {
+                let name = self.pending.pop_front().unwrap();
+                let methods = find_methods(&res.file_descriptor_proto, &name)?;
+                self.services.push(ServiceInfo { name, methods });
+            }
+            Some(MessageResponse::ErrorResponse(e)) => match self.pending.pop_front() {
+                Some(name) => self.services.push(ServiceInfo {
+                    name,
+                    methods: Vec::new(),
+                }),
+                None => return Err(reflection_error(e)),
+            },
+            _ => {
+                return Err(core_client::Error::new(
+                    core_client::ErrorKind::Format,
+                    "unexpected reflection response",
+                ));
+            }
+        }
+        if self.listed && self.pending.is_empty() {
+            // Ending the request stream lets the server end the response.
+            self.requests = None;
+        }
+        Ok(self)
+    }
+
+    fn finish(self) -> Result<Vec<ServiceInfo>, core_client::Error> {
+        if !self.listed || !self.pending.is_empty() {
+            return Err(core_client::Error::new(
+                core_client::ErrorKind::Format,
+                "the reflection stream ended before all responses were received",
+            ));
+        }
+        Ok(self.services)
+    }
+}
+
+fn reflection_error(e: ErrorResponse) -> core_client::Error {
+    // The error codes are gRPC status codes.
+    let kind = match e.error_code {
+        5 => core_client::ErrorKind::NotFound,
+        12 => core_client::ErrorKind::Unimplemented,
+        _ => core_client::ErrorKind::Rpc,
+    };
+    let msg = format!("reflection error {}: {}", e.error_code, e.error_message);
+    core_client::Error::new(kind, msg)
+}
+
+// Looks up the service in the serialized file descriptors and
+// returns the names of its methods.
+fn find_methods(files: &[Vec<u8>], service: &str) -> Result<Vec<String>, core_client::Error> {
+    for file in files {
+        let file = FileDescriptorProto::decode(&file[..])
+            .map_err(|e| core_client::Error::new(core_client::ErrorKind::Format, e))?;
+        for desc in file.service {
+            let name = if file.package.is_empty() {
+                desc.name
+            } else {
+                format!("{}.{}", file.package, desc.name)
+            };
+            if name == service {
+                return Ok(desc.method.into_iter().map(|method| method.name).collect());
+            }
+        }
+    }
+    Ok(Vec::new())
+}
+
+// The parts of the descriptor messages from google/protobuf/descriptor.proto
+// needed to list the methods of a service. Other fields are skipped
+// when decoding.
+
+#[derive(Clone, PartialEq, Message)]
+struct FileDescriptorProto {
+    #[prost(string, tag = "2")]
+    package: String,
+    #[prost(message, repeated, tag = "6")]
+    service: Vec<ServiceDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ServiceDescriptorProto {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(message, repeated, tag = "2")]
+    method: Vec<MethodDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MethodDescriptorProto {
+    #[prost(string, tag = "1")]
+    name: String,
+}
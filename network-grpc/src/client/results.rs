@@ -0,0 +1,64 @@
+use super::{ConvertResponse, ResponseStream, StreamHandle};
+
+use network_core::client as core_client;
+
+use futures::prelude::*;
+
+/// Stream adapter that yields the failures to deserialize individual
+/// items of a `ResponseStream` as items, rather than failing the stream.
+///
+/// Each message received in the stream yields an `Ok` item with
+/// the deserialized value, or an `Err` item with the error that
+/// the conversion of the message failed with, and the stream continues
+/// with the next message. Such an error has kind `ErrorKind::Format`
+/// and records the position of the item in the stream. This is meant
+/// for best-effort tools such as archivers, which would rather record
+/// a malformed block and go on than abort the download.
+///
+/// All other errors terminate the stream as they do without
+/// the adapter: connection and transport failures, a non-OK status
+/// the peer ends the stream with, exceeding the limit set with
+/// `ResponseStream::with_max_total_bytes`, and cancellation with
+/// the client's cancellation token.
+///
+/// An adapter is created with the `results` method of `ResponseStream`.
+pub struct Results<T, R> {
+    inner: ResponseStream<T, R>,
+}
+
+impl<T, R> Results<T, R>
+where
+    R: prost::Message + Default + ConvertResponse<T>,
+{
+    pub(super) fn new(inner: ResponseStream<T, R>) -> Self {
+        Results { inner }
+    }
+
+    /// Returns a handle that can be used to cancel the stream.
+    pub fn handle(&self) -> StreamHandle {
+        self.inner.handle()
+    }
+
+    /// Returns the number of items the server has announced it will
+    /// send in this stream, or `None` if the server did not provide a hint.
+    pub fn size_hint(&self) -> Option<u64> {
+        self.inner.size_hint()
+    }
+
+    /// Consumes the adapter, returning the wrapped stream.
+    pub fn into_inner(self) -> ResponseStream<T, R> {
+        self.inner
+    }
+}
+
+impl<T, R> Stream for Results<T, R>
+where
+    R: prost::Message + Default + ConvertResponse<T>,
+{
+    type Item = Result<T, core_client::Error>;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, core_client::Error> {
+        self.inner.poll_result()
+    }
+}
@@ -0,0 +1,59 @@
+use super::{Client, NodeClient, ResponseStream, ResponseStreamFuture};
+use crate::gen;
+
+use chain_core::property::{Block, Deserialize};
+
+use bytes::Bytes;
+use futures::future::Executor;
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+impl<T, R> ResponseStream<T, R> {
+    /// Returns the latest continuation token that the peer has sent
+    /// with the items received so far.
+    ///
+    /// The token is opaque to the client. It can be passed to
+    /// `Client::pull_blocks_resume`, possibly on a new connection to
+    /// the same peer, to continue the stream after the item that has
+    /// carried it, when the stream has failed or has been dropped.
+    /// Returns `None` if the peer has not sent a token, which peers
+    /// that do not support resumption never do.
+    pub fn continuation_token(&self) -> Option<&Bytes> {
+        self.items.continuation_token.as_ref()
+    }
+}
+
+impl<S, E> Client<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Continues a stream of blocks from a continuation token
+    /// obtained with `ResponseStream::continuation_token`.
+    ///
+    /// The peer streams the blocks that the interrupted stream would
+    /// have yielded after the block that has carried the token.
+    /// The token is only meaningful to the peer that has issued it,
+    /// and the peer may stop recognizing it after some time; the
+    /// request then fails with an error of kind `ErrorKind::Rpc`,
+    /// and the caller needs to fall back to the original request.
+    /// A peer that does not support resumption fails the request
+    /// with an error of kind `ErrorKind::Unimplemented`.
+    pub fn pull_blocks_resume<T>(
+        &mut self,
+        token: &Bytes,
+    ) -> ResponseStreamFuture<T, gen::node::Block>
+    where
+        T: Block,
+        <T as Deserialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let req = gen::node::PullBlocksResumeRequest {
+            token: token.to_vec(),
+        };
+        self.streaming_call("PullBlocksResume", req, |node, req| {
+            node.pull_blocks_resume(req)
+        })
+    }
+}
@@ -0,0 +1,180 @@
+use network_core::client as core_client;
+
+use std::{cmp, time::Duration};
+
+/// Settings for retrying idempotent unary requests that fail with
+/// a transient error.
+///
+/// A request is retried when the peer responds with gRPC status
+/// `UNAVAILABLE` or `RESOURCE_EXHAUSTED`, which typically indicate
+/// that the peer is briefly overloaded. Only idempotent unary requests,
+/// such as `tip`, are eligible; streaming requests are never retried.
+///
+/// The delay before each retry starts at the base delay and doubles
+/// with every failed attempt, up to the maximum delay. A random fraction of up to `jitter`
+/// of the delay is subtracted from it, so that clients that failed
+/// at the same time do not retry in lockstep. If the error carries
+/// a retry hint, as returned by `Error::retry_after`, the retry is not
+/// made earlier than the hint says. The client reads such hints from
+/// the `grpc-retry-delay` and `retry-after` headers of the response.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Creates a retry policy allowing up to `max_attempts` attempts
+    /// of a request, including the first one, where the delay before
+    /// the first retry is `base_delay` and each subsequent delay is
+    /// doubled up to `max_delay`.
+    ///
+    /// `jitter` is the fraction of each delay that is randomized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `jitter` is not in the range from 0.0 to 1.0 inclusive.
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration, jitter: f64) -> Self {
+        assert!(
+            jitter >= 0.0 && jitter <= 1.0,
+            "jitter should be in the range 0.0..=1.0"
+        );
+        RetryPolicy {
+            max_attempts,
+            base_delay,
+            max_delay,
+            jitter,
+        }
+    }
+
+    pub(super) fn is_retryable(&self, err: &core_client::Error) -> bool {
+        match err.kind() {
+            core_client::ErrorKind::Unavailable | core_client::ErrorKind::ResourceExhausted => true,
+            _ => false,
+        }
+    }
+
+    // Returns the delay before the next attempt after the given number of
+    // failed attempts and the last error, or None if no more attempts
+    // should be made.
+    pub(super) fn delay_after(
+        &self,
+        failed_attempts: u32,
+        err: &core_client::Error,
+    ) -> Option<Duration> {
+        let delay = self.delay(failed_attempts)?;
+        match err.retry_after() {
+            Some(hint) if hint > delay => Some(hint),
+            _ => Some(delay),
+        }
+    }
+
+    fn delay(&self, failed_attempts: u32) -> Option<Duration> {
+        if failed_attempts >= self.max_attempts {
+            return None;
+        }
+        let factor = 1u32
+            .checked_shl(failed_attempts - 1)
+            .unwrap_or(u32::max_value());
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay);
+        let delay = cmp::min(delay, self.max_delay);
+        let nanos = delay
+            .as_secs()
+            .saturating_mul(1_000_000_000)
+            .saturating_add(u64::from(delay.subsec_nanos()));
+        let jitter = (nanos as f64 * self.jitter * rand::random::<f64>()) as u64;
+        Some(Duration::from_nanos(nanos.saturating_sub(jitter)))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(3, Duration::from_millis(100), Duration::from_secs(10), 0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use network_core::client::ErrorKind;
+
+    fn error(kind: ErrorKind) -> core_client::Error {
+        core_client::Error::new(kind, "test error")
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(
+            max_attempts,
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            0.0,
+        )
+    }
+
+    #[test]
+    fn only_transient_errors_are_retryable() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&error(ErrorKind::Unavailable)));
+        assert!(policy.is_retryable(&error(ErrorKind::ResourceExhausted)));
+        assert!(!policy.is_retryable(&error(ErrorKind::NotFound)));
+        assert!(!policy.is_retryable(&error(ErrorKind::Timeout)));
+        assert!(!policy.is_retryable(&error(ErrorKind::Rpc)));
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_maximum() {
+        let policy = policy(10);
+        let err = error(ErrorKind::Unavailable);
+        let delays: Vec<_> = (1..10)
+            .map(|attempts| policy.delay_after(attempts, &err).unwrap())
+            .collect();
+        let expected: Vec<_> = [100, 200, 400, 800, 1000, 1000, 1000, 1000, 1000]
+            .iter()
+            .map(|&millis| Duration::from_millis(millis))
+            .collect();
+        assert_eq!(delays, expected);
+        assert_eq!(policy.delay_after(10, &err), None);
+    }
+
+    #[test]
+    fn delay_saturates_instead_of_giving_up() {
+        let policy = policy(u32::max_value());
+        let err = error(ErrorKind::Unavailable);
+        for &attempts in &[32, 33, 64, 1000, u32::max_value() - 1] {
+            assert_eq!(
+                policy.delay_after(attempts, &err),
+                Some(Duration::from_secs(1))
+            );
+        }
+    }
+
+    #[test]
+    fn delay_honors_a_longer_retry_hint() {
+        let policy = policy(3);
+        let err = error(ErrorKind::ResourceExhausted).with_retry_after(Duration::from_secs(5));
+        assert_eq!(policy.delay_after(1, &err), Some(Duration::from_secs(5)));
+        let err = error(ErrorKind::ResourceExhausted).with_retry_after(Duration::from_millis(50));
+        assert_eq!(
+            policy.delay_after(1, &err),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(policy.delay_after(3, &err), None);
+    }
+
+    #[test]
+    fn jitter_shortens_the_delay_by_up_to_its_fraction() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100), Duration::from_secs(1), 0.5);
+        let err = error(ErrorKind::Unavailable);
+        for _ in 0..100 {
+            let delay = policy.delay_after(1, &err).unwrap();
+            assert!(delay <= Duration::from_millis(100));
+            assert!(delay >= Duration::from_millis(50));
+        }
+    }
+}
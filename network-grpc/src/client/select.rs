@@ -0,0 +1,340 @@
+use super::Client;
+
+use chain_core::property::Block;
+use network_core::client::block::BlockService;
+
+use futures::prelude::*;
+use tokio::timer::Delay;
+
+use std::{
+    cmp::Ordering,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_DEMOTION: Duration = Duration::from_secs(30);
+
+/// The latest measurements of a peer taken by a `PeerSelector`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerStats {
+    /// The length of the chain of the peer, as the height of its tip.
+    pub chain_length: u64,
+    /// The smoothed round-trip time of the requests probing the peer.
+    pub rtt: Duration,
+}
+
+/// A set of clients ranked by how well the peers serve as a source
+/// of blocks for synchronization.
+///
+/// The selector probes the peers periodically with the `chain_length`
+/// request, and ranks the peers that have responded by the score of
+///
+/// `-(height_weight * blocks_behind + rtt_weight * rtt_millis)`
+///
+/// where `blocks_behind` is how far the chain of the peer is behind
+/// the longest chain among the peers, and `rtt_millis` is the smoothed
+/// round-trip time of the probes in milliseconds. By default, a block
+/// counts as much as 20 milliseconds of round-trip time. A peer whose
+/// probe fails, or whose client reports the connection as closed, is
+/// demoted for a while: it is ranked below all other peers that have
+/// responded, and is only selected if there is no other choice.
+///
+/// The probes are driven by `poll_probes`, which needs to be called
+/// from a task running on a runtime with a timer, e.g. by a task
+/// that repeatedly waits on
+/// `futures::future::poll_fn(|| Ok::<_, ()>(selector.poll_probes()))`.
+/// A probe is subject to the request timeout of the client; without
+/// a timeout, a peer that does not respond is not probed again until it
+/// does.
+pub struct PeerSelector<T, S, E>
+where
+    T: Block,
+    Client<S, E>: BlockService<T>,
+{
+    peers: Vec<Entry<T, S, E>>,
+    height_weight: f64,
+    rtt_weight: f64,
+    probe_interval: Duration,
+    demotion: Duration,
+    next_round: Delay,
+    // Whether a round of probes is in progress.
+    probing: bool,
+    _phantom: PhantomData<T>,
+}
+
+struct Entry<T, S, E>
+where
+    T: Block,
+    Client<S, E>: BlockService<T>,
+{
+    client: Client<S, E>,
+    stats: Option<PeerStats>,
+    demoted_until: Option<Instant>,
+    probe: Option<(
+        <Client<S, E> as BlockService<T>>::ChainLengthFuture,
+        Instant,
+    )>,
+}
+
+impl<T, S, E> Entry<T, S, E>
+where
+    T: Block,
+    Client<S, E>: BlockService<T>,
+{
+    fn poll_probe(&mut self, demotion: Duration) {
+        let res = match self.probe {
+            None => return,
+            Some((ref mut probe, started)) => match probe.poll() {
+                Ok(Async::NotReady) => return,
+                Ok(Async::Ready(chain_length)) => Ok((chain_length, started.elapsed())),
+                Err(e) => Err(e),
+            },
+        };
+        self.probe = None;
+        match res {
+            Ok((chain_length, sample)) => {
+                // Smoothed in the manner of the TCP round-trip time estimate.
+                let rtt = match self.stats {
+                    None => sample,
+                    Some(stats) => (stats.rtt * 7 + sample) / 8,
+                };
+                self.stats = Some(PeerStats { chain_length, rtt });
+                self.demoted_until = None;
+            }
+            Err(_) => {
+                self.demoted_until = Some(Instant::now() + demotion);
+            }
+        }
+    }
+
+    fn is_demoted(&self, now: Instant) -> bool {
+        !self.client.is_connected() || self.demoted_until.map_or(false, |until| until > now)
+    }
+}
+
+impl<T, S, E> PeerSelector<T, S, E>
+where
+    T: Block,
+    Client<S, E>: BlockService<T>,
+{
+    /// Creates a selector of the clients, to be ranked once
+    /// `poll_probes` has completed the first round of probes.
+    pub fn new(clients: Vec<Client<S, E>>) -> Self {
+        let mut selector = PeerSelector {
+            peers: Vec::with_capacity(clients.len()),
+            height_weight: 1.0,
+            rtt_weight: 0.05,
+            probe_interval: DEFAULT_PROBE_INTERVAL,
+            demotion: DEFAULT_DEMOTION,
+            next_round: Delay::new(Instant::now()),
+            probing: false,
+            _phantom: PhantomData,
+        };
+        for client in clients {
+            selector.add(client);
+        }
+        selector
+    }
+
+    /// Adds a client, which is probed with the next round of probes.
+    pub fn add(&mut self, client: Client<S, E>) {
+        self.peers.push(Entry {
+            client,
+            stats: None,
+            demoted_until: None,
+            probe: None,
+        });
+    }
+
+    /// Returns the number of clients in the selector.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Sets the weight of each block that the chain of a peer is behind
+    /// the longest chain in the score of the peer. The default is 1.
+    pub fn set_height_weight(&mut self, weight: f64) {
+        self.height_weight = weight;
+    }
+
+    /// Sets the weight of each millisecond of round-trip time in
+    /// the score of a peer. The default is 0.05.
+    pub fn set_rtt_weight(&mut self, weight: f64) {
+        self.rtt_weight = weight;
+    }
+
+    /// Sets the interval between the starts of rounds of probes.
+    /// The default is 10 seconds.
+    pub fn set_probe_interval(&mut self, interval: Duration) {
+        self.probe_interval = interval;
+    }
+
+    /// Sets the time for which a peer that has failed its probe
+    /// is demoted. The default is 30 seconds.
+    pub fn set_demotion(&mut self, demotion: Duration) {
+        self.demotion = demotion;
+    }
+
+    /// Drives the probes of the peers, starting a round of probes
+    /// when the probe interval has elapsed.
+    ///
+    /// Returns `Async::Ready` when a round of probes has completed,
+    /// with the ranking updated, and `Async::NotReady` otherwise,
+    /// in which case the current task is notified when there is
+    /// progress to make.
+    pub fn poll_probes(&mut self) -> Async<()> {
+        // A timer error only makes the round start early.
+        let due = match self.next_round.poll() {
+            Ok(Async::NotReady) => false,
+            Ok(Async::Ready(())) | Err(_) => true,
+        };
+        if due {
+            let now = Instant::now();
+            for peer in &mut self.peers {
+                if peer.probe.is_none() {
+                    let probe = BlockService::<T>::chain_length(&mut peer.client);
+                    peer.probe = Some((probe, now));
+                }
+            }
+            self.probing = true;
+            self.next_round.reset(now + self.probe_interval);
+            // Poll the timer to be notified when it expires.
+            let _ = self.next_round.poll();
+        }
+        let demotion = self.demotion;
+        let mut pending = false;
+        for peer in &mut self.peers {
+            peer.poll_probe(demotion);
+            pending |= peer.probe.is_some();
+        }
+        if self.probing && !pending {
+            self.probing = false;
+            return Async::Ready(());
+        }
+        Async::NotReady
+    }
+
+    /// Returns the client of the highest ranked peer, or `None` if
+    /// no peer has responded to a probe yet.
+    pub fn best_peer(&mut self) -> Option<&mut Client<S, E>> {
+        let now = Instant::now();
+        let candidates = self.peers.iter().enumerate().filter_map(|(i, peer)| {
+            peer.stats.map(|stats| Candidate {
+                index: i,
+                demoted: peer.is_demoted(now),
+                stats,
+            })
+        });
+        let index = best_candidate(candidates, self.height_weight, self.rtt_weight)?;
+        Some(&mut self.peers[index].client)
+    }
+
+    /// Returns the latest measurements of the peers that have responded
+    /// to a probe, with their origin URIs.
+    pub fn stats(&self) -> Vec<(&http::Uri, PeerStats)> {
+        self.peers
+            .iter()
+            .filter_map(|peer| peer.stats.map(|stats| (peer.client.origin(), stats)))
+            .collect()
+    }
+}
+
+struct Candidate {
+    index: usize,
+    demoted: bool,
+    stats: PeerStats,
+}
+
+fn best_candidate<I>(candidates: I, height_weight: f64, rtt_weight: f64) -> Option<usize>
+where
+    I: Iterator<Item = Candidate> + Clone,
+{
+    let longest = candidates.clone().map(|c| c.stats.chain_length).max()?;
+    let score = |stats: &PeerStats| {
+        let behind = (longest - stats.chain_length) as f64;
+        let rtt_millis =
+            stats.rtt.as_secs() as f64 * 1000.0 + f64::from(stats.rtt.subsec_micros()) / 1000.0;
+        -(height_weight * behind + rtt_weight * rtt_millis)
+    };
+    candidates
+        .map(|c| (c.index, !c.demoted, score(&c.stats)))
+        .max_by(|a, b| {
+            a.1.cmp(&b.1)
+                .then(a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal))
+        })
+        .map(|(index, ..)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientBuilder;
+    use crate::test_types::{serve, StubNode, TestBlock};
+
+    use futures::future;
+    use tokio::executor::DefaultExecutor;
+    use tokio::runtime::current_thread::Runtime;
+
+    fn candidate(index: usize, demoted: bool, chain_length: u64, rtt_millis: u64) -> Candidate {
+        Candidate {
+            index,
+            demoted,
+            stats: PeerStats {
+                chain_length,
+                rtt: Duration::from_millis(rtt_millis),
+            },
+        }
+    }
+
+    fn best(candidates: Vec<Candidate>, height_weight: f64, rtt_weight: f64) -> Option<usize> {
+        best_candidate(candidates.into_iter(), height_weight, rtt_weight)
+    }
+
+    #[test]
+    fn ranking_weighs_height_against_rtt() {
+        // One block behind weighs less than 100 ms of round-trip time.
+        let peers = || vec![candidate(0, false, 100, 200), candidate(1, false, 99, 100)];
+        assert_eq!(best(peers(), 1.0, 0.05), Some(1));
+        assert_eq!(best(peers(), 10.0, 0.05), Some(0));
+        assert_eq!(best(Vec::new(), 1.0, 0.05), None);
+    }
+
+    #[test]
+    fn demoted_peer_is_selected_only_as_last_resort() {
+        let peers = vec![candidate(0, true, 100, 10), candidate(1, false, 50, 500)];
+        assert_eq!(best(peers, 1.0, 0.05), Some(1));
+        let peers = vec![candidate(0, true, 100, 10), candidate(1, true, 50, 500)];
+        assert_eq!(best(peers, 1.0, 0.05), Some(0));
+    }
+
+    #[test]
+    fn peers_are_ranked_by_the_chain_length_they_serve() {
+        let mut rt = Runtime::new().unwrap();
+        let clients = vec![vec![0, 1, 2, 3], vec![0, 1, 2, 3, 4, 5]]
+            .into_iter()
+            .map(|heights| {
+                let peer = serve(&mut rt, StubNode::new(heights));
+                rt.block_on(ClientBuilder::new().build_connect(peer, DefaultExecutor::current()))
+                    .unwrap()
+            })
+            .collect();
+        let mut selector = PeerSelector::<TestBlock, _, _>::new(clients);
+        rt.block_on(future::poll_fn(|| Ok::<_, ()>(selector.poll_probes())))
+            .unwrap();
+        let mut lengths: Vec<_> = selector
+            .stats()
+            .into_iter()
+            .map(|(_, stats)| stats.chain_length)
+            .collect();
+        lengths.sort();
+        assert_eq!(lengths, vec![3, 5]);
+        let best = selector.best_peer().unwrap();
+        let length = rt.block_on(BlockService::<TestBlock>::chain_length(best));
+        assert_eq!(length.unwrap(), 5);
+    }
+}
@@ -0,0 +1,183 @@
+use futures::{try_ready, Async, Future, Poll};
+use http::{
+    header::{HeaderMap, RETRY_AFTER},
+    uri::{self, PathAndQuery, Uri},
+};
+use tower_service::Service;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+// The prefix of the request paths of the generated client, formed by
+// the fully qualified name of the Node service in node.proto.
+const NODE_SERVICE_PATH: &str = "/iohk.chain.node.Node/";
+
+// Returns the prefix of request paths for the service name, if the
+// name is valid in a request path.
+pub(super) fn path_prefix(service_name: &str) -> Option<Arc<str>> {
+    if service_name.is_empty() || service_name.contains(|c: char| c == '/' || c == '?') {
+        return None;
+    }
+    let prefix = format!("/{}/", service_name);
+    match format!("{}Method", prefix).parse::<PathAndQuery>() {
+        Ok(_) => Some(prefix.into()),
+        Err(_) => None,
+    }
+}
+
+// Service wrapper that sends the requests of the Node service to
+// a different service path, as configured with
+// `ClientBuilder::node_service_name`, and records the retry hints of
+// the responses.
+//
+// This needs to be public because it appears in the bounds of public
+// methods through the type of the generated client, but the module is
+// private and the type is not re-exported.
+#[derive(Clone)]
+pub struct ServicePath<T> {
+    inner: T,
+    prefix: Option<Arc<str>>,
+}
+
+impl<T> ServicePath<T> {
+    pub(super) fn new(inner: T, prefix: Option<Arc<str>>) -> Self {
+        ServicePath { inner, prefix }
+    }
+}
+
+impl<T, B, RB> Service<http::Request<B>> for ServicePath<T>
+where
+    T: Service<http::Request<B>, Response = http::Response<RB>>,
+{
+    type Response = T::Response;
+    type Error = T::Error;
+    type Future = ResponseFuture<T::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), T::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> T::Future {
+        let req = match self.prefix {
+            None => req,
+            Some(ref prefix) => rewrite_path(req, prefix),
+        };
+        let hint = req.extensions().get::<RetryHint>().cloned();
+        ResponseFuture {
+            inner: self.inner.call(req),
+            hint,
+        }
+    }
+}
+
+// Slot for the retry hint sent by the peer in the headers of
+// the response to a request. The status decoded by tower-grpc does not
+// retain the response headers, so the slot is passed in the extensions
+// of the request, where the service wrapper finds it and fills it in
+// when the response arrives, and the future of the request takes
+// the hint from it if the request fails.
+#[derive(Clone, Default)]
+pub(super) struct RetryHint(Arc<Mutex<Option<Duration>>>);
+
+impl RetryHint {
+    pub(super) fn attach<M>(&self, req: tower_grpc::Request<M>) -> tower_grpc::Request<M> {
+        let mut req = req.into_http();
+        req.extensions_mut().insert(self.clone());
+        tower_grpc::Request::from_http(req)
+    }
+
+    pub(super) fn take(&self) -> Option<Duration> {
+        self.0.lock().unwrap().take()
+    }
+
+    fn set(&self, delay: Option<Duration>) {
+        *self.0.lock().unwrap() = delay;
+    }
+}
+
+// Future of a response that records the retry hint of the response,
+// if the request has a slot for it. Public for the same reason as
+// `ServicePath`.
+pub struct ResponseFuture<F> {
+    inner: F,
+    hint: Option<RetryHint>,
+}
+
+impl<F, RB> Future for ResponseFuture<F>
+where
+    F: Future<Item = http::Response<RB>>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        let res = try_ready!(self.inner.poll());
+        if let Some(hint) = self.hint.take() {
+            hint.set(retry_hint(res.headers()));
+        }
+        Ok(Async::Ready(res))
+    }
+}
+
+const GRPC_RETRY_DELAY_HEADER: &str = "grpc-retry-delay";
+
+// Reads the retry hint from the headers of a response, which also carry
+// the status in a trailers-only response. The value of `grpc-retry-delay`
+// is encoded like that of `grpc-timeout`, while `retry-after` is
+// a number of seconds as in HTTP; the longer of the two is used.
+fn retry_hint(headers: &HeaderMap) -> Option<Duration> {
+    let grpc_delay = headers
+        .get(GRPC_RETRY_DELAY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_grpc_duration);
+    let http_delay = headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs);
+    grpc_delay.into_iter().chain(http_delay).max()
+}
+
+// Parses a duration in the encoding of the grpc-timeout header:
+// up to 8 digits followed by the unit.
+pub(super) fn parse_grpc_duration(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let digits = &value[..value.len() - unit.len_utf8()];
+    if digits.is_empty() || digits.len() > 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: u64 = digits.parse().ok()?;
+    let delay = match unit {
+        'n' => Duration::from_nanos(value),
+        'u' => Duration::from_micros(value),
+        'm' => Duration::from_millis(value),
+        'S' => Duration::from_secs(value),
+        'M' => Duration::from_secs(value * 60),
+        'H' => Duration::from_secs(value * 3600),
+        _ => return None,
+    };
+    Some(delay)
+}
+
+fn rewrite_path<B>(req: http::Request<B>, prefix: &str) -> http::Request<B> {
+    let method = {
+        let path = req.uri().path();
+        if !path.starts_with(NODE_SERVICE_PATH) {
+            return req;
+        }
+        path[NODE_SERVICE_PATH.len()..].to_owned()
+    };
+    let (mut head, body) = req.into_parts();
+    let mut parts = uri::Parts::from(head.uri);
+    // The prefix has been checked by `path_prefix` to form a valid
+    // path with a method name, and the method name comes from
+    // a valid path.
+    let path = format!("{}{}", prefix, method)
+        .parse::<PathAndQuery>()
+        .expect("rewritten request path should be valid");
+    parts.path_and_query = Some(path);
+    head.uri = Uri::from_parts(parts).expect("rewritten request URI should be valid");
+    http::Request::from_parts(head, body)
+}
@@ -0,0 +1,164 @@
+use super::{Client, ClientStreamingFuture, NodeClient, RequestStream};
+use crate::gen;
+
+use chain_core::property::{Serialize, Transaction};
+use network_core::client as core_client;
+
+use futures::{future::Executor, sync::mpsc, try_ready};
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+/// Sink for uploading transactions to the peer in a long-lived
+/// client-streaming request.
+///
+/// This sink is returned by `Client::upload_transactions`. Each
+/// transaction is serialized when the request body is polled for more
+/// data, and the sink holds at most one transaction that has not yet
+/// been taken for sending: `start_send` returns `AsyncSink::NotReady`
+/// until it has been, so a peer that keeps the HTTP/2 flow control
+/// window of the request closed slows the producer down, rather than
+/// having the transactions pile up in memory.
+///
+/// Closing the sink with `close` ends the request once the remaining
+/// transactions have been sent, and completes when the peer responds,
+/// acknowledging the upload with the number of transactions it has
+/// accepted, which can then be read with `accepted`. If the request
+/// fails, the next operation on the sink fails with the error.
+pub struct TransactionSink<T> {
+    sender: Option<mpsc::Sender<T>>,
+    response: ClientStreamingFuture<u64, gen::node::UploadTransactionsResponse>,
+    accepted: Option<u64>,
+}
+
+impl<T> TransactionSink<T> {
+    /// Returns the number of transactions accepted by the peer, once
+    /// the sink has been closed and the peer has responded.
+    pub fn accepted(&self) -> Option<u64> {
+        self.accepted
+    }
+
+    fn poll_response(&mut self) -> Poll<u64, core_client::Error> {
+        if let Some(accepted) = self.accepted {
+            return Ok(Async::Ready(accepted));
+        }
+        let accepted = try_ready!(self.response.poll());
+        self.accepted = Some(accepted);
+        Ok(Async::Ready(accepted))
+    }
+
+    // Checks that the request is still in progress while
+    // the sink is open.
+    fn poll_in_progress(&mut self) -> Result<(), core_client::Error> {
+        match self.poll_response()? {
+            Async::NotReady => Ok(()),
+            Async::Ready(_) => Err(core_client::Error::new(
+                core_client::ErrorKind::Rpc,
+                "the peer has responded to the upload before the sink was closed",
+            )),
+        }
+    }
+
+    // Returns the error that caused the request body to be dropped.
+    fn request_dropped(&mut self) -> core_client::Error {
+        self.sender = None;
+        match self.poll_in_progress() {
+            Err(e) => e,
+            Ok(()) => core_client::Error::new(
+                core_client::ErrorKind::Rpc,
+                "the upload request has been dropped",
+            ),
+        }
+    }
+}
+
+// The operations after the sink has been closed.
+fn sink_closed() -> core_client::Error {
+    core_client::Error::new(
+        core_client::ErrorKind::Internal,
+        "the transaction sink has been closed",
+    )
+}
+
+impl<T> Sink for TransactionSink<T> {
+    type SinkItem = T;
+    type SinkError = core_client::Error;
+
+    fn start_send(&mut self, item: T) -> StartSend<T, core_client::Error> {
+        self.poll_in_progress()?;
+        let res = match self.sender {
+            None => return Err(sink_closed()),
+            Some(ref mut sender) => sender.start_send(item),
+        };
+        res.map_err(|_| self.request_dropped())
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), core_client::Error> {
+        self.poll_in_progress()?;
+        let res = match self.sender {
+            None => return Err(sink_closed()),
+            Some(ref mut sender) => sender.poll_complete(),
+        };
+        res.map_err(|_| self.request_dropped())
+    }
+
+    fn close(&mut self) -> Poll<(), core_client::Error> {
+        let res = match self.sender {
+            None => Ok(Async::Ready(())),
+            Some(ref mut sender) => sender.poll_complete(),
+        };
+        match res {
+            Ok(Async::NotReady) => {
+                // Keep the request going while the last transaction
+                // is waiting to be taken.
+                self.poll_in_progress()?;
+                return Ok(Async::NotReady);
+            }
+            Ok(Async::Ready(())) => {}
+            Err(_) => return Err(self.request_dropped()),
+        }
+        // Dropping the sender ends the request body.
+        self.sender = None;
+        try_ready!(self.poll_response());
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<S, E> Client<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Starts uploading transactions to the peer, returning a sink
+    /// to feed them into.
+    ///
+    /// Unlike `TransactionService::submit_transactions`, which sends
+    /// a single batch, the request stays open for as long as the sink
+    /// is, so it suits relaying a continuous flow of transactions.
+    /// See `TransactionSink` for details.
+    pub fn upload_transactions<T>(&mut self) -> TransactionSink<T>
+    where
+        T: Transaction + Send + 'static,
+        <T as Serialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(0);
+        // The receiving end of a channel never fails.
+        let transactions = receiver.map_err(|()| {
+            core_client::Error::new(
+                core_client::ErrorKind::Internal,
+                "the transaction channel has failed",
+            )
+        });
+        let stream = RequestStream::new(transactions, |content| gen::node::Transaction { content });
+        let context = self.request_context("UploadTransactions");
+        let req = context.hint_request(self.new_request(stream));
+        let future = self.authorized(req, |node, req| node.upload_transactions(req));
+        let response = ClientStreamingFuture::new(future).with_context(context);
+        TransactionSink {
+            sender: Some(sender),
+            response,
+            accepted: None,
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use super::{
+    empty_content, serialize_to_vec, take_block_continuation_token, verify_block_checksum, Client,
+    ConvertResponse, NodeClient, ResponseStream, ResponseStreamFuture,
+};
+use crate::gen;
+
+use chain_core::property::{Block, Deserialize, Serialize};
+use network_core::client as core_client;
+
+use bytes::Bytes;
+use futures::{
+    future::{self, Executor},
+    stream,
+};
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+/// Stream returned by `Client::pull_blocks_to_tip_with_size`.
+pub type BlockWithSizeStream<T> =
+    stream::Map<ResponseStream<WithSize<T>, gen::node::Block>, fn(WithSize<T>) -> (T, usize)>;
+
+/// Future that resolves to a `BlockWithSizeStream`.
+pub type BlockWithSizeStreamFuture<T> = future::Map<
+    ResponseStreamFuture<WithSize<T>, gen::node::Block>,
+    fn(ResponseStream<WithSize<T>, gen::node::Block>) -> BlockWithSizeStream<T>,
+>;
+
+/// Stream returned by `Client::pull_blocks_to_tip_sizes`.
+pub type BlockSizeStream =
+    stream::Map<ResponseStream<BlockSize, gen::node::Block>, fn(BlockSize) -> usize>;
+
+/// Future that resolves to a `BlockSizeStream`.
+pub type BlockSizeStreamFuture = future::Map<
+    ResponseStreamFuture<BlockSize, gen::node::Block>,
+    fn(ResponseStream<BlockSize, gen::node::Block>) -> BlockSizeStream,
+>;
+
+// A block decoded along with the size of its serialized content.
+// A tuple cannot be the target of a conversion of block messages,
+// because it would conflict with the conversion into any `Block`.
+pub struct WithSize<T>(T, usize);
+
+impl<T> WithSize<T> {
+    fn into_tuple(self) -> (T, usize) {
+        (self.0, self.1)
+    }
+}
+
+fn into_tuples<T>(stream: ResponseStream<WithSize<T>, gen::node::Block>) -> BlockWithSizeStream<T> {
+    stream.map(WithSize::into_tuple as fn(_) -> _)
+}
+
+impl<T> ConvertResponse<WithSize<T>> for gen::node::Block
+where
+    T: Block,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+{
+    fn convert_response(self) -> Result<WithSize<T>, core_client::Error> {
+        let size = self.content.len();
+        let block = ConvertResponse::<T>::convert_response(self)?;
+        Ok(WithSize(block, size))
+    }
+
+    fn verify_checksum(&self) -> Result<(), core_client::Error> {
+        ConvertResponse::<T>::verify_checksum(self)
+    }
+
+    fn take_item_id(&mut self) -> Option<Vec<u8>> {
+        ConvertResponse::<T>::take_item_id(self)
+    }
+
+    fn take_continuation_token(&mut self) -> Option<Bytes> {
+        ConvertResponse::<T>::take_continuation_token(self)
+    }
+}
+
+// The size of the serialized content of a block message,
+// with the content discarded undecoded.
+pub struct BlockSize(usize);
+
+impl BlockSize {
+    fn into_size(self) -> usize {
+        self.0
+    }
+}
+
+fn into_sizes(stream: ResponseStream<BlockSize, gen::node::Block>) -> BlockSizeStream {
+    stream.map(BlockSize::into_size as fn(_) -> _)
+}
+
+impl ConvertResponse<BlockSize> for gen::node::Block {
+    fn convert_response(self) -> Result<BlockSize, core_client::Error> {
+        if self.content.is_empty() {
+            return Err(empty_content("block"));
+        }
+        Ok(BlockSize(self.content.len()))
+    }
+
+    fn verify_checksum(&self) -> Result<(), core_client::Error> {
+        verify_block_checksum(self)
+    }
+
+    fn take_continuation_token(&mut self) -> Option<Bytes> {
+        take_block_continuation_token(self)
+    }
+}
+
+impl<S, E> Client<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the peer up to the tip of its chain, each along with
+    /// the size in bytes of its serialized content as received.
+    ///
+    /// This is the same request as `BlockService::pull_blocks_to_tip`.
+    /// The size is taken from the message before the block is
+    /// deserialized, so it does not depend on the block type.
+    pub fn pull_blocks_to_tip_with_size<T>(
+        &mut self,
+        from: &[T::Id],
+    ) -> BlockWithSizeStreamFuture<T>
+    where
+        T: Block,
+        <T as Deserialize>::Error: Send + Sync + 'static,
+        <T::Id as Serialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let future = match serialize_to_vec(from) {
+            Ok(from) => {
+                let req = gen::node::PullBlocksToTipRequest { from, known: None };
+                self.streaming_call("PullBlocksToTip", req, |node, req| {
+                    node.pull_blocks_to_tip(req)
+                })
+            }
+            Err(e) => ResponseStreamFuture::error(e),
+        };
+        future.map(into_tuples as fn(_) -> _)
+    }
+
+    /// Retrieves the sizes in bytes of the serialized content of
+    /// the blocks following the latest of the `from` blocks known to
+    /// the peer up to the tip of its chain.
+    ///
+    /// This is the same request as `BlockService::pull_blocks_to_tip`,
+    /// so the blocks are transferred in full, but they are not
+    /// deserialized. It is meant for estimating the storage needed
+    /// for a chain before committing to pulling it.
+    pub fn pull_blocks_to_tip_sizes<Id>(&mut self, from: &[Id]) -> BlockSizeStreamFuture
+    where
+        Id: Serialize,
+        Id::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let future = match serialize_to_vec(from) {
+            Ok(from) => {
+                let req = gen::node::PullBlocksToTipRequest { from, known: None };
+                self.streaming_call("PullBlocksToTip", req, |node, req| {
+                    node.pull_blocks_to_tip(req)
+                })
+            }
+            Err(e) => ResponseStreamFuture::error(e),
+        };
+        future.map(into_sizes as fn(_) -> _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockSize;
+    use crate::client::ConvertResponse;
+    use crate::gen;
+
+    use network_core::client as core_client;
+
+    fn block(content: Vec<u8>) -> gen::node::Block {
+        gen::node::Block {
+            content,
+            id: Vec::new(),
+            serve_info: None,
+            checksum: Vec::new(),
+            continuation_token: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn size_is_the_length_of_the_content() {
+        let size = ConvertResponse::<BlockSize>::convert_response(block(vec![0; 42]));
+        assert_eq!(size.unwrap().into_size(), 42);
+        match ConvertResponse::<BlockSize>::convert_response(block(Vec::new())) {
+            Err(ref e) => match e.kind() {
+                core_client::ErrorKind::Format => {}
+                kind => panic!("unexpected error kind {:?}", kind),
+            },
+            Ok(_) => panic!("an empty block should fail to convert"),
+        }
+    }
+}
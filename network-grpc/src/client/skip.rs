@@ -0,0 +1,199 @@
+use super::{
+    deserialize_bytes, empty_content, serialize_to_vec, take_block_continuation_token,
+    verify_block_checksum, Client, ConvertResponse, NodeClient, ResponseStream,
+    ResponseStreamFuture, StreamHandle,
+};
+use crate::gen;
+
+use chain_core::property::{Block, Deserialize, Serialize};
+use network_core::client as core_client;
+
+use bytes::Bytes;
+use futures::{future::Executor, try_ready};
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::marker::PhantomData;
+
+/// Stream of blocks that skips the blocks the caller already has.
+///
+/// This stream is returned by `Client::pull_blocks_to_tip_skipping_known`.
+/// The predicate is evaluated on the block identifier that the peer
+/// sends alongside each block, and a block for which it returns true is
+/// dropped without being deserialized. A block that is not skipped is
+/// checked to have the identifier sent by the peer, failing the stream
+/// with an error of kind `ErrorKind::Format` if it does not.
+///
+/// If the peer does not send the identifier of a block, the block is
+/// deserialized and the predicate is evaluated on its identifier, so
+/// the same blocks are skipped, only at the cost of decoding them.
+pub struct SkipKnownBlocks<T, F> {
+    inner: ResponseStream<UndecodedBlock, gen::node::Block>,
+    is_known: F,
+    skipped: u64,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, F> SkipKnownBlocks<T, F> {
+    /// Returns a handle that can be used to cancel the stream.
+    pub fn handle(&self) -> StreamHandle {
+        self.inner.handle()
+    }
+
+    /// Returns the number of blocks the server has announced it will
+    /// send in this stream, including the blocks to be skipped, or `None`
+    /// if the server did not provide a hint.
+    pub fn size_hint(&self) -> Option<u64> {
+        self.inner.size_hint()
+    }
+
+    /// Returns the number of blocks skipped so far.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+impl<T, F> Stream for SkipKnownBlocks<T, F>
+where
+    T: Block,
+    F: FnMut(&T::Id) -> bool,
+    <T as Deserialize>::Error: Send + Sync + 'static,
+    <T::Id as Deserialize>::Error: Send + Sync + 'static,
+{
+    type Item = T;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, core_client::Error> {
+        loop {
+            let block = match try_ready!(self.inner.poll()) {
+                None => return Ok(Async::Ready(None)),
+                Some(block) => block,
+            };
+            if block.id.is_empty() {
+                let decoded: T = deserialize_bytes(&block.content)?;
+                if (self.is_known)(&decoded.id()) {
+                    self.skipped += 1;
+                    continue;
+                }
+                return Ok(Async::Ready(Some(decoded)));
+            }
+            let id: T::Id = deserialize_bytes(&block.id)?;
+            if (self.is_known)(&id) {
+                self.skipped += 1;
+                continue;
+            }
+            let decoded: T = deserialize_bytes(&block.content)?;
+            if decoded.id() != id {
+                return Err(core_client::Error::new(
+                    core_client::ErrorKind::Format,
+                    format!("block {:?} was sent with identifier {:?}", decoded.id(), id),
+                ));
+            }
+            return Ok(Async::Ready(Some(decoded)));
+        }
+    }
+}
+
+/// Future that resolves to a `SkipKnownBlocks` stream.
+pub struct SkipKnownBlocksFuture<T, F> {
+    inner: ResponseStreamFuture<UndecodedBlock, gen::node::Block>,
+    is_known: Option<F>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, F> SkipKnownBlocksFuture<T, F> {
+    /// Returns a handle that can be used to cancel the request.
+    ///
+    /// If the request is canceled before the response arrives, the future
+    /// resolves to an empty stream.
+    pub fn handle(&self) -> StreamHandle {
+        self.inner.handle()
+    }
+}
+
+impl<T, F> Future for SkipKnownBlocksFuture<T, F> {
+    type Item = SkipKnownBlocks<T, F>;
+    type Error = core_client::Error;
+
+    fn poll(&mut self) -> Poll<SkipKnownBlocks<T, F>, core_client::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let is_known = self
+            .is_known
+            .take()
+            .expect("polled SkipKnownBlocksFuture after completion");
+        Ok(Async::Ready(SkipKnownBlocks {
+            inner,
+            is_known,
+            skipped: 0,
+            _phantom: PhantomData,
+        }))
+    }
+}
+
+// A block message with the fields not yet deserialized.
+pub struct UndecodedBlock {
+    id: Vec<u8>,
+    content: Vec<u8>,
+}
+
+impl ConvertResponse<UndecodedBlock> for gen::node::Block {
+    fn convert_response(self) -> Result<UndecodedBlock, core_client::Error> {
+        if self.content.is_empty() {
+            return Err(empty_content("block"));
+        }
+        Ok(UndecodedBlock {
+            id: self.id,
+            content: self.content,
+        })
+    }
+
+    fn verify_checksum(&self) -> Result<(), core_client::Error> {
+        verify_block_checksum(self)
+    }
+
+    fn take_continuation_token(&mut self) -> Option<Bytes> {
+        take_block_continuation_token(self)
+    }
+}
+
+impl<S, E> Client<S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the peer up to the tip of its chain, skipping the blocks
+    /// for which `is_known` returns true.
+    ///
+    /// This is the same request as `BlockService::pull_blocks_to_tip`.
+    /// It is meant for catching up with blocks some of which have already
+    /// been received from other peers: see `SkipKnownBlocks` for how
+    /// the blocks are skipped.
+    pub fn pull_blocks_to_tip_skipping_known<T, F>(
+        &mut self,
+        from: &[T::Id],
+        is_known: F,
+    ) -> SkipKnownBlocksFuture<T, F>
+    where
+        T: Block,
+        F: FnMut(&T::Id) -> bool,
+        <T::Id as Serialize>::Error: Send + Sync + 'static,
+        NodeClient<S, E>: Clone + Send + 'static,
+    {
+        let inner = match serialize_to_vec(from) {
+            Ok(from) => {
+                let req = gen::node::PullBlocksToTipRequest { from, known: None };
+                self.streaming_call("PullBlocksToTip", req, |node, req| {
+                    node.pull_blocks_to_tip(req)
+                })
+            }
+            Err(e) => ResponseStreamFuture::error(e),
+        };
+        SkipKnownBlocksFuture {
+            inner,
+            is_known: Some(is_known),
+            _phantom: PhantomData,
+        }
+    }
+}
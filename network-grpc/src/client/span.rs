@@ -0,0 +1,84 @@
+// Tracing spans of connections and requests.
+//
+// With the `tracing` feature enabled, every connection attempt and
+// every request gets a span with the fields `peer`, the origin URI of
+// the peer, `method`, the name of the gRPC method or "connect", and
+// `request_id`, a number unique within the process. When the request
+// completes, `status` is recorded as "ok", "canceled", or the error
+// message. Response streams keep the span of the request that opened
+// them and record the number of `items` yielded when they terminate.
+//
+// Without the feature, `RequestSpan` is an empty type and its methods
+// do nothing.
+
+use std::fmt::Display;
+
+#[cfg(feature = "tracing")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "tracing")]
+static NEXT_REQUEST_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
+pub(super) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl RequestSpan {
+    #[cfg(feature = "tracing")]
+    pub fn new(peer: &http::Uri, method: &'static str) -> Self {
+        let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed) as u64;
+        let span = tracing::debug_span!(
+            "grpc_request",
+            peer = %peer,
+            method = method,
+            request_id = request_id,
+            items = tracing::field::Empty,
+            status = tracing::field::Empty,
+        );
+        RequestSpan { span }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub fn new(_peer: &http::Uri, _method: &'static str) -> Self {
+        RequestSpan {}
+    }
+
+    // Runs the closure, typically polling a future or a stream,
+    // within the span.
+    pub fn in_scope<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        #[cfg(feature = "tracing")]
+        let _entered = self.span.enter();
+        f()
+    }
+
+    pub fn record_status<T, E: Display>(&self, result: &Result<T, E>) {
+        #[cfg(feature = "tracing")]
+        match result {
+            Ok(_) => {
+                self.span.record("status", &"ok");
+            }
+            Err(e) => {
+                self.span.record("status", &tracing::field::display(e));
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = result;
+    }
+
+    pub fn record_canceled(&self) {
+        #[cfg(feature = "tracing")]
+        self.span.record("status", &"canceled");
+    }
+
+    pub fn record_items(&self, items: u64) {
+        #[cfg(feature = "tracing")]
+        self.span.record("items", &items);
+        #[cfg(not(feature = "tracing"))]
+        let _ = items;
+    }
+}
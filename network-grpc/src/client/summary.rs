@@ -0,0 +1,42 @@
+use super::ResponseStream;
+
+use chain_core::property::{Block, Deserialize};
+
+/// A summary of the blocks received in a `ResponseStream`.
+///
+/// This is returned by `ResponseStream::summary`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamSummary<Id> {
+    /// The number of blocks yielded so far.
+    pub count: u64,
+    /// The identifier of the last block yielded, or `None` if no blocks
+    /// have been yielded yet or the peer did not send the identifier
+    /// of the last block.
+    pub last_id: Option<Id>,
+    /// The total encoded size of the messages received so far.
+    pub received_bytes: u64,
+}
+
+impl<T, R> ResponseStream<T, R>
+where
+    T: Block,
+{
+    /// Returns a summary of the blocks yielded by the stream so far.
+    ///
+    /// This can be called at any time, and is meant to be logged after
+    /// the stream has ended, failed, or has been canceled. The identifier
+    /// of the last block is the one the peer has sent alongside it, so
+    /// it is tracked without deserializing every identifier.
+    pub fn summary(&self) -> StreamSummary<T::Id> {
+        let last_id = self
+            .items
+            .last_item_id
+            .as_ref()
+            .and_then(|id| T::Id::deserialize(&id[..]).ok());
+        StreamSummary {
+            count: self.yielded,
+            last_id,
+            received_bytes: self.items.received_bytes,
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use super::{Client, Error};
+use crate::peer::Origin;
+
+use chain_core::property::{Block, HasHeader};
+use network_core::{
+    bloom::BloomFilter,
+    client::{
+        self as core_client,
+        block::{BlockService, HeaderService},
+        peer::PeerService,
+    },
+};
+
+use futures::future::Executor;
+use tokio::io;
+use tokio::prelude::*;
+use tower_grpc::BoxBody;
+use tower_h2::client::Background;
+
+use std::marker::PhantomData;
+
+/// A client for the block type `T`, fixed when the client is created.
+///
+/// `Client` implements the service traits for any block type that
+/// satisfies their bounds, so the block type of a request is selected
+/// by the call site, e.g. `BlockService::<MyBlock>::tip(&mut client)`.
+/// `TypedClient` wraps a `Client` and provides the request methods
+/// without type parameters, so a client always deals in the same blocks
+/// and the types of requests are inferred.
+///
+/// The methods return the same futures and streams as the corresponding
+/// methods of `Client`. The underlying client can be accessed to
+/// configure it or to use it generically.
+pub struct TypedClient<T, S, E> {
+    inner: Client<S, E>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, S, E> TypedClient<T, S, E> {
+    /// Wraps the client to make requests for blocks of type `T`.
+    pub fn new(client: Client<S, E>) -> Self {
+        TypedClient {
+            inner: client,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the underlying client.
+    pub fn get_ref(&self) -> &Client<S, E> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying client.
+    pub fn get_mut(&mut self) -> &mut Client<S, E> {
+        &mut self.inner
+    }
+
+    /// Consumes the wrapper, returning the underlying client.
+    pub fn into_inner(self) -> Client<S, E> {
+        self.inner
+    }
+}
+
+impl<T, S, E> Clone for TypedClient<T, S, E>
+where
+    Client<S, E>: Clone,
+{
+    fn clone(&self) -> Self {
+        TypedClient::new(self.inner.clone())
+    }
+}
+
+impl<T, S, E> TypedClient<T, S, E>
+where
+    S: AsyncRead + AsyncWrite,
+    E: Executor<Background<S, BoxBody>> + Clone,
+{
+    /// Connects to the peer, using the origin derived from the peer's
+    /// connection details for requests.
+    pub fn connect<P>(peer: P, executor: E) -> impl Future<Item = Self, Error = Error>
+    where
+        P: tokio_connect::Connect<Connected = S, Error = io::Error> + Origin + 'static,
+    {
+        Client::connect(peer, executor).map(TypedClient::new)
+    }
+}
+
+impl<T, S, E> TypedClient<T, S, E>
+where
+    T: Block,
+    Client<S, E>: BlockService<T>,
+{
+    /// Requests the identifier and the date of the current chain tip.
+    ///
+    /// See `BlockService::tip`.
+    pub fn tip(&mut self) -> <Client<S, E> as BlockService<T>>::TipFuture {
+        BlockService::<T>::tip(&mut self.inner)
+    }
+
+    /// Requests the length of the peer's chain, as the height of its tip.
+    ///
+    /// See `BlockService::chain_length`.
+    pub fn chain_length(&mut self) -> <Client<S, E> as BlockService<T>>::ChainLengthFuture {
+        BlockService::<T>::chain_length(&mut self.inner)
+    }
+
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the peer, up to the tip of its chain.
+    ///
+    /// See `BlockService::pull_blocks_to_tip`.
+    pub fn pull_blocks_to_tip(
+        &mut self,
+        from: &[T::Id],
+    ) -> <Client<S, E> as BlockService<T>>::PullBlocksToTipFuture {
+        BlockService::<T>::pull_blocks_to_tip(&mut self.inner, from)
+    }
+
+    /// Retrieves the blocks from the latest of the `from` blocks that is
+    /// an ancestor of `to`, up to and including `to`.
+    ///
+    /// See `BlockService::pull_blocks`.
+    pub fn pull_blocks(
+        &mut self,
+        from: &[T::Id],
+        to: &T::Id,
+    ) -> <Client<S, E> as BlockService<T>>::PullBlocksFuture {
+        BlockService::<T>::pull_blocks(&mut self.inner, from, to)
+    }
+
+    /// Retrieves the chain in descending order from the tip, stopping
+    /// before the first block identified in `to`.
+    ///
+    /// See `BlockService::pull_blocks_from_tip`.
+    pub fn pull_blocks_from_tip(
+        &mut self,
+        to: &[T::Id],
+    ) -> <Client<S, E> as BlockService<T>>::PullBlocksFuture {
+        BlockService::<T>::pull_blocks_from_tip(&mut self.inner, to)
+    }
+
+    /// Retrieves the blocks following the latest of the `from` blocks
+    /// known to the peer, letting the peer skip the blocks in `known`.
+    ///
+    /// See `BlockService::pull_blocks_to_tip_filtered`.
+    pub fn pull_blocks_to_tip_filtered(
+        &mut self,
+        from: &[T::Id],
+        known: &BloomFilter,
+    ) -> <Client<S, E> as BlockService<T>>::PullBlocksFuture {
+        BlockService::<T>::pull_blocks_to_tip_filtered(&mut self.inner, from, known)
+    }
+
+    /// Retrieves the blocks identified by `ids`.
+    ///
+    /// See `BlockService::get_blocks`.
+    pub fn get_blocks(
+        &mut self,
+        ids: &[T::Id],
+    ) -> <Client<S, E> as BlockService<T>>::GetBlocksFuture {
+        BlockService::<T>::get_blocks(&mut self.inner, ids)
+    }
+
+    /// Retrieves the block identified by `id`.
+    ///
+    /// See `BlockService::get_block`.
+    pub fn get_block(&mut self, id: T::Id) -> <Client<S, E> as BlockService<T>>::GetBlockFuture {
+        BlockService::<T>::get_block(&mut self.inner, id)
+    }
+
+    /// Retrieves the blocks identified by the items of the stream `ids`
+    /// in a single exchange, in the order of the identifiers.
+    ///
+    /// See `BlockService::get_blocks_pipelined`.
+    pub fn get_blocks_pipelined<St>(
+        &mut self,
+        ids: St,
+    ) -> <Client<S, E> as BlockService<T>>::GetBlocksPipelinedFuture
+    where
+        St: Stream<Item = T::Id, Error = core_client::Error> + Send + 'static,
+    {
+        BlockService::<T>::get_blocks_pipelined(&mut self.inner, ids)
+    }
+
+    /// Retrieves up to `count` consecutive blocks of the chain, starting
+    /// from the block at height `from_height`.
+    ///
+    /// See `BlockService::get_blocks_by_height`.
+    pub fn get_blocks_by_height(
+        &mut self,
+        from_height: u64,
+        count: u32,
+    ) -> <Client<S, E> as BlockService<T>>::GetBlocksFuture {
+        BlockService::<T>::get_blocks_by_height(&mut self.inner, from_height, count)
+    }
+
+    /// Retrieves the identifier of the block at `height` in the chain
+    /// of the peer.
+    ///
+    /// See `BlockService::block_id_at_height`.
+    pub fn block_id_at_height(
+        &mut self,
+        height: u64,
+    ) -> <Client<S, E> as BlockService<T>>::BlockIdAtHeightFuture {
+        BlockService::<T>::block_id_at_height(&mut self.inner, height)
+    }
+
+    /// Checks which of the blocks identified by `ids` are known to the peer.
+    ///
+    /// See `BlockService::has_blocks`.
+    pub fn has_blocks(
+        &mut self,
+        ids: &[T::Id],
+    ) -> <Client<S, E> as BlockService<T>>::HasBlocksFuture {
+        BlockService::<T>::has_blocks(&mut self.inner, ids)
+    }
+
+    /// Subscribes to blocks appended to the chain of the peer.
+    ///
+    /// See `BlockService::block_subscription`.
+    pub fn block_subscription(
+        &mut self,
+    ) -> <Client<S, E> as BlockService<T>>::BlockSubscriptionFuture {
+        BlockService::<T>::block_subscription(&mut self.inner)
+    }
+
+    /// Uploads the blocks from the stream to the peer.
+    ///
+    /// See `BlockService::upload_blocks`.
+    pub fn upload_blocks<St>(
+        &mut self,
+        blocks: St,
+    ) -> <Client<S, E> as BlockService<T>>::UploadBlocksFuture
+    where
+        St: Stream<Item = T, Error = core_client::Error> + Send + 'static,
+    {
+        BlockService::<T>::upload_blocks(&mut self.inner, blocks)
+    }
+}
+
+impl<T, S, E> TypedClient<T, S, E>
+where
+    T: Block + HasHeader,
+    Client<S, E>: HeaderService<T>,
+{
+    /// Retrieves the headers of the blocks identified by `ids`.
+    ///
+    /// See `HeaderService::get_headers`.
+    pub fn get_headers(
+        &mut self,
+        ids: &[T::Id],
+    ) -> <Client<S, E> as HeaderService<T>>::GetHeadersFuture {
+        HeaderService::<T>::get_headers(&mut self.inner, ids)
+    }
+
+    /// Requests the header of the current chain tip.
+    ///
+    /// See `HeaderService::tip_header`.
+    pub fn tip_header(&mut self) -> <Client<S, E> as HeaderService<T>>::TipHeaderFuture {
+        HeaderService::<T>::tip_header(&mut self.inner)
+    }
+
+    /// Retrieves the headers of the last `count` blocks of the chain.
+    ///
+    /// See `HeaderService::tip_headers`.
+    pub fn tip_headers(
+        &mut self,
+        count: u32,
+    ) -> <Client<S, E> as HeaderService<T>>::TipHeadersFuture {
+        HeaderService::<T>::tip_headers(&mut self.inner, count)
+    }
+
+    /// Retrieves the headers of the blocks following the latest of
+    /// the `from` blocks known to the peer, up to the tip of its chain.
+    ///
+    /// See `HeaderService::pull_headers_to_tip`.
+    pub fn pull_headers_to_tip(
+        &mut self,
+        from: &[T::Id],
+    ) -> <Client<S, E> as HeaderService<T>>::PullHeadersFuture {
+        HeaderService::<T>::pull_headers_to_tip(&mut self.inner, from)
+    }
+
+    /// Announces headers of new blocks to the peer.
+    ///
+    /// See `HeaderService::push_headers`.
+    pub fn push_headers(
+        &mut self,
+        headers: &[T::Header],
+    ) -> <Client<S, E> as HeaderService<T>>::PushHeadersFuture {
+        HeaderService::<T>::push_headers(&mut self.inner, headers)
+    }
+}
+
+impl<T, S, E> TypedClient<T, S, E>
+where
+    T: Block,
+    Client<S, E>: PeerService<T>,
+{
+    /// Checks that the peer is alive and retrieves its node information.
+    ///
+    /// See `PeerService::ping`.
+    pub fn ping(&mut self) -> <Client<S, E> as PeerService<T>>::PingFuture {
+        PeerService::<T>::ping(&mut self.inner)
+    }
+}
@@ -0,0 +1,301 @@
+//! Logging of the HTTP/2 frames exchanged over a connection, for
+//! diagnosing interoperability problems with peers.
+//!
+//! `FrameTrace` wraps the byte stream of a connection and logs the header
+//! of every HTTP/2 frame passing through it, in either direction, as
+//! a `tracing` event at the debug level with the fields `peer`,
+//! `direction` ("send" or "recv"), `frame`, `stream_id`, `length`, and
+//! `flags`. The bytes are passed on unchanged, and frame payloads are
+//! not logged. `TracedPeer` applies the wrapper to the connections made
+//! to a peer by a client.
+//!
+//! This module is available with the `frame-trace` feature.
+
+use crate::peer::Origin;
+
+use futures::try_ready;
+use tokio::prelude::*;
+
+use std::{cmp, io, sync::Arc, time::Duration};
+
+// The connection preface sent by the client before the first frame.
+const CLIENT_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_HEADER_LEN: usize = 9;
+
+/// Wrapper of the byte stream of an HTTP/2 connection that logs
+/// the headers of the frames read and written.
+///
+/// See the module documentation for details.
+pub struct FrameTrace<S> {
+    inner: S,
+    peer: Arc<str>,
+    recv: FrameParser,
+    send: FrameParser,
+}
+
+impl<S> FrameTrace<S> {
+    /// Wraps the stream of a connection on the client side,
+    /// which writes the connection preface.
+    ///
+    /// The `peer` label identifies the connection in the log.
+    pub fn client<L: Into<Arc<str>>>(inner: S, peer: L) -> Self {
+        FrameTrace {
+            inner,
+            peer: peer.into(),
+            recv: FrameParser::new(0),
+            send: FrameParser::new(CLIENT_PREFACE.len()),
+        }
+    }
+
+    /// Wraps the stream of a connection accepted by a server,
+    /// which reads the connection preface.
+    ///
+    /// The `peer` label identifies the connection in the log.
+    pub fn server<L: Into<Arc<str>>>(inner: S, peer: L) -> Self {
+        FrameTrace {
+            inner,
+            peer: peer.into(),
+            recv: FrameParser::new(CLIENT_PREFACE.len()),
+            send: FrameParser::new(0),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Read> Read for FrameTrace<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let peer = &self.peer;
+        self.recv.feed(&buf[..n], |header| header.log(peer, "recv"));
+        Ok(n)
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for FrameTrace<S> {}
+
+impl<S: Write> Write for FrameTrace<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        let peer = &self.peer;
+        self.send.feed(&buf[..n], |header| header.log(peer, "send"));
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for FrameTrace<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+/// Connection details of a peer whose connections are wrapped
+/// with `FrameTrace`.
+///
+/// A client connected with a `TracedPeer` logs the frames exchanged
+/// with the peer, labeled with the origin URI of the peer.
+#[derive(Clone, Debug)]
+pub struct TracedPeer<P> {
+    inner: P,
+    peer: Arc<str>,
+}
+
+impl<P: Origin> TracedPeer<P> {
+    pub fn new(inner: P) -> Self {
+        let peer = inner.origin().to_string().into();
+        TracedPeer { inner, peer }
+    }
+}
+
+impl<P> TracedPeer<P> {
+    pub fn get_ref(&self) -> &P {
+        &self.inner
+    }
+}
+
+impl<P: Origin> Origin for TracedPeer<P> {
+    fn origin(&self) -> http::Uri {
+        self.inner.origin()
+    }
+
+    fn set_default_keepalive(&mut self, interval: Duration) {
+        self.inner.set_default_keepalive(interval)
+    }
+}
+
+impl<P> tokio_connect::Connect for TracedPeer<P>
+where
+    P: tokio_connect::Connect,
+{
+    type Connected = FrameTrace<P::Connected>;
+    type Error = P::Error;
+    type Future = TracedConnectFuture<P::Future>;
+
+    fn connect(&self) -> Self::Future {
+        TracedConnectFuture {
+            inner: self.inner.connect(),
+            peer: self.peer.clone(),
+        }
+    }
+}
+
+/// Future returned by the `connect` method of `TracedPeer`.
+pub struct TracedConnectFuture<F> {
+    inner: F,
+    peer: Arc<str>,
+}
+
+impl<F: Future> Future for TracedConnectFuture<F> {
+    type Item = FrameTrace<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, F::Error> {
+        let stream = try_ready!(self.inner.poll());
+        Ok(Async::Ready(FrameTrace::client(stream, self.peer.clone())))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FrameHeader {
+    length: u32,
+    frame_type: u8,
+    flags: u8,
+    stream_id: u32,
+}
+
+impl FrameHeader {
+    fn parse(bytes: &[u8; FRAME_HEADER_LEN]) -> Self {
+        FrameHeader {
+            length: u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]),
+            frame_type: bytes[3],
+            flags: bytes[4],
+            // The reserved bit is ignored.
+            stream_id: u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]) & 0x7fff_ffff,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self.frame_type {
+            0x0 => "DATA",
+            0x1 => "HEADERS",
+            0x2 => "PRIORITY",
+            0x3 => "RST_STREAM",
+            0x4 => "SETTINGS",
+            0x5 => "PUSH_PROMISE",
+            0x6 => "PING",
+            0x7 => "GOAWAY",
+            0x8 => "WINDOW_UPDATE",
+            0x9 => "CONTINUATION",
+            _ => "unknown",
+        }
+    }
+
+    fn log(&self, peer: &str, direction: &'static str) {
+        tracing::debug!(
+            peer = peer,
+            direction = direction,
+            frame = self.type_name(),
+            frame_type = u64::from(self.frame_type),
+            stream_id = u64::from(self.stream_id),
+            length = u64::from(self.length),
+            flags = u64::from(self.flags),
+            "HTTP/2 frame"
+        );
+    }
+}
+
+// Follows the frames in one direction of the byte stream, which may
+// be split at any point between reads or writes.
+struct FrameParser {
+    // The bytes of the connection preface yet to be passed.
+    preface_left: usize,
+    header: [u8; FRAME_HEADER_LEN],
+    header_len: usize,
+    // The bytes of the payload of the current frame yet to be passed.
+    payload_left: usize,
+}
+
+impl FrameParser {
+    fn new(preface_len: usize) -> Self {
+        FrameParser {
+            preface_left: preface_len,
+            header: [0; FRAME_HEADER_LEN],
+            header_len: 0,
+            payload_left: 0,
+        }
+    }
+
+    fn feed<F>(&mut self, mut data: &[u8], mut on_frame: F)
+    where
+        F: FnMut(FrameHeader),
+    {
+        while !data.is_empty() {
+            if self.preface_left > 0 {
+                let n = cmp::min(self.preface_left, data.len());
+                self.preface_left -= n;
+                data = &data[n..];
+            } else if self.payload_left > 0 {
+                let n = cmp::min(self.payload_left, data.len());
+                self.payload_left -= n;
+                data = &data[n..];
+            } else {
+                let n = cmp::min(FRAME_HEADER_LEN - self.header_len, data.len());
+                self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+                self.header_len += n;
+                data = &data[n..];
+                if self.header_len == FRAME_HEADER_LEN {
+                    let header = FrameHeader::parse(&self.header);
+                    self.header_len = 0;
+                    self.payload_left = header.length as usize;
+                    on_frame(header);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_split_across_writes_are_parsed() {
+        let mut bytes = CLIENT_PREFACE.to_vec();
+        // SETTINGS with one setting, then PING with the ACK flag.
+        bytes.extend_from_slice(&[0, 0, 6, 0x4, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0, 3, 0, 0, 0, 100]);
+        bytes.extend_from_slice(&[0, 0, 8, 0x6, 0x1, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0; 8]);
+        // HEADERS on stream 1, with the reserved bit set.
+        bytes.extend_from_slice(&[0, 0, 1, 0x1, 0x4, 0x80, 0, 0, 1, 0x82]);
+
+        let mut parser = FrameParser::new(CLIENT_PREFACE.len());
+        let mut frames = Vec::new();
+        for chunk in bytes.chunks(5) {
+            parser.feed(chunk, |header| frames.push(header));
+        }
+        let summary: Vec<_> = frames
+            .iter()
+            .map(|h| (h.type_name(), h.stream_id, h.length, h.flags))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("SETTINGS", 0, 6, 0),
+                ("PING", 0, 8, 0x1),
+                ("HEADERS", 1, 1, 0x4),
+            ]
+        );
+    }
+}
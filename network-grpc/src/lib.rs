@@ -5,6 +5,8 @@ extern crate prost_derive;
 extern crate tokio_connect;
 extern crate tower_grpc;
 extern crate tower_h2;
+extern crate tower_http;
+extern crate tower_service;
 extern crate tower_util;
 
 // Generated protobuf/gRPC code.
@@ -15,9 +17,18 @@ mod gen {
     pub mod node {
         include!(concat!(env!("OUT_DIR"), "/iohk.chain.node.rs"));
     }
+
+    #[cfg(feature = "reflection")]
+    pub mod reflection {
+        include!(concat!(env!("OUT_DIR"), "/grpc.reflection.v1alpha.rs"));
+    }
 }
 
 pub mod client;
+#[cfg(feature = "frame-trace")]
+pub mod frame_trace;
 pub mod peer;
 pub mod server;
 mod service;
+#[cfg(test)]
+mod test_types;
@@ -1,26 +1,61 @@
+use futures::try_ready;
 use tokio::net::tcp::{self, TcpStream};
 #[cfg(unix)]
 use tokio::net::unix::{self, UnixStream};
+use tokio::prelude::*;
 
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, time::Duration};
 
 #[cfg(unix)]
 use std::path::{Path, PathBuf};
 
+/// Provides the origin URI of a peer.
+///
+/// The scheme and authority of the origin are set on every request
+/// made to the peer.
+pub trait Origin {
+    fn origin(&self) -> http::Uri;
+
+    /// Sets the TCP keepalive interval configured with
+    /// `ClientBuilder::tcp_keepalive` for connections made to the peer,
+    /// unless the peer has its own setting.
+    ///
+    /// The default implementation does nothing, which is right for
+    /// peers that are not connected over TCP.
+    fn set_default_keepalive(&mut self, _interval: Duration) {}
+}
+
 /// Specifies the connection details of a remote TCP/IP peer.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TcpPeer {
     addr: SocketAddr,
+    keepalive: Option<Duration>,
 }
 
 impl TcpPeer {
     pub fn new(addr: SocketAddr) -> Self {
-        TcpPeer { addr }
+        TcpPeer {
+            addr,
+            keepalive: None,
+        }
     }
 
     pub fn addr(&self) -> &SocketAddr {
         &self.addr
     }
+
+    /// Sets the TCP keepalive interval for connections made to this peer.
+    ///
+    /// HTTP/2 PING frames are not used for keepalive by this
+    /// implementation, so long-lived connections should rely on this
+    /// setting to detect dead peers.
+    pub fn set_keepalive(&mut self, keepalive: Option<Duration>) {
+        self.keepalive = keepalive;
+    }
+
+    pub fn keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
 }
 
 /// Specifies the connection details of a local Unix socket peer.
@@ -43,13 +78,54 @@ impl UnixPeer {
     }
 }
 
+impl Origin for TcpPeer {
+    fn origin(&self) -> http::Uri {
+        format!("http://{}", self.addr)
+            .parse()
+            .expect("socket address should be a valid URI authority")
+    }
+
+    fn set_default_keepalive(&mut self, interval: Duration) {
+        if self.keepalive.is_none() {
+            self.keepalive = Some(interval);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Origin for UnixPeer {
+    fn origin(&self) -> http::Uri {
+        http::Uri::from_static("http://localhost")
+    }
+}
+
 impl tokio_connect::Connect for TcpPeer {
     type Connected = TcpStream;
     type Error = io::Error;
-    type Future = tcp::ConnectFuture;
+    type Future = TcpConnectFuture;
+
+    fn connect(&self) -> TcpConnectFuture {
+        TcpConnectFuture {
+            inner: TcpStream::connect(self.addr()),
+            keepalive: self.keepalive,
+        }
+    }
+}
+
+/// Future returned by the `connect` method of `TcpPeer`.
+pub struct TcpConnectFuture {
+    inner: tcp::ConnectFuture,
+    keepalive: Option<Duration>,
+}
+
+impl Future for TcpConnectFuture {
+    type Item = TcpStream;
+    type Error = io::Error;
 
-    fn connect(&self) -> tcp::ConnectFuture {
-        TcpStream::connect(self.addr())
+    fn poll(&mut self) -> Poll<TcpStream, io::Error> {
+        let sock = try_ready!(self.inner.poll());
+        sock.set_keepalive(self.keepalive)?;
+        Ok(Async::Ready(sock))
     }
 }
 
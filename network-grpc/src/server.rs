@@ -88,7 +88,14 @@ where
 {
     /// Creates a server instance around the node service implementation.
     pub fn new(node: T, executor: E) -> Self {
-        let grpc_service = gen_server::NodeServer::new(NodeService::new(node));
+        Server::with_block_options(node, executor, BlockOptions::default())
+    }
+
+    /// Creates a server instance around the node service implementation,
+    /// sending the blocks it serves as set in `options`.
+    pub fn with_block_options(node: T, executor: E, options: BlockOptions) -> Self {
+        let node_service = NodeService::with_block_options(node, options);
+        let grpc_service = gen_server::NodeServer::new(node_service);
         let h2 = tower_h2::Server::new(grpc_service, Default::default(), executor);
         Server { h2 }
     }
@@ -105,6 +112,51 @@ where
     }
 }
 
+/// Settings for the block messages sent by the server.
+///
+/// By default, every block is sent along with its serialized identifier
+/// and a CRC-32 checksum of its serialized content. Clients use the
+/// identifier to keep track of their position in a stream of blocks,
+/// and verify the checksum if enabled with
+/// `ClientBuilder::verify_block_checksums`. Either can be left out
+/// to save computing it for every block served; clients do not verify
+/// blocks that are sent without a checksum.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockOptions {
+    pub(crate) send_ids: bool,
+    pub(crate) send_checksums: bool,
+}
+
+impl Default for BlockOptions {
+    fn default() -> Self {
+        BlockOptions {
+            send_ids: true,
+            send_checksums: true,
+        }
+    }
+}
+
+impl BlockOptions {
+    /// Creates the default settings, sending both the identifiers
+    /// and the checksums of blocks.
+    pub fn new() -> Self {
+        BlockOptions::default()
+    }
+
+    /// Sets whether blocks are sent along with their identifiers.
+    pub fn send_ids(&mut self, send: bool) -> &mut Self {
+        self.send_ids = send;
+        self
+    }
+
+    /// Sets whether blocks are sent along with the checksums
+    /// of their content.
+    pub fn send_checksums(&mut self, send: bool) -> &mut Self {
+        self.send_checksums = send;
+        self
+    }
+}
+
 /// Sets up a listening TCP socket bound to the given address.
 /// If successful, returns an asynchronous stream of `TcpStream` socket
 /// objects representing accepted TCP connections from clients.
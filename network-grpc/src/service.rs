@@ -1,33 +1,54 @@
 use crate::gen;
+use crate::server::BlockOptions;
 
 use chain_core::property::{
-    Block, BlockDate, BlockId, Deserialize, Header, Serialize, TransactionId,
+    Block, BlockDate, BlockId, Deserialize, Header, Serialize, Transaction, TransactionId,
 };
-use network_core::server::{
-    self,
-    block::{BlockService, HeaderService},
-    transaction::TransactionService,
-    Node,
+use network_core::{
+    client::transaction::MempoolStats,
+    codes::TransactionAcceptance,
+    peer::PeerInfo,
+    server::{
+        self,
+        block::{BlockError, BlockService, HeaderService},
+        transaction::{TransactionError, TransactionService},
+        Node,
+    },
 };
 
-use futures::prelude::*;
+use futures::future;
+use futures::{prelude::*, try_ready};
 use tower_grpc::Error::Grpc as GrpcError;
-use tower_grpc::{self, Code, Request, Status};
+use tower_grpc::{self, Code, Request, Status, Streaming};
 
-use std::{error, marker::PhantomData, mem};
+use std::{cmp, error, marker::PhantomData, mem};
+
+// The maximum number of headers sent in response to TipHeaders.
+const MAX_TIP_HEADERS: u32 = 2160;
+
+// The maximum number of blocks sent in response to GetBlockRange.
+const MAX_BLOCK_RANGE: u32 = 500;
 
 pub struct NodeService<T: Node> {
     block_service: Option<T::BlockService>,
     header_service: Option<T::HeaderService>,
     tx_service: Option<T::TransactionService>,
+    peer_info: PeerInfo<<T::BlockService as BlockService>::BlockId>,
+    block_options: BlockOptions,
 }
 
 impl<T: Node> NodeService<T> {
     pub fn new(node: T) -> Self {
+        NodeService::with_block_options(node, BlockOptions::default())
+    }
+
+    pub fn with_block_options(node: T, block_options: BlockOptions) -> Self {
         NodeService {
             block_service: node.block_service(),
             header_service: node.header_service(),
             tx_service: node.transaction_service(),
+            peer_info: node.peer_info(),
+            block_options,
         }
     }
 }
@@ -44,12 +65,17 @@ where
             block_service: self.block_service.clone(),
             header_service: self.header_service.clone(),
             tx_service: self.tx_service.clone(),
+            peer_info: self.peer_info.clone(),
+            block_options: self.block_options,
         }
     }
 }
 
 pub enum ResponseFuture<T, F> {
-    Pending(F),
+    Pending(F, BlockOptions),
+    // Pending on a lookup that the block service fails when
+    // the requested blocks are not found.
+    Lookup(F, BlockOptions),
     Failed(Status),
     Finished(PhantomData<T>),
 }
@@ -60,7 +86,19 @@ where
     F::Item: ConvertResponse<T>,
 {
     fn new(future: F) -> Self {
-        ResponseFuture::Pending(future)
+        ResponseFuture::Pending(future, BlockOptions::default())
+    }
+
+    // Creates a future for a response containing blocks, which are
+    // converted into messages as set in `options`.
+    fn with_block_options(future: F, options: BlockOptions) -> Self {
+        ResponseFuture::Pending(future, options)
+    }
+
+    // Like `with_block_options`, but a failure of the future is
+    // reported with status code NotFound.
+    fn lookup(future: F, options: BlockOptions) -> Self {
+        ResponseFuture::Lookup(future, options)
     }
 }
 
@@ -81,20 +119,31 @@ fn convert_error<E: error::Error>(e: E) -> tower_grpc::Error {
 
 pub trait ConvertResponse<T> {
     fn convert_response(self) -> Result<T, tower_grpc::Error>;
+
+    // Converts into a message as set in the server's block options.
+    // This is only different from `convert_response` for blocks
+    // and streams of blocks.
+    fn convert_response_with(self, _options: &BlockOptions) -> Result<T, tower_grpc::Error>
+    where
+        Self: Sized,
+    {
+        self.convert_response()
+    }
 }
 
 fn poll_and_convert_response<T, F>(
     future: &mut F,
+    options: &BlockOptions,
+    convert_error: fn(F::Error) -> tower_grpc::Error,
 ) -> Poll<tower_grpc::Response<T>, tower_grpc::Error>
 where
     F: Future,
     F::Item: ConvertResponse<T>,
-    F::Error: error::Error,
 {
     match future.poll() {
         Ok(Async::NotReady) => Ok(Async::NotReady),
         Ok(Async::Ready(res)) => {
-            let item = res.convert_response()?;
+            let item = res.convert_response_with(options)?;
             let response = tower_grpc::Response::new(item);
             Ok(Async::Ready(response))
         }
@@ -102,7 +151,10 @@ where
     }
 }
 
-fn poll_and_convert_stream<T, S>(stream: &mut S) -> Poll<Option<T>, tower_grpc::Error>
+fn poll_and_convert_stream<T, S>(
+    stream: &mut S,
+    options: &BlockOptions,
+) -> Poll<Option<T>, tower_grpc::Error>
 where
     S: Stream,
     S::Item: ConvertResponse<T>,
@@ -112,7 +164,7 @@ where
         Ok(Async::NotReady) => Ok(Async::NotReady),
         Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
         Ok(Async::Ready(Some(item))) => {
-            let item = item.convert_response()?;
+            let item = item.convert_response_with(options)?;
             Ok(Async::Ready(Some(item)))
         }
         Err(e) => Err(convert_error(e)),
@@ -129,25 +181,124 @@ where
     type Error = tower_grpc::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, tower_grpc::Error> {
-        if let ResponseFuture::Pending(f) = self {
-            let res = poll_and_convert_response(f);
-            if let Ok(Async::NotReady) = res {
-                return Ok(Async::NotReady);
+        let res = match *self {
+            ResponseFuture::Pending(ref mut f, ref options) => {
+                poll_and_convert_response(f, options, convert_error)
             }
-            *self = ResponseFuture::Finished(PhantomData);
-            res
-        } else {
-            match mem::replace(self, ResponseFuture::Finished(PhantomData)) {
-                ResponseFuture::Pending(_) => unreachable!(),
-                ResponseFuture::Failed(status) => Err(GrpcError(status)),
-                ResponseFuture::Finished(_) => panic!("polled a finished response"),
+            ResponseFuture::Lookup(ref mut f, ref options) => {
+                poll_and_convert_response(f, options, not_found)
+            }
+            _ => {
+                return match mem::replace(self, ResponseFuture::Finished(PhantomData)) {
+                    ResponseFuture::Pending(..) | ResponseFuture::Lookup(..) => unreachable!(),
+                    ResponseFuture::Failed(status) => Err(GrpcError(status)),
+                    ResponseFuture::Finished(_) => panic!("polled a finished response"),
+                };
             }
+        };
+        if let Ok(Async::NotReady) = res {
+            return Ok(Async::NotReady);
         }
+        *self = ResponseFuture::Finished(PhantomData);
+        res
     }
 }
 
+// Resolves to the response converted from the first block of the
+// stream returned by the block service for a lookup of a single block,
+// by identifier or by height.
+pub enum GetBlockFuture<T, F, S> {
+    Pending(F, BlockOptions),
+    Streaming(S, BlockOptions),
+    Failed(Status),
+    Finished(PhantomData<T>),
+}
+
+impl<T, F, S> Future for GetBlockFuture<T, F, S>
+where
+    F: Future<Item = S, Error = BlockError>,
+    S: Stream<Error = BlockError>,
+    S::Item: ConvertResponse<T>,
+{
+    type Item = tower_grpc::Response<T>;
+    type Error = tower_grpc::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, tower_grpc::Error> {
+        loop {
+            let res = match self {
+                GetBlockFuture::Pending(f, options) => {
+                    // BlockError does not tell the causes apart, but
+                    // a missing block is what fails a single lookup.
+                    let stream = try_ready!(f.poll().map_err(not_found));
+                    *self = GetBlockFuture::Streaming(stream, *options);
+                    continue;
+                }
+                GetBlockFuture::Streaming(s, options) => {
+                    match try_ready!(s.poll().map_err(not_found)) {
+                        Some(block) => block
+                            .convert_response_with(options)
+                            .map(|block| Async::Ready(tower_grpc::Response::new(block))),
+                        None => Err(not_found(BlockError())),
+                    }
+                }
+                GetBlockFuture::Failed(_) => {
+                    match mem::replace(self, GetBlockFuture::Finished(PhantomData)) {
+                        GetBlockFuture::Failed(status) => Err(GrpcError(status)),
+                        _ => unreachable!(),
+                    }
+                }
+                GetBlockFuture::Finished(_) => panic!("polled a finished response"),
+            };
+            *self = GetBlockFuture::Finished(PhantomData);
+            return res;
+        }
+    }
+}
+
+// Streams the blocks looked up for the identifiers received in
+// a request stream, in the order of the requests. A lookup is made
+// when the previous block has been sent, while the next requests
+// are buffered by the transport.
+pub struct PipelinedBlocks<B: BlockService> {
+    service: B,
+    requests: Streaming<gen::node::GetBlockRequest>,
+    options: BlockOptions,
+    lookup: Option<GetBlockFuture<gen::node::Block, B::GetBlocksFuture, B::GetBlocksStream>>,
+}
+
+impl<B: BlockService> Stream for PipelinedBlocks<B> {
+    type Item = gen::node::Block;
+    type Error = tower_grpc::Error;
+
+    fn poll(&mut self) -> Poll<Option<gen::node::Block>, tower_grpc::Error> {
+        loop {
+            if let Some(ref mut lookup) = self.lookup {
+                let res = try_ready!(lookup.poll());
+                self.lookup = None;
+                return Ok(Async::Ready(Some(res.into_inner())));
+            }
+            let req = match try_ready!(self.requests.poll()) {
+                Some(req) => req,
+                None => return Ok(Async::Ready(None)),
+            };
+            let block_id = deserialize_bytes(&req.id)?;
+            let lookup =
+                GetBlockFuture::Pending(self.service.get_blocks(&[block_id]), self.options);
+            self.lookup = Some(lookup);
+        }
+    }
+}
+
+fn not_found<E>(_: E) -> tower_grpc::Error {
+    GrpcError(Status::with_code_and_message(
+        Code::NotFound,
+        "block not found",
+    ))
+}
+
 pub struct ResponseStream<T, S> {
     inner: S,
+    options: BlockOptions,
     _phantom: PhantomData<T>,
 }
 
@@ -156,9 +307,10 @@ where
     S: Stream,
     S::Item: ConvertResponse<T>,
 {
-    pub fn new(stream: S) -> Self {
+    pub fn new(stream: S, options: BlockOptions) -> Self {
         ResponseStream {
             inner: stream,
+            options,
             _phantom: PhantomData,
         }
     }
@@ -174,7 +326,7 @@ where
     type Error = tower_grpc::Error;
 
     fn poll(&mut self) -> Poll<Option<T>, tower_grpc::Error> {
-        poll_and_convert_stream(&mut self.inner)
+        poll_and_convert_stream(&mut self.inner, &self.options)
     }
 }
 
@@ -190,6 +342,14 @@ fn deserialize_vec<H: Deserialize>(pb: &[Vec<u8>]) -> Result<Vec<H>, tower_grpc:
     }
 }
 
+fn deserialize_bytes<H: Deserialize>(mut buf: &[u8]) -> Result<H, tower_grpc::Error> {
+    H::deserialize(&mut buf).map_err(|e| {
+        // FIXME: log the error
+        let status = Status::with_code_and_message(Code::InvalidArgument, format!("{}", e));
+        GrpcError(status)
+    })
+}
+
 fn serialize_to_bytes<T>(obj: T) -> Result<Vec<u8>, tower_grpc::Error>
 where
     T: Serialize,
@@ -211,11 +371,33 @@ where
     S::Item: ConvertResponse<T>,
 {
     fn convert_response(self) -> Result<ResponseStream<T, S>, tower_grpc::Error> {
-        let stream = ResponseStream::new(self);
+        self.convert_response_with(&BlockOptions::default())
+    }
+
+    fn convert_response_with(
+        self,
+        options: &BlockOptions,
+    ) -> Result<ResponseStream<T, S>, tower_grpc::Error> {
+        let stream = ResponseStream::new(self, *options);
         Ok(stream)
     }
 }
 
+impl<I> ConvertResponse<gen::node::PingResponse> for PeerInfo<I>
+where
+    I: BlockId + Serialize,
+{
+    fn convert_response(self) -> Result<gen::node::PingResponse, tower_grpc::Error> {
+        let genesis_hash = serialize_to_bytes(self.genesis_hash)?;
+        let response = gen::node::PingResponse {
+            node_id: self.node_id,
+            protocol_version: self.protocol_version.to_string(),
+            genesis_hash,
+        };
+        Ok(response)
+    }
+}
+
 impl<I, D> ConvertResponse<gen::node::TipResponse> for (I, D)
 where
     I: BlockId + Serialize,
@@ -234,8 +416,41 @@ where
     B: Block + Serialize,
 {
     fn convert_response(self) -> Result<gen::node::Block, tower_grpc::Error> {
+        self.convert_response_with(&BlockOptions::default())
+    }
+
+    fn convert_response_with(
+        self,
+        options: &BlockOptions,
+    ) -> Result<gen::node::Block, tower_grpc::Error> {
+        let id = if options.send_ids {
+            serialize_to_bytes(self.id())?
+        } else {
+            Vec::new()
+        };
         let content = serialize_to_bytes(self)?;
-        Ok(gen::node::Block { content })
+        let checksum = if options.send_checksums {
+            crc32fast::hash(&content).to_be_bytes().to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(gen::node::Block {
+            content,
+            id,
+            serve_info: None,
+            checksum,
+            continuation_token: Vec::new(),
+        })
+    }
+}
+
+impl<B> ConvertResponse<gen::node::BlockIdResponse> for B
+where
+    B: Block,
+{
+    fn convert_response(self) -> Result<gen::node::BlockIdResponse, tower_grpc::Error> {
+        let id = serialize_to_bytes(self.id())?;
+        Ok(gen::node::BlockIdResponse { id })
     }
 }
 
@@ -249,6 +464,40 @@ where
     }
 }
 
+impl<X> ConvertResponse<gen::node::Transaction> for X
+where
+    X: Transaction + Serialize,
+{
+    fn convert_response(self) -> Result<gen::node::Transaction, tower_grpc::Error> {
+        let content = serialize_to_bytes(self)?;
+        Ok(gen::node::Transaction { content })
+    }
+}
+
+impl ConvertResponse<gen::node::UploadBlocksResponse> for u64 {
+    fn convert_response(self) -> Result<gen::node::UploadBlocksResponse, tower_grpc::Error> {
+        Ok(gen::node::UploadBlocksResponse { accepted: self })
+    }
+}
+
+impl ConvertResponse<gen::node::HasBlocksResponse> for Vec<bool> {
+    fn convert_response(self) -> Result<gen::node::HasBlocksResponse, tower_grpc::Error> {
+        Ok(gen::node::HasBlocksResponse { present: self })
+    }
+}
+
+impl ConvertResponse<gen::node::UploadTransactionsResponse> for u64 {
+    fn convert_response(self) -> Result<gen::node::UploadTransactionsResponse, tower_grpc::Error> {
+        Ok(gen::node::UploadTransactionsResponse { accepted: self })
+    }
+}
+
+impl ConvertResponse<gen::node::PushHeadersResponse> for () {
+    fn convert_response(self) -> Result<gen::node::PushHeadersResponse, tower_grpc::Error> {
+        Ok(gen::node::PushHeadersResponse {})
+    }
+}
+
 impl<I> ConvertResponse<gen::node::ProposeTransactionsResponse>
     for server::transaction::ProposeTransactionsResponse<I>
 where
@@ -269,6 +518,28 @@ where
     }
 }
 
+impl ConvertResponse<gen::node::ChainLengthResponse> for u64 {
+    fn convert_response(self) -> Result<gen::node::ChainLengthResponse, tower_grpc::Error> {
+        Ok(gen::node::ChainLengthResponse { length: self })
+    }
+}
+
+impl ConvertResponse<gen::node::MempoolStatsResponse> for MempoolStats {
+    fn convert_response(self) -> Result<gen::node::MempoolStatsResponse, tower_grpc::Error> {
+        Ok(gen::node::MempoolStatsResponse {
+            count: self.count,
+            bytes: self.bytes,
+        })
+    }
+}
+
+impl ConvertResponse<gen::node::SubmitTransactionsResponse> for Vec<TransactionAcceptance> {
+    fn convert_response(self) -> Result<gen::node::SubmitTransactionsResponse, tower_grpc::Error> {
+        let results = self.into_iter().map(|result| result as i32).collect();
+        Ok(gen::node::SubmitTransactionsResponse { results })
+    }
+}
+
 impl<T> gen::node::server::Node for NodeService<T>
 where
     T: Node,
@@ -276,10 +547,21 @@ where
     <T as Node>::HeaderService: Clone,
     <T as Node>::TransactionService: Clone,
 {
+    type PingFuture = ResponseFuture<
+        gen::node::PingResponse,
+        future::FutureResult<PeerInfo<<T::BlockService as BlockService>::BlockId>, BlockError>,
+    >;
+    type PeerGossipStream = futures::stream::Empty<gen::node::Gossip, tower_grpc::Error>;
+    type PeerGossipFuture =
+        future::FutureResult<tower_grpc::Response<Self::PeerGossipStream>, tower_grpc::Error>;
     type TipFuture = ResponseFuture<
         gen::node::TipResponse,
         <<T as Node>::BlockService as BlockService>::TipFuture,
     >;
+    type ChainLengthFuture = ResponseFuture<
+        gen::node::ChainLengthResponse,
+        <<T as Node>::BlockService as BlockService>::ChainLengthFuture,
+    >;
     type GetBlocksStream = ResponseStream<
         gen::node::Block,
         <<T as Node>::BlockService as BlockService>::GetBlocksStream,
@@ -288,6 +570,33 @@ where
         Self::GetBlocksStream,
         <<T as Node>::BlockService as BlockService>::GetBlocksFuture,
     >;
+    type GetBlockFuture = GetBlockFuture<
+        gen::node::Block,
+        <<T as Node>::BlockService as BlockService>::GetBlocksFuture,
+        <<T as Node>::BlockService as BlockService>::GetBlocksStream,
+    >;
+    type GetBlocksPipelinedStream = PipelinedBlocks<<T as Node>::BlockService>;
+    type GetBlocksPipelinedFuture = future::FutureResult<
+        tower_grpc::Response<Self::GetBlocksPipelinedStream>,
+        tower_grpc::Error,
+    >;
+    type BlockIdAtHeightFuture = GetBlockFuture<
+        gen::node::BlockIdResponse,
+        <<T as Node>::BlockService as BlockService>::GetBlocksFuture,
+        <<T as Node>::BlockService as BlockService>::GetBlocksStream,
+    >;
+    type HasBlocksFuture = ResponseFuture<
+        gen::node::HasBlocksResponse,
+        <<T as Node>::BlockService as BlockService>::HasBlocksFuture,
+    >;
+    type GetBlockRangeStream = ResponseStream<
+        gen::node::Block,
+        <<T as Node>::BlockService as BlockService>::GetBlocksStream,
+    >;
+    type GetBlockRangeFuture = ResponseFuture<
+        Self::GetBlockRangeStream,
+        <<T as Node>::BlockService as BlockService>::GetBlocksFuture,
+    >;
     type GetHeadersStream = ResponseStream<
         gen::node::Header,
         <<T as Node>::HeaderService as HeaderService>::GetHeadersStream,
@@ -296,6 +605,26 @@ where
         Self::GetHeadersStream,
         <<T as Node>::HeaderService as HeaderService>::GetHeadersFuture,
     >;
+    type TipHeaderFuture = ResponseFuture<
+        gen::node::Header,
+        <<T as Node>::HeaderService as HeaderService>::TipHeaderFuture,
+    >;
+    type TipHeadersStream = ResponseStream<
+        gen::node::Header,
+        <<T as Node>::HeaderService as HeaderService>::GetHeadersStream,
+    >;
+    type TipHeadersFuture = ResponseFuture<
+        Self::TipHeadersStream,
+        <<T as Node>::HeaderService as HeaderService>::GetHeadersFuture,
+    >;
+    type PullHeadersToTipStream = ResponseStream<
+        gen::node::Header,
+        <<T as Node>::HeaderService as HeaderService>::GetHeadersStream,
+    >;
+    type PullHeadersToTipFuture = ResponseFuture<
+        Self::PullHeadersToTipStream,
+        <<T as Node>::HeaderService as HeaderService>::GetHeadersFuture,
+    >;
     type PullBlocksToTipStream = ResponseStream<
         gen::node::Block,
         <<T as Node>::BlockService as BlockService>::PullBlocksToTipStream,
@@ -304,6 +633,37 @@ where
         Self::PullBlocksToTipStream,
         <<T as Node>::BlockService as BlockService>::PullBlocksFuture,
     >;
+    type PullBlocksStream = ResponseStream<
+        gen::node::Block,
+        <<T as Node>::BlockService as BlockService>::PullBlocksToTipStream,
+    >;
+    type PullBlocksFuture = ResponseFuture<
+        Self::PullBlocksStream,
+        <<T as Node>::BlockService as BlockService>::PullBlocksFuture,
+    >;
+    type PullBlocksFromTipStream = ResponseStream<
+        gen::node::Block,
+        <<T as Node>::BlockService as BlockService>::PullBlocksToTipStream,
+    >;
+    type PullBlocksFromTipFuture = ResponseFuture<
+        Self::PullBlocksFromTipStream,
+        <<T as Node>::BlockService as BlockService>::PullBlocksFuture,
+    >;
+    type PullBlocksResumeStream = futures::stream::Empty<gen::node::Block, tower_grpc::Error>;
+    type PullBlocksResumeFuture =
+        future::FutureResult<tower_grpc::Response<Self::PullBlocksResumeStream>, tower_grpc::Error>;
+    type BlockSubscriptionStream = ResponseStream<
+        gen::node::Block,
+        <<T as Node>::BlockService as BlockService>::BlockSubscriptionStream,
+    >;
+    type BlockSubscriptionFuture = ResponseFuture<
+        Self::BlockSubscriptionStream,
+        <<T as Node>::BlockService as BlockService>::BlockSubscriptionFuture,
+    >;
+    type PushHeadersFuture =
+        ResponseFuture<gen::node::PushHeadersResponse, future::Empty<(), BlockError>>;
+    type UploadBlocksFuture =
+        ResponseFuture<gen::node::UploadBlocksResponse, future::Empty<u64, BlockError>>;
     type ProposeTransactionsFuture = ResponseFuture<
         gen::node::ProposeTransactionsResponse,
         <<T as Node>::TransactionService as TransactionService>::ProposeTransactionsFuture,
@@ -312,6 +672,42 @@ where
         gen::node::RecordTransactionResponse,
         <<T as Node>::TransactionService as TransactionService>::RecordTransactionFuture,
     >;
+    type SubmitTransactionsFuture = ResponseFuture<
+        gen::node::SubmitTransactionsResponse,
+        <<T as Node>::TransactionService as TransactionService>::SubmitTransactionsFuture,
+    >;
+    type UploadTransactionsFuture =
+        ResponseFuture<gen::node::UploadTransactionsResponse, future::Empty<u64, TransactionError>>;
+    type TransactionStatusSubscriptionStream =
+        futures::stream::Empty<gen::node::TransactionStatusUpdate, tower_grpc::Error>;
+    type TransactionStatusSubscriptionFuture = future::FutureResult<
+        tower_grpc::Response<Self::TransactionStatusSubscriptionStream>,
+        tower_grpc::Error,
+    >;
+    type MempoolStatsFuture = ResponseFuture<
+        gen::node::MempoolStatsResponse,
+        future::Empty<MempoolStats, TransactionError>,
+    >;
+    type GetTransactionsStream = ResponseStream<
+        gen::node::Transaction,
+        <<T as Node>::TransactionService as TransactionService>::GetTransactionsStream,
+    >;
+    type GetTransactionsFuture = ResponseFuture<
+        Self::GetTransactionsStream,
+        <<T as Node>::TransactionService as TransactionService>::GetTransactionsFuture,
+    >;
+
+    fn ping(&mut self, _request: Request<gen::node::PingRequest>) -> Self::PingFuture {
+        ResponseFuture::new(future::ok(self.peer_info.clone()))
+    }
+
+    fn peer_gossip(
+        &mut self,
+        _request: Request<gen::node::PeerGossipRequest>,
+    ) -> Self::PeerGossipFuture {
+        // TODO: add a server-side abstraction for gossip
+        future::err(GrpcError(Status::with_code(Code::Unimplemented)))
+    }
 
     fn tip(&mut self, _request: Request<gen::node::TipRequest>) -> Self::TipFuture {
         let service = match self.block_service {
@@ -321,18 +717,141 @@ where
         ResponseFuture::new(service.tip())
     }
 
-    fn get_blocks(
+    fn chain_length(
         &mut self,
-        _request: Request<gen::node::GetBlocksRequest>,
-    ) -> Self::GetBlocksFuture {
-        unimplemented!()
+        _request: Request<gen::node::ChainLengthRequest>,
+    ) -> Self::ChainLengthFuture {
+        let service = match self.block_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        ResponseFuture::new(service.chain_length())
     }
 
-    fn get_headers(
+    fn tip_header(
         &mut self,
-        _request: Request<gen::node::GetBlocksRequest>,
-    ) -> Self::GetHeadersFuture {
-        unimplemented!()
+        _request: Request<gen::node::TipHeaderRequest>,
+    ) -> Self::TipHeaderFuture {
+        let service = match self.header_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        ResponseFuture::new(service.tip_header())
+    }
+
+    fn tip_headers(
+        &mut self,
+        req: Request<gen::node::TipHeadersRequest>,
+    ) -> Self::TipHeadersFuture {
+        let service = match self.header_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let count = cmp::min(req.get_ref().count, MAX_TIP_HEADERS);
+        ResponseFuture::new(service.tip_headers(count))
+    }
+
+    fn get_blocks(&mut self, req: Request<gen::node::GetBlocksRequest>) -> Self::GetBlocksFuture {
+        let service = match self.block_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let block_ids = match deserialize_vec(&req.get_ref().ids) {
+            Ok(block_ids) => block_ids,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        ResponseFuture::with_block_options(service.get_blocks(&block_ids), self.block_options)
+    }
+
+    fn get_block(&mut self, req: Request<gen::node::GetBlockRequest>) -> Self::GetBlockFuture {
+        let service = match self.block_service {
+            None => return GetBlockFuture::Failed(Status::with_code(Code::Unimplemented)),
+            Some(ref mut service) => service,
+        };
+        let block_id = match deserialize_bytes(&req.get_ref().id) {
+            Ok(block_id) => block_id,
+            Err(GrpcError(status)) => {
+                return GetBlockFuture::Failed(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        GetBlockFuture::Pending(service.get_blocks(&[block_id]), self.block_options)
+    }
+
+    fn get_blocks_pipelined(
+        &mut self,
+        req: Request<Streaming<gen::node::GetBlockRequest>>,
+    ) -> Self::GetBlocksPipelinedFuture {
+        let service = match self.block_service {
+            None => return future::err(GrpcError(Status::with_code(Code::Unimplemented))),
+            Some(ref service) => service.clone(),
+        };
+        let stream = PipelinedBlocks {
+            service,
+            requests: req.into_inner(),
+            options: self.block_options,
+            lookup: None,
+        };
+        future::ok(tower_grpc::Response::new(stream))
+    }
+
+    fn block_id_at_height(
+        &mut self,
+        req: Request<gen::node::BlockIdAtHeightRequest>,
+    ) -> Self::BlockIdAtHeightFuture {
+        let service = match self.block_service {
+            None => return GetBlockFuture::Failed(Status::with_code(Code::Unimplemented)),
+            Some(ref mut service) => service,
+        };
+        let future = service.get_blocks_by_height(req.get_ref().height, 1);
+        GetBlockFuture::Pending(future, self.block_options)
+    }
+
+    fn has_blocks(&mut self, req: Request<gen::node::GetBlocksRequest>) -> Self::HasBlocksFuture {
+        let service = match self.block_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let block_ids = match deserialize_vec(&req.get_ref().ids) {
+            Ok(block_ids) => block_ids,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        ResponseFuture::new(service.has_blocks(&block_ids))
+    }
+
+    fn get_block_range(
+        &mut self,
+        req: Request<gen::node::BlockRangeRequest>,
+    ) -> Self::GetBlockRangeFuture {
+        let service = match self.block_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let req = req.get_ref();
+        let count = cmp::min(req.count, MAX_BLOCK_RANGE);
+        let future = service.get_blocks_by_height(req.from_height, count);
+        ResponseFuture::with_block_options(future, self.block_options)
+    }
+
+    fn get_headers(&mut self, req: Request<gen::node::GetBlocksRequest>) -> Self::GetHeadersFuture {
+        let service = match self.header_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let block_ids = match deserialize_vec(&req.get_ref().ids) {
+            Ok(block_ids) => block_ids,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        ResponseFuture::new(service.get_headers(&block_ids))
     }
 
     fn pull_blocks_to_tip(
@@ -350,7 +869,110 @@ where
             }
             Err(e) => panic!("unexpected error {:?}", e),
         };
-        ResponseFuture::new(service.pull_blocks_to_tip(&block_ids))
+        // The request is failed when none of the blocks is known.
+        let future = service.pull_blocks_to_tip(&block_ids);
+        ResponseFuture::lookup(future, self.block_options)
+    }
+
+    fn pull_headers_to_tip(
+        &mut self,
+        req: Request<gen::node::PullBlocksToTipRequest>,
+    ) -> Self::PullHeadersToTipFuture {
+        let service = match self.header_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let block_ids = match deserialize_vec(&req.get_ref().from) {
+            Ok(block_ids) => block_ids,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        // The request is failed when none of the blocks is known.
+        let future = service.block_headers_to_tip(&block_ids);
+        ResponseFuture::lookup(future, BlockOptions::default())
+    }
+
+    fn pull_blocks(
+        &mut self,
+        req: Request<gen::node::PullBlocksRequest>,
+    ) -> Self::PullBlocksFuture {
+        let service = match self.block_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let from = match deserialize_vec(&req.get_ref().from) {
+            Ok(block_ids) => block_ids,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        let to = match deserialize_bytes(&req.get_ref().to) {
+            Ok(block_id) => block_id,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        // BlockError does not tell the causes apart, but the request
+        // is failed when `to` is unknown or not in range of `from`.
+        ResponseFuture::lookup(service.pull_blocks_to(&from, &to), self.block_options)
+    }
+
+    fn pull_blocks_from_tip(
+        &mut self,
+        req: Request<gen::node::PullBlocksFromTipRequest>,
+    ) -> Self::PullBlocksFromTipFuture {
+        let service = match self.block_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let to = match deserialize_vec(&req.get_ref().to) {
+            Ok(block_ids) => block_ids,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        ResponseFuture::with_block_options(service.pull_blocks_from_tip(&to), self.block_options)
+    }
+
+    fn pull_blocks_resume(
+        &mut self,
+        _request: Request<gen::node::PullBlocksResumeRequest>,
+    ) -> Self::PullBlocksResumeFuture {
+        // TODO: issue continuation tokens from the server-side
+        // block service
+        future::err(GrpcError(Status::with_code(Code::Unimplemented)))
+    }
+
+    fn block_subscription(
+        &mut self,
+        _request: Request<gen::node::BlockSubscriptionRequest>,
+    ) -> Self::BlockSubscriptionFuture {
+        let service = match self.block_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        ResponseFuture::with_block_options(service.block_subscription(), self.block_options)
+    }
+
+    fn push_headers(
+        &mut self,
+        _request: Request<Streaming<gen::node::Header>>,
+    ) -> Self::PushHeadersFuture {
+        // TODO: add a server-side abstraction for received headers
+        ResponseFuture::unimplemented()
+    }
+
+    fn upload_blocks(
+        &mut self,
+        _request: Request<Streaming<gen::node::Block>>,
+    ) -> Self::UploadBlocksFuture {
+        // TODO: add a server-side abstraction for received blocks
+        ResponseFuture::unimplemented()
     }
 
     fn propose_transactions(
@@ -374,4 +996,116 @@ where
         };
         unimplemented!()
     }
+
+    fn submit_transactions(
+        &mut self,
+        req: Request<gen::node::SubmitTransactionsRequest>,
+    ) -> Self::SubmitTransactionsFuture {
+        let service = match self.tx_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let txs = match deserialize_vec(&req.get_ref().transactions) {
+            Ok(txs) => txs,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        ResponseFuture::new(service.submit_transactions(&txs))
+    }
+
+    fn upload_transactions(
+        &mut self,
+        _request: Request<Streaming<gen::node::Transaction>>,
+    ) -> Self::UploadTransactionsFuture {
+        // TODO: add a server-side abstraction for received transactions
+        ResponseFuture::unimplemented()
+    }
+
+    fn transaction_status_subscription(
+        &mut self,
+        _request: Request<gen::node::TransactionStatusSubscriptionRequest>,
+    ) -> Self::TransactionStatusSubscriptionFuture {
+        // TODO: add transaction status tracking to the server-side
+        // transaction service
+        future::err(GrpcError(Status::with_code(Code::Unimplemented)))
+    }
+
+    fn mempool_stats(
+        &mut self,
+        _request: Request<gen::node::MempoolStatsRequest>,
+    ) -> Self::MempoolStatsFuture {
+        // TODO: add the mempool statistics to the server-side transaction service
+        ResponseFuture::unimplemented()
+    }
+
+    fn get_transactions(
+        &mut self,
+        req: Request<gen::node::GetTransactionsRequest>,
+    ) -> Self::GetTransactionsFuture {
+        let service = match self.tx_service {
+            None => return ResponseFuture::unimplemented(),
+            Some(ref mut service) => service,
+        };
+        let tx_ids = match deserialize_vec(&req.get_ref().ids) {
+            Ok(tx_ids) => tx_ids,
+            Err(GrpcError(status)) => {
+                return ResponseFuture::error(status);
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+        ResponseFuture::new(service.get_transactions(&tx_ids))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeService;
+    use crate::gen::{self, node::server::Node as _};
+    use crate::server::BlockOptions;
+    use crate::test_types::StubNode;
+
+    use futures::prelude::*;
+    use tower_grpc::{Code, Request};
+
+    #[test]
+    fn get_blocks_streams_in_request_order_up_to_an_unknown_block() {
+        let mut service = NodeService::new(StubNode::new(vec![1, 2, 3]));
+        let ids = [3u64, 1, 7, 2]
+            .iter()
+            .map(|height| height.to_be_bytes().to_vec())
+            .collect();
+        let req = Request::new(gen::node::GetBlocksRequest { ids });
+        let mut blocks = service.get_blocks(req).wait().unwrap().into_inner().wait();
+        for &height in &[3u64, 1] {
+            let block = blocks.next().unwrap().unwrap();
+            assert_eq!(block.id, height.to_be_bytes().to_vec());
+        }
+        match blocks.next() {
+            Some(Err(tower_grpc::Error::Grpc(ref status))) => match status.code() {
+                Code::Unknown => {}
+                code => panic!("unexpected status code {:?}", code),
+            },
+            res => panic!("unexpected stream item {:?}", res),
+        }
+    }
+
+    #[test]
+    fn blocks_are_sent_without_ids_or_checksums_if_disabled() {
+        let get_block = |options| {
+            let mut service = NodeService::with_block_options(StubNode::new(vec![1]), options);
+            let ids = vec![1u64.to_be_bytes().to_vec()];
+            let req = Request::new(gen::node::GetBlocksRequest { ids });
+            let blocks = service.get_blocks(req).wait().unwrap().into_inner();
+            blocks.wait().next().unwrap().unwrap()
+        };
+        let block = get_block(BlockOptions::default());
+        assert_eq!(block.id, 1u64.to_be_bytes().to_vec());
+        assert_eq!(block.checksum.len(), 4);
+        let block = get_block(*BlockOptions::new().send_ids(false).send_checksums(false));
+        assert!(block.id.is_empty());
+        assert!(block.checksum.is_empty());
+        assert_eq!(block.content, 1u64.to_be_bytes().to_vec());
+    }
 }
@@ -0,0 +1,426 @@
+// Block types and a stub node shared by the tests of the client
+// and the server.
+
+use crate::{peer::TcpPeer, server::Server};
+
+use chain_core::property::{self, Deserialize, Serialize};
+use network_core::{
+    codes::TransactionAcceptance,
+    peer::{PeerInfo, ProtocolVersion},
+    server::{
+        block::{BlockError, BlockService, HeaderService},
+        transaction::{
+            ProposeTransactionsResponse, RecordTransactionResponse, TransactionError,
+            TransactionService,
+        },
+        Node,
+    },
+};
+
+use futures::{future, prelude::*, stream};
+use tokio::executor::DefaultExecutor;
+use tokio::net::TcpListener;
+use tokio::runtime::current_thread::{self, Runtime};
+
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+    num::ParseIntError,
+    str::FromStr,
+    vec,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TestId(pub u64);
+
+impl Serialize for TestId {
+    type Error = io::Error;
+
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), io::Error> {
+        writer.write_all(&self.0.to_be_bytes())
+    }
+}
+
+impl Deserialize for TestId {
+    type Error = io::Error;
+
+    fn deserialize<R: BufRead>(mut reader: R) -> Result<Self, io::Error> {
+        let mut buf = [0; 8];
+        reader.read_exact(&mut buf)?;
+        Ok(TestId(u64::from_be_bytes(buf)))
+    }
+}
+
+impl property::BlockId for TestId {}
+
+impl property::TransactionId for TestId {}
+
+// A date in the EPOCH.SLOT format of the wire protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TestDate {
+    pub epoch: u64,
+    pub slot: u64,
+}
+
+impl property::BlockDate for TestDate {
+    fn from_epoch_slot_id(epoch: u64, slot: u64) -> Self {
+        TestDate { epoch, slot }
+    }
+}
+
+impl FromStr for TestDate {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIntError> {
+        let mut parts = s.splitn(2, '.');
+        let epoch = parts.next().unwrap().parse()?;
+        let slot = match parts.next() {
+            Some(slot) => slot.parse()?,
+            None => 0,
+        };
+        Ok(TestDate { epoch, slot })
+    }
+}
+
+impl fmt::Display for TestDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.epoch, self.slot)
+    }
+}
+
+// A block at a given height, identified by its height,
+// in slot of the same number in epoch 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestBlock(pub u64);
+
+impl Serialize for TestBlock {
+    type Error = io::Error;
+
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), io::Error> {
+        TestId(self.0).serialize(writer)
+    }
+}
+
+impl Deserialize for TestBlock {
+    type Error = io::Error;
+
+    fn deserialize<R: BufRead>(reader: R) -> Result<Self, io::Error> {
+        TestId::deserialize(reader).map(|id| TestBlock(id.0))
+    }
+}
+
+// The header of a `TestBlock`, carrying the height of the block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestHeader(pub u64);
+
+impl Serialize for TestHeader {
+    type Error = io::Error;
+
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), io::Error> {
+        TestId(self.0).serialize(writer)
+    }
+}
+
+impl Deserialize for TestHeader {
+    type Error = io::Error;
+
+    fn deserialize<R: BufRead>(reader: R) -> Result<Self, io::Error> {
+        TestId::deserialize(reader).map(|id| TestHeader(id.0))
+    }
+}
+
+impl property::Header for TestHeader {}
+
+impl property::HasHeader for TestBlock {
+    type Header = TestHeader;
+
+    fn header(&self) -> TestHeader {
+        TestHeader(self.0)
+    }
+}
+
+// A transaction with no inputs or outputs, identified by its number.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestTx(pub u64);
+
+impl Serialize for TestTx {
+    type Error = io::Error;
+
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), io::Error> {
+        TestId(self.0).serialize(writer)
+    }
+}
+
+impl Deserialize for TestTx {
+    type Error = io::Error;
+
+    fn deserialize<R: BufRead>(reader: R) -> Result<Self, io::Error> {
+        TestId::deserialize(reader).map(|id| TestTx(id.0))
+    }
+}
+
+impl property::Transaction for TestTx {
+    type Input = ();
+    type Output = ();
+    type Id = TestId;
+
+    fn inputs<'a>(&'a self) -> std::slice::Iter<'a, ()> {
+        let none: &'static [()] = &[];
+        none.iter()
+    }
+
+    fn outputs<'a>(&'a self) -> std::slice::Iter<'a, ()> {
+        let none: &'static [()] = &[];
+        none.iter()
+    }
+
+    fn id(&self) -> TestId {
+        TestId(self.0)
+    }
+}
+
+impl property::Block for TestBlock {
+    type Id = TestId;
+    type Date = TestDate;
+
+    fn id(&self) -> TestId {
+        TestId(self.0)
+    }
+
+    fn parent_id(&self) -> TestId {
+        TestId(self.0.saturating_sub(1))
+    }
+
+    fn date(&self) -> TestDate {
+        TestDate {
+            epoch: 0,
+            slot: self.0,
+        }
+    }
+}
+
+// Serves the node on a loopback TCP socket with tasks run by `rt`,
+// returning the peer to connect to.
+pub fn serve(rt: &mut Runtime, node: StubNode) -> TcpPeer {
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let peer = TcpPeer::new(listener.local_addr().unwrap());
+    let mut server = Server::new(node, DefaultExecutor::current());
+    let accept = listener
+        .incoming()
+        .map_err(|e| panic!("accept error: {}", e))
+        .for_each(move |sock| {
+            // The connection fails when the client goes away
+            // at the end of a test.
+            current_thread::spawn(server.serve(sock).map_err(|_| ()));
+            Ok(())
+        });
+    rt.spawn(accept);
+    peer
+}
+
+type BlockStream = stream::IterResult<vec::IntoIter<Result<TestBlock, BlockError>>>;
+
+type SubscriptionStream = Box<dyn Stream<Item = TestBlock, Error = BlockError> + Send>;
+
+// A node whose block service knows the blocks at the given heights,
+// the last of them being the tip, whose header service fails
+// or streams nothing, and which serves no transactions.
+#[derive(Clone)]
+pub struct StubNode {
+    heights: Vec<u64>,
+}
+
+impl StubNode {
+    pub fn new(heights: Vec<u64>) -> Self {
+        assert!(!heights.is_empty(), "the chain should not be empty");
+        StubNode { heights }
+    }
+
+    // Streams the known blocks following the latest of the `from`
+    // blocks that precedes the block at height `to`, up to and
+    // including the latter. An empty `from` list starts the stream
+    // at the first known block. Fails if `to` is unknown
+    // or none of the `from` blocks precedes it.
+    fn pull(&self, from: &[TestId], to: u64) -> future::FutureResult<BlockStream, BlockError> {
+        if !self.heights.contains(&to) {
+            return future::err(BlockError());
+        }
+        let start = if from.is_empty() {
+            None
+        } else {
+            let known = from
+                .iter()
+                .map(|id| id.0)
+                .filter(|height| *height <= to && self.heights.contains(height))
+                .max();
+            match known {
+                Some(height) => Some(height),
+                None => return future::err(BlockError()),
+            }
+        };
+        let blocks = self
+            .heights
+            .iter()
+            .filter(|&&height| start.map_or(true, |start| height > start) && height <= to)
+            .map(|&height| Ok(TestBlock(height)))
+            .collect::<Vec<_>>();
+        future::ok(stream::iter_result(blocks))
+    }
+}
+
+impl Node for StubNode {
+    type BlockService = StubNode;
+    type HeaderService = StubHeaders;
+    type TransactionService = StubTransactions;
+
+    fn block_service(&self) -> Option<StubNode> {
+        Some(self.clone())
+    }
+
+    fn header_service(&self) -> Option<StubHeaders> {
+        Some(StubHeaders)
+    }
+
+    fn transaction_service(&self) -> Option<StubTransactions> {
+        None
+    }
+
+    fn peer_info(&self) -> PeerInfo<TestId> {
+        PeerInfo {
+            node_id: Vec::new(),
+            protocol_version: ProtocolVersion::new(0, 1),
+            genesis_hash: TestId(0),
+        }
+    }
+}
+
+impl BlockService for StubNode {
+    type BlockId = TestId;
+    type BlockDate = TestDate;
+    type Block = TestBlock;
+    type TipFuture = future::FutureResult<(TestId, TestDate), BlockError>;
+    type ChainLengthFuture = future::FutureResult<u64, BlockError>;
+    type GetBlocksStream = BlockStream;
+    type GetBlocksFuture = future::FutureResult<BlockStream, BlockError>;
+    type HasBlocksFuture = future::FutureResult<Vec<bool>, BlockError>;
+    type PullBlocksToTipStream = BlockStream;
+    type PullBlocksFuture = future::FutureResult<BlockStream, BlockError>;
+    type BlockSubscriptionStream = SubscriptionStream;
+    type BlockSubscriptionFuture = future::FutureResult<SubscriptionStream, BlockError>;
+
+    fn tip(&mut self) -> Self::TipFuture {
+        let tip = TestBlock(*self.heights.last().unwrap());
+        future::ok((property::Block::id(&tip), property::Block::date(&tip)))
+    }
+
+    fn chain_length(&mut self) -> Self::ChainLengthFuture {
+        future::ok(*self.heights.last().unwrap())
+    }
+
+    // Streams the known blocks in the order of the identifiers,
+    // failing the stream at the first unknown one.
+    fn get_blocks(&mut self, ids: &[TestId]) -> Self::GetBlocksFuture {
+        let mut blocks = Vec::new();
+        for id in ids {
+            if self.heights.contains(&id.0) {
+                blocks.push(Ok(TestBlock(id.0)));
+            } else {
+                blocks.push(Err(BlockError()));
+                break;
+            }
+        }
+        future::ok(stream::iter_result(blocks))
+    }
+
+    // The requests that the tests do not need fail, or get
+    // an empty stream, rather than panic in the server task.
+
+    fn get_blocks_by_height(&mut self, _: u64, _: u32) -> Self::GetBlocksFuture {
+        future::ok(stream::iter_result(Vec::new()))
+    }
+
+    fn has_blocks(&mut self, ids: &[TestId]) -> Self::HasBlocksFuture {
+        future::ok(ids.iter().map(|id| self.heights.contains(&id.0)).collect())
+    }
+
+    fn pull_blocks_to_tip(&mut self, from: &[TestId]) -> Self::PullBlocksFuture {
+        self.pull(from, *self.heights.last().unwrap())
+    }
+
+    fn pull_blocks_to(&mut self, from: &[TestId], to: &TestId) -> Self::PullBlocksFuture {
+        self.pull(from, to.0)
+    }
+
+    fn pull_blocks_from_tip(&mut self, _: &[TestId]) -> Self::PullBlocksFuture {
+        future::ok(stream::iter_result(Vec::new()))
+    }
+
+    // Pushes all of the known blocks, then keeps the subscription open
+    // without ever sending more.
+    fn block_subscription(&mut self) -> Self::BlockSubscriptionFuture {
+        let blocks = self.heights.iter().map(|&height| TestBlock(height));
+        let pending = stream::poll_fn(|| Ok(Async::NotReady));
+        let stream = stream::iter_ok(blocks.collect::<Vec<_>>()).chain(pending);
+        future::ok(Box::new(stream))
+    }
+}
+
+#[derive(Clone)]
+pub struct StubHeaders;
+
+impl HeaderService for StubHeaders {
+    type Header = TestHeader;
+    type HeaderId = TestId;
+    type GetHeadersStream = stream::Empty<TestHeader, BlockError>;
+    type GetHeadersFuture = future::FutureResult<Self::GetHeadersStream, BlockError>;
+    type TipHeaderFuture = future::FutureResult<TestHeader, BlockError>;
+
+    fn tip_header(&mut self) -> Self::TipHeaderFuture {
+        future::err(BlockError())
+    }
+
+    fn tip_headers(&mut self, _: u32) -> Self::GetHeadersFuture {
+        future::ok(stream::empty())
+    }
+
+    fn get_headers(&mut self, _: &[TestId]) -> Self::GetHeadersFuture {
+        future::ok(stream::empty())
+    }
+
+    fn block_headers(&mut self, _: &[TestId], _: &TestId) -> Self::GetHeadersFuture {
+        future::ok(stream::empty())
+    }
+
+    fn block_headers_to_tip(&mut self, _: &[TestId]) -> Self::GetHeadersFuture {
+        future::ok(stream::empty())
+    }
+}
+
+#[derive(Clone)]
+pub struct StubTransactions;
+
+impl TransactionService for StubTransactions {
+    type TransactionId = TestId;
+    type Transaction = TestTx;
+    type ProposeTransactionsFuture =
+        future::FutureResult<ProposeTransactionsResponse<TestId>, TransactionError>;
+    type RecordTransactionFuture =
+        future::FutureResult<RecordTransactionResponse<TestId>, TransactionError>;
+    type SubmitTransactionsFuture =
+        future::FutureResult<Vec<TransactionAcceptance>, TransactionError>;
+    type GetTransactionsStream = stream::Empty<TestTx, TransactionError>;
+    type GetTransactionsFuture =
+        future::FutureResult<Self::GetTransactionsStream, TransactionError>;
+
+    fn propose_transactions(&mut self, _: &[TestId]) -> Self::ProposeTransactionsFuture {
+        future::err(TransactionError())
+    }
+
+    fn submit_transactions(&mut self, _: &[TestTx]) -> Self::SubmitTransactionsFuture {
+        future::err(TransactionError())
+    }
+
+    fn get_transactions(&mut self, _: &[TestId]) -> Self::GetTransactionsFuture {
+        future::ok(stream::empty())
+    }
+}